@@ -0,0 +1,233 @@
+//! gcloud accounts and configurations
+//!
+//! Lets one session work with several identities at once (e.g. a client's
+//! project and our own) by running each gcloud call with an explicit
+//! `--account` / `--configuration` instead of whatever is active globally.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use tokio::process::Command as TokioCommand;
+use tokio::time::{timeout, Duration};
+
+use crate::validation::{validate_account, validate_configuration_name};
+
+/// Which gcloud identity an operation runs as
+///
+/// The default (both `None`) means "whatever gcloud has active", which is the
+/// historical behaviour.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GcloudContext {
+    pub account: Option<String>,
+    pub configuration: Option<String>,
+}
+
+impl GcloudContext {
+    pub fn for_account(account: &str) -> Self {
+        Self { account: Some(account.to_string()), configuration: None }
+    }
+
+    pub fn for_configuration(configuration: &str) -> Self {
+        Self { account: None, configuration: Some(configuration.to_string()) }
+    }
+
+    /// True when no account or configuration is pinned
+    pub fn is_active_default(&self) -> bool {
+        self.account.is_none() && self.configuration.is_none()
+    }
+
+    /// SECURITY: Must be called before the values reach a command line
+    pub fn validate(&self) -> Result<()> {
+        if let Some(account) = &self.account {
+            validate_account(account)?;
+        }
+        if let Some(configuration) = &self.configuration {
+            validate_configuration_name(configuration)?;
+        }
+        Ok(())
+    }
+
+    /// Global gcloud flags selecting this identity
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(configuration) = &self.configuration {
+            args.push(format!("--configuration={}", configuration));
+        }
+        if let Some(account) = &self.account {
+            args.push(format!("--account={}", account));
+        }
+        args
+    }
+
+    /// Human readable label for logs and the UI
+    pub fn label(&self) -> String {
+        match (&self.account, &self.configuration) {
+            (Some(account), Some(configuration)) => format!("{} ({})", account, configuration),
+            (Some(account), None) => account.clone(),
+            (None, Some(configuration)) => format!("configuration {}", configuration),
+            (None, None) => "active gcloud account".to_string(),
+        }
+    }
+}
+
+/// An account known to `gcloud auth list`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcloudAccount {
+    pub account: String,
+    /// "ACTIVE" for the active account, empty otherwise
+    #[serde(default)]
+    pub status: String,
+}
+
+impl GcloudAccount {
+    pub fn is_active(&self) -> bool {
+        self.status == "ACTIVE"
+    }
+}
+
+/// A named gcloud configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GcloudConfiguration {
+    pub name: String,
+    pub is_active: bool,
+    pub account: Option<String>,
+    pub project: Option<String>,
+}
+
+/// Raw `gcloud config configurations list --format=json` entry
+#[derive(Deserialize)]
+struct RawConfiguration {
+    name: String,
+    #[serde(default)]
+    is_active: bool,
+    #[serde(default)]
+    properties: serde_json::Value,
+}
+
+impl From<RawConfiguration> for GcloudConfiguration {
+    fn from(raw: RawConfiguration) -> Self {
+        let core = &raw.properties["core"];
+        Self {
+            name: raw.name,
+            is_active: raw.is_active,
+            account: core["account"].as_str().map(str::to_string),
+            project: core["project"].as_str().map(str::to_string),
+        }
+    }
+}
+
+/// Accounts with stored credentials
+pub async fn list_accounts_async() -> Result<Vec<GcloudAccount>> {
+    let output = timeout(
+        Duration::from_secs(10),
+        TokioCommand::new("gcloud")
+            .args(["auth", "list", "--format=json"])
+            .output()
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud auth list took longer than 10 seconds"))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("gcloud error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| anyhow!("Failed to parse accounts JSON: {}", e))
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn list_accounts() -> Result<Vec<GcloudAccount>> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
+    rt.block_on(list_accounts_async())
+}
+
+/// Named configurations with their account and default project
+pub async fn list_configurations_async() -> Result<Vec<GcloudConfiguration>> {
+    let output = timeout(
+        Duration::from_secs(10),
+        TokioCommand::new("gcloud")
+            .args(["config", "configurations", "list", "--format=json"])
+            .output()
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud config configurations list took longer than 10 seconds"))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("gcloud error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    parse_configurations(&output.stdout)
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn list_configurations() -> Result<Vec<GcloudConfiguration>> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
+    rt.block_on(list_configurations_async())
+}
+
+fn parse_configurations(json: &[u8]) -> Result<Vec<GcloudConfiguration>> {
+    let raw: Vec<RawConfiguration> = serde_json::from_slice(json)
+        .map_err(|e| anyhow!("Failed to parse configurations JSON: {}", e))?;
+    Ok(raw.into_iter().map(GcloudConfiguration::from).collect())
+}
+
+/// Revoke the credentials of a single account, leaving the others logged in
+pub fn revoke_account(account: &str) -> Result<()> {
+    // SECURITY: Validate before passing to gcloud
+    validate_account(account)?;
+
+    tracing::info!(account = account, "Revoking gcloud credentials for account");
+
+    let output = Command::new("gcloud")
+        .args(["auth", "revoke", account, "--quiet"])
+        .output()
+        .map_err(|e| anyhow!("Failed to execute gcloud revoke: {}", e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Failed to revoke {}: {}",
+            account,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_args_and_validation() {
+        assert!(GcloudContext::default().args().is_empty());
+
+        let ctx = GcloudContext {
+            account: Some("ops@example.com".to_string()),
+            configuration: Some("client-a".to_string()),
+        };
+        assert!(ctx.validate().is_ok());
+        assert_eq!(ctx.args(), vec!["--configuration=client-a", "--account=ops@example.com"]);
+
+        assert!(GcloudContext::for_account("ops@example.com; rm -rf /").validate().is_err());
+        assert!(GcloudContext::for_configuration("--impersonate").validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_configurations() {
+        let json = br#"[
+            {"is_active": true, "name": "default",
+             "properties": {"core": {"account": "me@example.com", "project": "our-project"}}},
+            {"is_active": false, "name": "client-a", "properties": {"core": {"account": "sa@client.iam.gserviceaccount.com"}}}
+        ]"#;
+        let configs = parse_configurations(json).unwrap();
+        assert_eq!(configs.len(), 2);
+        assert!(configs[0].is_active);
+        assert_eq!(configs[0].project.as_deref(), Some("our-project"));
+        assert_eq!(configs[1].account.as_deref(), Some("sa@client.iam.gserviceaccount.com"));
+        assert_eq!(configs[1].project, None);
+    }
+}
@@ -0,0 +1,821 @@
+//! Unified cloud backend
+//!
+//! One async trait over the two ways this app talks to GCP: shelling out to
+//! the gcloud CLI (`gcloud.rs`) and calling the REST APIs directly
+//! (`gcloud_client_poc.rs`). Callers pick a backend once with
+//! [`backend_for`] instead of branching on the API method at every call.
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+use crate::accounts::GcloudContext;
+use crate::gcloud::{self, GcpInstance, GcpProject};
+use crate::gcloud_client_poc::{ComputeEngineClient, GcpAuthClient, ResourceManagerClient};
+use crate::instance_details::InstanceDetails;
+use crate::inventory::InstanceFilter;
+use crate::machine_types::{offline_spec, parse_custom, MachineSpec};
+use crate::pagination::{ListOptions, PageSender};
+use crate::runtime;
+use crate::validation::{validate_instance_name, validate_machine_type, validate_project_id, validate_zone};
+
+/// How the app reaches GCP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiMethod {
+    GcloudCli,
+    ClientLibrary,
+}
+
+/// Lifecycle operations common to every backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InstanceAction {
+    Start,
+    Stop,
+    Reset,
+    Suspend,
+    Resume,
+}
+
+impl InstanceAction {
+    /// REST verb / gcloud subcommand for this action
+    pub fn verb(&self) -> &'static str {
+        match self {
+            InstanceAction::Start => "start",
+            InstanceAction::Stop => "stop",
+            InstanceAction::Reset => "reset",
+            InstanceAction::Suspend => "suspend",
+            InstanceAction::Resume => "resume",
+        }
+    }
+}
+
+#[async_trait]
+pub trait CloudBackend: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    async fn list_projects(&self) -> Result<Vec<GcpProject>>;
+
+    async fn list_instances(&self, project: &str) -> Result<Vec<GcpInstance>>;
+
+    /// List projects, sending each page to `pages` as it arrives
+    ///
+    /// Backends without real pagination deliver everything as one page.
+    async fn list_projects_paged(
+        &self,
+        _options: &ListOptions,
+        pages: Option<&PageSender<GcpProject>>,
+    ) -> Result<Vec<GcpProject>> {
+        let projects = self.list_projects().await?;
+        send_single_page(pages, &projects);
+        Ok(projects)
+    }
+
+    /// List instances, sending each page to `pages` as it arrives
+    async fn list_instances_paged(
+        &self,
+        project: &str,
+        _options: &ListOptions,
+        pages: Option<&PageSender<GcpInstance>>,
+    ) -> Result<Vec<GcpInstance>> {
+        let instances = self.list_instances(project).await?;
+        send_single_page(pages, &instances);
+        Ok(instances)
+    }
+
+    /// List the instances matching `filter`, server-side where the backend can
+    ///
+    /// The default lists everything and filters locally, which cannot see
+    /// labels.
+    async fn list_instances_filtered(
+        &self,
+        project: &str,
+        filter: &InstanceFilter,
+        options: &ListOptions,
+    ) -> Result<Vec<GcpInstance>> {
+        if !filter.labels.is_empty() {
+            return Err(anyhow!("The {} backend cannot filter on labels", self.name()));
+        }
+        let mut instances = self.list_instances_paged(project, options, None).await?;
+        instances.retain(|i| filter.matches(i));
+        Ok(instances)
+    }
+
+    async fn describe_instance(&self, project: &str, zone: &str, instance: &str) -> Result<GcpInstance>;
+
+    /// Full description: network, labels, identity, scheduling, disks...
+    async fn describe_instance_details(&self, project: &str, zone: &str, instance: &str) -> Result<InstanceDetails>;
+
+    async fn start_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()>;
+
+    async fn stop_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()>;
+
+    async fn reset_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()>;
+
+    /// RUNNING -> SUSPENDED, keeping memory on disk
+    async fn suspend_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()>;
+
+    /// SUSPENDED -> RUNNING
+    async fn resume_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()>;
+
+    /// Change the machine type of a TERMINATED instance
+    async fn set_machine_type(&self, project: &str, zone: &str, instance: &str, machine_type: &str) -> Result<()>;
+
+    async fn set_deletion_protection(&self, project: &str, zone: &str, instance: &str, enabled: bool) -> Result<()>;
+
+    /// Spec of `machine_type` if `zone` offers it
+    async fn machine_type_in_zone(&self, project: &str, zone: &str, machine_type: &str) -> Result<Option<MachineSpec>>;
+
+    /// Resize: stop (if running) -> setMachineType -> start again
+    ///
+    /// The target type is checked against the zone first (custom shapes are
+    /// only checked for a well-formed name). If setMachineType fails the
+    /// instance is started again with its old type.
+    async fn resize_instance(&self, project: &str, zone: &str, instance: &str, machine_type: &str) -> Result<ResizeOutcome> {
+        validate_target(project, zone, instance)?;
+        validate_machine_type(machine_type)?;
+
+        let current = self.describe_instance(project, zone, instance).await?;
+        let mut outcome = ResizeOutcome {
+            previous_machine_type: current.machine_type.clone(),
+            machine_type: machine_type.to_string(),
+            changed: false,
+            restarted: false,
+        };
+        if current.machine_type == machine_type {
+            return Ok(outcome);
+        }
+
+        if parse_custom(machine_type).is_none() && self.machine_type_in_zone(project, zone, machine_type).await?.is_none() {
+            return Err(anyhow!("Machine type '{}' is not available in zone {}", machine_type, zone));
+        }
+
+        let was_running = match current.status.as_str() {
+            "RUNNING" => true,
+            "TERMINATED" => false,
+            other => {
+                return Err(anyhow!(
+                    "Instance '{}' is {}; resizing needs it RUNNING or TERMINATED",
+                    instance,
+                    other
+                ))
+            }
+        };
+
+        tracing::info!(
+            instance = instance,
+            from = %current.machine_type,
+            to = machine_type,
+            restart = was_running,
+            "Resizing instance"
+        );
+
+        if was_running {
+            self.stop_instance(project, zone, instance).await?;
+        }
+
+        if let Err(e) = self.set_machine_type(project, zone, instance, machine_type).await {
+            if was_running {
+                if let Err(start_err) = self.start_instance(project, zone, instance).await {
+                    tracing::error!(instance = instance, error = %start_err, "Could not restart instance after failed resize");
+                }
+            }
+            return Err(anyhow!("Failed to change machine type of '{}': {}", instance, e));
+        }
+        outcome.changed = true;
+
+        if was_running {
+            self.start_instance(project, zone, instance).await?;
+            outcome.restarted = true;
+        }
+        Ok(outcome)
+    }
+
+    /// Dispatch a lifecycle action
+    async fn run_action(&self, action: InstanceAction, project: &str, zone: &str, instance: &str) -> Result<()> {
+        match action {
+            InstanceAction::Start => self.start_instance(project, zone, instance).await,
+            InstanceAction::Stop => self.stop_instance(project, zone, instance).await,
+            InstanceAction::Reset => self.reset_instance(project, zone, instance).await,
+            InstanceAction::Suspend => self.suspend_instance(project, zone, instance).await,
+            InstanceAction::Resume => self.resume_instance(project, zone, instance).await,
+        }
+    }
+}
+
+fn send_single_page<T: Clone>(pages: Option<&PageSender<T>>, items: &[T]) {
+    if let Some(tx) = pages {
+        if !items.is_empty() {
+            let _ = tx.send(items.to_vec());
+        }
+    }
+}
+
+/// Result of [`CloudBackend::resize_instance`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResizeOutcome {
+    pub previous_machine_type: String,
+    pub machine_type: String,
+    /// False when the instance already had the requested type
+    pub changed: bool,
+    /// The instance was running and has been started again
+    pub restarted: bool,
+}
+
+/// Backend that shells out to the gcloud CLI
+#[derive(Default)]
+pub struct GcloudCliBackend {
+    context: GcloudContext,
+}
+
+impl GcloudCliBackend {
+    /// Run every command as the given account/configuration
+    pub fn new(context: GcloudContext) -> Self {
+        Self { context }
+    }
+}
+
+#[async_trait]
+impl CloudBackend for GcloudCliBackend {
+    fn name(&self) -> &'static str {
+        "gcloud-cli"
+    }
+
+    async fn list_projects(&self) -> Result<Vec<GcpProject>> {
+        gcloud::get_projects_async(&self.context).await
+    }
+
+    async fn list_instances(&self, project: &str) -> Result<Vec<GcpInstance>> {
+        gcloud::get_instances_async(&self.context, project).await
+    }
+
+    async fn list_projects_paged(
+        &self,
+        options: &ListOptions,
+        pages: Option<&PageSender<GcpProject>>,
+    ) -> Result<Vec<GcpProject>> {
+        let projects = gcloud::get_projects_paged_async(&self.context, options).await?;
+        send_single_page(pages, &projects);
+        Ok(projects)
+    }
+
+    async fn list_instances_paged(
+        &self,
+        project: &str,
+        options: &ListOptions,
+        pages: Option<&PageSender<GcpInstance>>,
+    ) -> Result<Vec<GcpInstance>> {
+        let instances = gcloud::get_instances_paged_async(&self.context, project, options).await?;
+        send_single_page(pages, &instances);
+        Ok(instances)
+    }
+
+    async fn list_instances_filtered(
+        &self,
+        project: &str,
+        filter: &InstanceFilter,
+        options: &ListOptions,
+    ) -> Result<Vec<GcpInstance>> {
+        gcloud::get_instances_filtered_async(&self.context, project, filter, options).await
+    }
+
+    async fn describe_instance(&self, project: &str, zone: &str, instance: &str) -> Result<GcpInstance> {
+        gcloud::describe_instance_async(&self.context, project, zone, instance).await
+    }
+
+    async fn describe_instance_details(&self, project: &str, zone: &str, instance: &str) -> Result<InstanceDetails> {
+        gcloud::describe_instance_details_async(&self.context, project, zone, instance).await
+    }
+
+    async fn start_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        gcloud::start_instance_async(&self.context, project, zone, instance).await
+    }
+
+    async fn stop_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        gcloud::stop_instance_async(&self.context, project, zone, instance).await
+    }
+
+    async fn reset_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        gcloud::reset_instance_async(&self.context, project, zone, instance).await
+    }
+
+    async fn suspend_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        gcloud::suspend_instance_async(&self.context, project, zone, instance).await
+    }
+
+    async fn resume_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        gcloud::resume_instance_async(&self.context, project, zone, instance).await
+    }
+
+    async fn set_machine_type(&self, project: &str, zone: &str, instance: &str, machine_type: &str) -> Result<()> {
+        gcloud::set_machine_type_async(&self.context, project, zone, instance, machine_type).await
+    }
+
+    async fn set_deletion_protection(&self, project: &str, zone: &str, instance: &str, enabled: bool) -> Result<()> {
+        gcloud::set_deletion_protection_async(&self.context, project, zone, instance, enabled).await
+    }
+
+    async fn machine_type_in_zone(&self, project: &str, zone: &str, machine_type: &str) -> Result<Option<MachineSpec>> {
+        gcloud::machine_type_in_zone_async(&self.context, project, zone, machine_type).await
+    }
+}
+
+/// Backend that calls the Resource Manager and Compute REST APIs
+pub struct RestBackend {
+    resource_manager: ResourceManagerClient,
+    compute: ComputeEngineClient,
+}
+
+impl RestBackend {
+    pub async fn new() -> Result<Self> {
+        Self::for_context(&GcloudContext::default()).await
+    }
+
+    /// Use the credentials matching a gcloud account/configuration
+    pub async fn for_context(context: &GcloudContext) -> Result<Self> {
+        let auth = GcpAuthClient::for_context(context).await?;
+        Ok(Self {
+            resource_manager: ResourceManagerClient::with_auth(auth.clone()),
+            compute: ComputeEngineClient::with_auth(auth),
+        })
+    }
+}
+
+#[async_trait]
+impl CloudBackend for RestBackend {
+    fn name(&self) -> &'static str {
+        "rest"
+    }
+
+    async fn list_projects(&self) -> Result<Vec<GcpProject>> {
+        self.resource_manager.list_projects().await
+    }
+
+    async fn list_instances(&self, project: &str) -> Result<Vec<GcpInstance>> {
+        validate_project_id(project)?;
+        self.compute.list_instances(project).await
+    }
+
+    async fn list_projects_paged(
+        &self,
+        options: &ListOptions,
+        pages: Option<&PageSender<GcpProject>>,
+    ) -> Result<Vec<GcpProject>> {
+        self.resource_manager.list_projects_paged(options, pages).await
+    }
+
+    async fn list_instances_paged(
+        &self,
+        project: &str,
+        options: &ListOptions,
+        pages: Option<&PageSender<GcpInstance>>,
+    ) -> Result<Vec<GcpInstance>> {
+        validate_project_id(project)?;
+        self.compute.list_instances_paged(project, options, pages).await
+    }
+
+    async fn list_instances_filtered(
+        &self,
+        project: &str,
+        filter: &InstanceFilter,
+        options: &ListOptions,
+    ) -> Result<Vec<GcpInstance>> {
+        validate_project_id(project)?;
+        self.compute.list_instances_filtered(project, filter, options, None).await
+    }
+
+    async fn describe_instance(&self, project: &str, zone: &str, instance: &str) -> Result<GcpInstance> {
+        validate_target(project, zone, instance)?;
+        self.compute.describe_instance(project, zone, instance).await
+    }
+
+    async fn describe_instance_details(&self, project: &str, zone: &str, instance: &str) -> Result<InstanceDetails> {
+        validate_target(project, zone, instance)?;
+        self.compute.describe_instance_details(project, zone, instance).await
+    }
+
+    async fn start_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        validate_target(project, zone, instance)?;
+        self.compute.start_instance(project, zone, instance).await
+    }
+
+    async fn stop_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        validate_target(project, zone, instance)?;
+        self.compute.stop_instance(project, zone, instance).await
+    }
+
+    async fn reset_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        validate_target(project, zone, instance)?;
+        self.compute.reset_instance(project, zone, instance).await
+    }
+
+    async fn suspend_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        validate_target(project, zone, instance)?;
+        self.compute.suspend_instance(project, zone, instance).await
+    }
+
+    async fn resume_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        validate_target(project, zone, instance)?;
+        self.compute.resume_instance(project, zone, instance).await
+    }
+
+    async fn set_machine_type(&self, project: &str, zone: &str, instance: &str, machine_type: &str) -> Result<()> {
+        validate_target(project, zone, instance)?;
+        self.compute.set_machine_type(project, zone, instance, machine_type).await
+    }
+
+    async fn set_deletion_protection(&self, project: &str, zone: &str, instance: &str, enabled: bool) -> Result<()> {
+        validate_target(project, zone, instance)?;
+        self.compute.set_deletion_protection(project, zone, instance, enabled).await
+    }
+
+    async fn machine_type_in_zone(&self, project: &str, zone: &str, machine_type: &str) -> Result<Option<MachineSpec>> {
+        validate_project_id(project)?;
+        validate_zone(zone)?;
+        self.compute.get_machine_type(project, zone, machine_type).await
+    }
+}
+
+fn validate_target(project: &str, zone: &str, instance: &str) -> Result<()> {
+    // SECURITY: Same validation as the CLI path, before building API URLs
+    validate_project_id(project)?;
+    validate_zone(zone)?;
+    validate_instance_name(instance)?;
+    Ok(())
+}
+
+/// In-memory backend for tests and UI development
+///
+/// Lifecycle calls update the stored status the way GCE would once the
+/// operation finishes (start -> RUNNING, stop -> TERMINATED). Machine types
+/// known to the offline table count as available in every zone.
+#[derive(Default)]
+pub struct FakeBackend {
+    projects: Mutex<Vec<GcpProject>>,
+    instances: Mutex<HashMap<String, Vec<GcpInstance>>>,
+    /// Instance names with deletion protection on
+    protected: Mutex<HashSet<String>>,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a project (with no instances)
+    pub fn add_project(&self, project_id: &str) {
+        if let Ok(mut projects) = self.projects.lock() {
+            projects.push(GcpProject {
+                project_id: project_id.to_string(),
+                name: Some(project_id.to_string()),
+                project_number: None,
+                state: Some("ACTIVE".to_string()),
+            });
+        }
+        if let Ok(mut instances) = self.instances.lock() {
+            instances.entry(project_id.to_string()).or_default();
+        }
+    }
+
+    /// Register an instance; the project is created if needed
+    pub fn add_instance(&self, project_id: &str, instance: GcpInstance) {
+        let known = self
+            .projects
+            .lock()
+            .map(|p| p.iter().any(|p| p.project_id == project_id))
+            .unwrap_or(false);
+        if !known {
+            self.add_project(project_id);
+        }
+        if let Ok(mut instances) = self.instances.lock() {
+            instances.entry(project_id.to_string()).or_default().push(instance);
+        }
+    }
+
+    pub fn is_deletion_protected(&self, instance: &str) -> bool {
+        self.protected.lock().map(|p| p.contains(instance)).unwrap_or(false)
+    }
+
+    fn with_instance<T>(
+        &self,
+        project: &str,
+        zone: &str,
+        instance: &str,
+        f: impl FnOnce(&mut GcpInstance) -> Result<T>,
+    ) -> Result<T> {
+        let mut instances = self.instances.lock().map_err(|_| anyhow!("Fake backend lock poisoned"))?;
+        let found = instances
+            .get_mut(project)
+            .and_then(|list| list.iter_mut().find(|i| i.name == instance && i.zone == zone))
+            .ok_or_else(|| anyhow!("Instance '{}' not found in {}/{}", instance, project, zone))?;
+        f(found)
+    }
+}
+
+/// `e2-medium` instance (2 vCPU, 4 GB, 10 GB boot disk) to seed a [`FakeBackend`]
+#[cfg(test)]
+pub(crate) fn test_instance(name: &str, zone: &str, status: &str) -> GcpInstance {
+    GcpInstance {
+        name: name.to_string(),
+        status: status.to_string(),
+        zone: zone.to_string(),
+        machine_type: "e2-medium".to_string(),
+        cpu_count: Some(2),
+        memory_mb: Some(4096),
+        disk_gb: Some(10),
+    }
+}
+
+/// `dev-project/europe-west1-b/<instance>`
+#[cfg(test)]
+pub(crate) fn test_target(instance: &str) -> crate::bulk::InstanceRef {
+    crate::bulk::InstanceRef {
+        project: "dev-project".to_string(),
+        zone: "europe-west1-b".to_string(),
+        instance: instance.to_string(),
+    }
+}
+
+#[async_trait]
+impl CloudBackend for FakeBackend {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    async fn list_projects(&self) -> Result<Vec<GcpProject>> {
+        Ok(self.projects.lock().map_err(|_| anyhow!("Fake backend lock poisoned"))?.clone())
+    }
+
+    async fn list_instances(&self, project: &str) -> Result<Vec<GcpInstance>> {
+        self.instances
+            .lock()
+            .map_err(|_| anyhow!("Fake backend lock poisoned"))?
+            .get(project)
+            .cloned()
+            .ok_or_else(|| anyhow!("Project '{}' not found", project))
+    }
+
+    async fn describe_instance(&self, project: &str, zone: &str, instance: &str) -> Result<GcpInstance> {
+        self.with_instance(project, zone, instance, |i| Ok(i.clone()))
+    }
+
+    async fn describe_instance_details(&self, project: &str, zone: &str, instance: &str) -> Result<InstanceDetails> {
+        self.with_instance(project, zone, instance, |i| Ok(InstanceDetails::from(&*i)))
+    }
+
+    async fn start_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.with_instance(project, zone, instance, |i| {
+            i.status = "RUNNING".to_string();
+            Ok(())
+        })
+    }
+
+    async fn stop_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.with_instance(project, zone, instance, |i| {
+            i.status = "TERMINATED".to_string();
+            Ok(())
+        })
+    }
+
+    async fn reset_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.with_instance(project, zone, instance, |i| {
+            if i.status != "RUNNING" {
+                return Err(anyhow!("Instance '{}' is not running", i.name));
+            }
+            Ok(())
+        })
+    }
+
+    async fn suspend_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.with_instance(project, zone, instance, |i| {
+            if i.status != "RUNNING" {
+                return Err(anyhow!("Instance '{}' is not running", i.name));
+            }
+            i.status = "SUSPENDED".to_string();
+            Ok(())
+        })
+    }
+
+    async fn resume_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.with_instance(project, zone, instance, |i| {
+            if i.status != "SUSPENDED" {
+                return Err(anyhow!("Instance '{}' is not suspended", i.name));
+            }
+            i.status = "RUNNING".to_string();
+            Ok(())
+        })
+    }
+
+    async fn set_machine_type(&self, project: &str, zone: &str, instance: &str, machine_type: &str) -> Result<()> {
+        self.with_instance(project, zone, instance, |i| {
+            if i.status != "TERMINATED" {
+                return Err(anyhow!("Instance '{}' must be stopped to change its machine type", i.name));
+            }
+            let spec = offline_spec(machine_type);
+            i.machine_type = machine_type.to_string();
+            i.cpu_count = spec.as_ref().map(|s| s.guest_cpus);
+            i.memory_mb = spec.as_ref().map(|s| s.memory_mb);
+            Ok(())
+        })
+    }
+
+    async fn set_deletion_protection(&self, project: &str, zone: &str, instance: &str, enabled: bool) -> Result<()> {
+        self.with_instance(project, zone, instance, |_| Ok(()))?;
+        let mut protected = self.protected.lock().map_err(|_| anyhow!("Fake backend lock poisoned"))?;
+        if enabled {
+            protected.insert(instance.to_string());
+        } else {
+            protected.remove(instance);
+        }
+        Ok(())
+    }
+
+    async fn machine_type_in_zone(&self, _project: &str, _zone: &str, machine_type: &str) -> Result<Option<MachineSpec>> {
+        Ok(offline_spec(machine_type))
+    }
+}
+
+/// Backend for the API method selected in the UI, acting as `context`
+pub async fn backend_for(method: ApiMethod, context: &GcloudContext) -> Result<Box<dyn CloudBackend>> {
+    match method {
+        ApiMethod::GcloudCli => Ok(Box::new(GcloudCliBackend::new(context.clone()))),
+        ApiMethod::ClientLibrary => Ok(Box::new(RestBackend::for_context(context).await?)),
+    }
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn list_projects_via(method: ApiMethod, context: &GcloudContext) -> Result<Vec<GcpProject>> {
+    runtime::block_on(async { backend_for(method, context).await?.list_projects().await })
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn list_instances_via(method: ApiMethod, context: &GcloudContext, project: &str) -> Result<Vec<GcpInstance>> {
+    runtime::block_on(async { backend_for(method, context).await?.list_instances(project).await })
+}
+
+/// Synchronous wrapper for FFI bridge: `on_page` runs for every page as it arrives
+///
+/// Returns the complete list once the last page is in.
+pub fn list_projects_streaming_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    options: &ListOptions,
+    on_page: impl FnMut(Vec<GcpProject>),
+) -> Result<Vec<GcpProject>> {
+    runtime::block_on(stream_pages(on_page, |tx| async move {
+        backend_for(method, context).await?.list_projects_paged(options, Some(&tx)).await
+    }))
+}
+
+/// Synchronous wrapper for FFI bridge: `on_page` runs for every page as it arrives
+pub fn list_instances_streaming_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    project: &str,
+    options: &ListOptions,
+    on_page: impl FnMut(Vec<GcpInstance>),
+) -> Result<Vec<GcpInstance>> {
+    runtime::block_on(stream_pages(on_page, |tx| async move {
+        backend_for(method, context).await?.list_instances_paged(project, options, Some(&tx)).await
+    }))
+}
+
+/// Drive `list` while forwarding its pages to `on_page`
+async fn stream_pages<T, F, Fut>(mut on_page: impl FnMut(Vec<T>), list: F) -> Result<Vec<T>>
+where
+    F: FnOnce(PageSender<T>) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<T>>>,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let listing = list(tx);
+    tokio::pin!(listing);
+
+    loop {
+        tokio::select! {
+            Some(page) = rx.recv() => on_page(page),
+            result = &mut listing => {
+                // Pages queued before the listing finished
+                while let Ok(page) = rx.try_recv() {
+                    on_page(page);
+                }
+                return result;
+            }
+        }
+    }
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn resize_instance_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    project: &str,
+    zone: &str,
+    instance: &str,
+    machine_type: &str,
+) -> Result<ResizeOutcome> {
+    runtime::block_on(async {
+        backend_for(method, context).await?.resize_instance(project, zone, instance, machine_type).await
+    })
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn set_deletion_protection_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    project: &str,
+    zone: &str,
+    instance: &str,
+    enabled: bool,
+) -> Result<()> {
+    runtime::block_on(async {
+        backend_for(method, context).await?.set_deletion_protection(project, zone, instance, enabled).await
+    })
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn run_instance_action_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    action: InstanceAction,
+    project: &str,
+    zone: &str,
+    instance: &str,
+) -> Result<()> {
+    runtime::block_on(async { backend_for(method, context).await?.run_action(action, project, zone, instance).await })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fake_backend_lifecycle() {
+        let fake = FakeBackend::new();
+        fake.add_instance("my-project", test_instance("web-1", "us-central1-a", "TERMINATED"));
+        let backend: &dyn CloudBackend = &fake;
+
+        assert_eq!(backend.list_projects().await.unwrap().len(), 1);
+        assert!(backend.reset_instance("my-project", "us-central1-a", "web-1").await.is_err());
+
+        backend
+            .run_action(InstanceAction::Start, "my-project", "us-central1-a", "web-1")
+            .await
+            .unwrap();
+        let described = backend.describe_instance("my-project", "us-central1-a", "web-1").await.unwrap();
+        assert_eq!(described.status, "RUNNING");
+        let details = backend.describe_instance_details("my-project", "us-central1-a", "web-1").await.unwrap();
+        assert_eq!(details.boot_disk().and_then(|d| d.disk_size_gb), Some(10));
+
+        backend.stop_instance("my-project", "us-central1-a", "web-1").await.unwrap();
+        let listed = backend.list_instances("my-project").await.unwrap();
+        assert_eq!(listed[0].status, "TERMINATED");
+    }
+
+    #[tokio::test]
+    async fn test_fake_backend_unknown_resources() {
+        let fake = FakeBackend::new();
+        assert!(fake.list_instances("missing-project").await.is_err());
+        assert!(fake.start_instance("missing-project", "us-central1-a", "vm").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_pages_delivers_pages_before_result() {
+        let fake = FakeBackend::new();
+        fake.add_project("p-one");
+
+        let mut pages = Vec::new();
+        let all = stream_pages(|page| pages.push(page), |tx| async move {
+            fake.list_projects_paged(&ListOptions::default(), Some(&tx)).await
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(all.len(), 1);
+        assert_eq!(pages, vec![all]);
+    }
+
+    #[tokio::test]
+    async fn test_resize_and_suspend_workflows() {
+        let fake = FakeBackend::new();
+        fake.add_instance("my-project", test_instance("vm-1", "us-central1-a", "RUNNING"));
+        let zone = "us-central1-a";
+
+        let outcome = fake.resize_instance("my-project", zone, "vm-1", "n2-standard-8").await.unwrap();
+        assert!(outcome.changed && outcome.restarted);
+        let resized = fake.describe_instance("my-project", zone, "vm-1").await.unwrap();
+        assert_eq!((resized.status.as_str(), resized.cpu_count), ("RUNNING", Some(8)));
+
+        let err = fake.resize_instance("my-project", zone, "vm-1", "z9-mystery-4").await.unwrap_err();
+        assert!(err.to_string().contains("not available"));
+
+        fake.run_action(InstanceAction::Suspend, "my-project", zone, "vm-1").await.unwrap();
+        assert!(fake.resize_instance("my-project", zone, "vm-1", "e2-medium").await.is_err());
+        fake.run_action(InstanceAction::Resume, "my-project", zone, "vm-1").await.unwrap();
+
+        fake.set_deletion_protection("my-project", zone, "vm-1", true).await.unwrap();
+        assert!(fake.is_deletion_protected("vm-1"));
+    }
+}
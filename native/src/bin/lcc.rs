@@ -0,0 +1,1218 @@
+//! `lcc` - headless command line front-end for the native crate
+//!
+//! Exposes the same flows as the desktop UI (projects, instances, lifecycle,
+//! IAP tunnels, SFTP and Remmina) for scripts, CI jump boxes and tmux.
+
+use anyhow::{Result, anyhow};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use native::accounts::{self, GcloudContext};
+use native::gcloud;
+use native::backend::{ApiMethod, CloudBackend, GcloudCliBackend, InstanceAction};
+use native::bulk::{run_bulk_action, BulkOptions, BulkTarget, CancelHandle, InstanceOutcome, InstanceRef, OutcomeStatus};
+use native::cost::{self, PriceTable};
+use native::hierarchy::{self, LifecycleFilter};
+use native::instance_details::InstanceDetails;
+use native::inventory::{self, InstanceFilter, InventoryOptions};
+use native::metrics;
+use native::pagination::ListOptions;
+use native::remmina::{self, RdpSettings};
+use native::runtime::block_on;
+use native::schedules::{self, CronExpr, Schedule, SchedulerConfig};
+use native::sftp;
+use native::ssh_config::{self, SshConfigOptions, SshConfigTarget};
+use native::supervisor::{self, SupervisorConfig};
+use native::tunnel::{self, LocalPortSpec, TunnelBackendKind, TunnelKey, TunnelSpec};
+use native::tunnel_state;
+
+#[derive(Parser)]
+#[command(name = "lcc", version, about = "Linux Cloud Connector - headless CLI")]
+struct Cli {
+    /// Output format
+    #[arg(long, short, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
+    /// gcloud account to act as (default: the active account)
+    #[arg(long, global = true)]
+    account: Option<String>,
+
+    /// gcloud configuration to use (default: the active configuration)
+    #[arg(long, global = true)]
+    configuration: Option<String>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// List authenticated gcloud accounts
+    Accounts,
+    /// List gcloud configurations
+    Configurations,
+    /// Revoke the credentials of one account
+    Logout {
+        account: String,
+    },
+    /// List accessible projects
+    Projects(ListArgs),
+    /// Browse organizations, folders and projects as a tree
+    Tree {
+        /// Start below this node (organizations/ID or folders/ID)
+        #[arg(long)]
+        parent: Option<String>,
+        /// Levels to expand below the starting point
+        #[arg(long, default_value_t = 3)]
+        depth: usize,
+        /// Also show folders and projects pending deletion
+        #[arg(long)]
+        include_deleted: bool,
+    },
+    /// List instances in a project
+    Instances {
+        #[arg(long)]
+        project: String,
+        #[command(flatten)]
+        list: ListArgs,
+    },
+    /// Find instances across projects by name, status, zone or labels
+    ///
+    /// Example: lcc search '^db-replica-3$'
+    Search {
+        /// Regular expression searched in instance names
+        name: Option<String>,
+        /// Project to search (repeatable; default: every accessible project)
+        #[arg(long = "project")]
+        projects: Vec<String>,
+        #[arg(long)]
+        status: Option<String>,
+        #[arg(long)]
+        zone: Option<String>,
+        /// Label filter KEY=VALUE (repeatable)
+        #[arg(long = "label", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+        /// Projects listed at the same time
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+        #[command(flatten)]
+        list: ListArgs,
+    },
+    /// Estimate what instances cost per hour and since they were started
+    ///
+    /// Example: lcc cost --project dev-project
+    Cost {
+        /// Project to price (repeatable; default: every accessible project)
+        #[arg(long = "project")]
+        projects: Vec<String>,
+        /// Projects and instances queried at the same time
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+        /// Refresh the price table from the Cloud Billing Catalog API first
+        #[arg(long)]
+        refresh_prices: bool,
+    },
+    /// Show everything about one instance (IPs, labels, identity, disks...)
+    Describe(InstanceArgs),
+    /// Start a stopped instance
+    Start(InstanceArgs),
+    /// Stop a running instance
+    Stop(InstanceArgs),
+    /// Reset (hard restart) an instance
+    Reset(InstanceArgs),
+    /// Suspend a running instance (memory kept on disk)
+    Suspend(InstanceArgs),
+    /// Resume a suspended instance
+    Resume(InstanceArgs),
+    /// Change the machine type (stops and restarts a running instance)
+    Resize {
+        #[command(flatten)]
+        instance: InstanceArgs,
+        #[arg(long)]
+        machine_type: String,
+    },
+    /// Turn deletion protection on or off
+    DeletionProtection {
+        #[command(flatten)]
+        instance: InstanceArgs,
+        #[arg(value_enum)]
+        state: Toggle,
+    },
+    /// Run a lifecycle action on many instances at once
+    ///
+    /// Example: lcc bulk stop --project dev-project --label env=dev
+    Bulk {
+        #[arg(value_enum)]
+        action: BulkActionArg,
+        /// Explicit instance as PROJECT/ZONE/INSTANCE (repeatable)
+        #[arg(long = "target", value_parser = parse_instance_ref)]
+        targets: Vec<InstanceRef>,
+        /// Project to select from (repeatable; default: every accessible project)
+        #[arg(long = "project")]
+        projects: Vec<String>,
+        /// Label selector KEY=VALUE (repeatable)
+        #[arg(long = "label", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+        /// Regular expression on instance names
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        status: Option<String>,
+        /// Operations in flight at the same time
+        #[arg(long, default_value_t = 5)]
+        concurrency: usize,
+    },
+    /// Start/stop instances on a timetable
+    #[command(subcommand)]
+    Schedule(ScheduleCommand),
+    /// Manage IAP tunnels
+    #[command(subcommand)]
+    Tunnel(TunnelCommand),
+    /// Transfer files over SFTP through an IAP tunnel
+    #[command(subcommand)]
+    Sftp(SftpCommand),
+    /// Relay one IAP connection over stdin/stdout, for SSH ProxyCommand
+    ///
+    /// Example ~/.ssh/config entry:
+    ///   ProxyCommand lcc proxy-command --project P --zone Z %h %p
+    ProxyCommand {
+        #[arg(long)]
+        project: String,
+        #[arg(long)]
+        zone: String,
+        /// Instance name (ssh's %h)
+        instance: String,
+        /// Port on the instance (ssh's %p)
+        #[arg(default_value_t = 22)]
+        port: u16,
+        #[arg(long, value_enum, default_value_t = BackendArg::Auto)]
+        backend: BackendArg,
+    },
+    /// Write `Host project.instance` entries for a project to ~/.ssh
+    SshConfig {
+        #[arg(long)]
+        project: String,
+        /// Remote login user
+        #[arg(long)]
+        user: Option<String>,
+        /// Private key for the hosts (e.g. ~/.ssh/google_compute_engine)
+        #[arg(long)]
+        identity_file: Option<String>,
+        /// Alias prefix instead of the project id
+        #[arg(long)]
+        alias: Option<String>,
+        /// Edit ~/.ssh/config directly instead of an included lcc_config file
+        #[arg(long)]
+        in_place: bool,
+        /// Remove the project's managed block instead of writing it
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Open an RDP session in Remmina through an IAP tunnel
+    Rdp {
+        #[command(flatten)]
+        instance: InstanceArgs,
+        #[arg(long, default_value_t = 3389)]
+        remote_port: u16,
+        #[command(flatten)]
+        ports: LocalPortArgs,
+        #[arg(long)]
+        username: Option<String>,
+        #[arg(long)]
+        domain: Option<String>,
+        #[arg(long)]
+        fullscreen: bool,
+    },
+}
+
+#[derive(Args, Clone)]
+struct InstanceArgs {
+    #[arg(long)]
+    project: String,
+    #[arg(long)]
+    zone: String,
+    /// Instance name
+    instance: String,
+}
+
+impl InstanceArgs {
+    /// Identity of a tunnel to this instance opened by this process
+    fn key(&self, remote_port: u16, ctx: &GcloudContext) -> TunnelKey {
+        TunnelKey::new(&self.project, &self.zone, &self.instance, remote_port).with_context(ctx)
+    }
+}
+
+/// Where the local end of a tunnel listens (default: any free port)
+#[derive(Args, Clone)]
+struct LocalPortArgs {
+    /// Listen on exactly this port, failing if it is taken
+    #[arg(long, conflicts_with_all = ["prefer_port", "port_range"])]
+    local_port: Option<u16>,
+    /// Listen on this port if it is free, any free port otherwise
+    #[arg(long, conflicts_with = "port_range")]
+    prefer_port: Option<u16>,
+    /// Listen on the first free port in START-END
+    #[arg(long, value_parser = parse_port_range)]
+    port_range: Option<(u16, u16)>,
+}
+
+impl LocalPortArgs {
+    fn spec(&self) -> LocalPortSpec {
+        match (self.local_port, self.prefer_port, self.port_range) {
+            (Some(port), _, _) => LocalPortSpec::Fixed(port),
+            (_, Some(port), _) => LocalPortSpec::Preferred(port),
+            (_, _, Some((start, end))) => LocalPortSpec::Range { start, end },
+            _ => LocalPortSpec::Any,
+        }
+    }
+}
+
+#[derive(Args, Clone)]
+struct ListArgs {
+    /// Items requested per API page
+    #[arg(long, default_value_t = 500)]
+    page_size: u32,
+    /// Give up after this many seconds (all pages)
+    #[arg(long, default_value_t = 120)]
+    timeout: u64,
+}
+
+impl ListArgs {
+    fn options(&self) -> ListOptions {
+        ListOptions { page_size: self.page_size, timeout: Duration::from_secs(self.timeout) }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BulkActionArg {
+    Start,
+    Stop,
+    Reset,
+    Suspend,
+    Resume,
+}
+
+impl From<BulkActionArg> for InstanceAction {
+    fn from(value: BulkActionArg) -> Self {
+        match value {
+            BulkActionArg::Start => InstanceAction::Start,
+            BulkActionArg::Stop => InstanceAction::Stop,
+            BulkActionArg::Reset => InstanceAction::Reset,
+            BulkActionArg::Suspend => InstanceAction::Suspend,
+            BulkActionArg::Resume => InstanceAction::Resume,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Toggle {
+    On,
+    Off,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BackendArg {
+    Auto,
+    Native,
+    Gcloud,
+}
+
+impl From<BackendArg> for TunnelBackendKind {
+    fn from(value: BackendArg) -> Self {
+        match value {
+            BackendArg::Auto => TunnelBackendKind::Auto,
+            BackendArg::Native => TunnelBackendKind::Native,
+            BackendArg::Gcloud => TunnelBackendKind::Gcloud,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum TunnelCommand {
+    /// Start a tunnel and keep it open until interrupted
+    Start {
+        #[command(flatten)]
+        instance: InstanceArgs,
+        /// Port on the instance
+        remote_port: u16,
+        #[arg(long, value_enum, default_value_t = BackendArg::Auto)]
+        backend: BackendArg,
+        #[command(flatten)]
+        ports: LocalPortArgs,
+        /// Local address to listen on (e.g. ::1, or an interface address to share the tunnel)
+        #[arg(long, default_value_t = tunnel::DEFAULT_BIND_ADDRESS)]
+        bind: IpAddr,
+        /// Restart the tunnel on the same local port if it dies
+        #[arg(long)]
+        supervise: bool,
+        /// Serve Prometheus metrics of the tunnel on ADDRESS (e.g. 127.0.0.1:9464)
+        #[arg(long, value_name = "ADDRESS")]
+        metrics: Option<std::net::SocketAddr>,
+    },
+    /// Stop a tunnel started by another `lcc tunnel start`
+    Stop {
+        instance: String,
+        remote_port: u16,
+        /// Needed when tunnels to same-named instances run in several projects
+        #[arg(long)]
+        project: Option<String>,
+        #[arg(long)]
+        zone: Option<String>,
+    },
+    /// List tunnels started by `lcc tunnel start`
+    List,
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommand {
+    /// List saved schedules and when they fire next
+    List,
+    /// Create or replace a schedule
+    ///
+    /// Example: lcc schedule add office-hours --label env=dev
+    ///   --start "0 8 * * 1-5" --stop "0 20 * * 1-5" --time-zone Europe/Madrid
+    Add {
+        id: String,
+        /// Cron expression (minute hour day month weekday) to start at
+        #[arg(long)]
+        start: Option<String>,
+        /// Cron expression to stop at
+        #[arg(long)]
+        stop: Option<String>,
+        /// IANA time zone the expressions are in
+        #[arg(long, default_value = "UTC")]
+        time_zone: String,
+        /// Explicit instance as PROJECT/ZONE/INSTANCE (repeatable)
+        #[arg(long = "target", value_parser = parse_instance_ref)]
+        targets: Vec<InstanceRef>,
+        /// Project to select from (repeatable; default: every accessible project)
+        #[arg(long = "project")]
+        projects: Vec<String>,
+        /// Label selector KEY=VALUE (repeatable)
+        #[arg(long = "label", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+        #[arg(long)]
+        description: Option<String>,
+        /// Save the schedule disabled
+        #[arg(long)]
+        disabled: bool,
+    },
+    /// Delete a schedule
+    Remove { id: String },
+    /// Push a schedule to Compute Engine as an instance schedule policy
+    Sync { id: String },
+    /// Evaluate schedules in the foreground until interrupted
+    Run,
+}
+
+#[derive(Args, Clone)]
+struct SftpTarget {
+    #[command(flatten)]
+    instance: InstanceArgs,
+    /// Remote username (defaults to the local user)
+    #[arg(long)]
+    user: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum SftpCommand {
+    /// List a remote directory
+    Ls {
+        #[command(flatten)]
+        target: SftpTarget,
+        remote_path: String,
+    },
+    /// Download a remote file
+    Get {
+        #[command(flatten)]
+        target: SftpTarget,
+        remote_path: String,
+        local_path: String,
+    },
+    /// Upload a local file
+    Put {
+        #[command(flatten)]
+        target: SftpTarget,
+        local_path: String,
+        remote_path: String,
+    },
+}
+
+/// A tunnel owned by a running `lcc tunnel start` process
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TunnelRecord {
+    pid: u32,
+    project: String,
+    zone: String,
+    instance: String,
+    remote_port: u16,
+    local_port: u16,
+    #[serde(default = "default_bind_address")]
+    bind_address: IpAddr,
+}
+
+fn default_bind_address() -> IpAddr {
+    tunnel::DEFAULT_BIND_ADDRESS
+}
+
+impl TunnelRecord {
+    fn key(&self) -> TunnelKey {
+        TunnelKey::new(&self.project, &self.zone, &self.instance, self.remote_port)
+    }
+
+    fn local_address(&self) -> String {
+        std::net::SocketAddr::new(self.bind_address, self.local_port).to_string()
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_cli_logging();
+
+    if let Err(e) = run(cli) {
+        eprintln!("error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Log to stderr only, so stdout stays clean for JSON output
+fn init_cli_logging() {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn"));
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .try_init();
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let output = cli.output;
+    let ctx = GcloudContext { account: cli.account, configuration: cli.configuration };
+    ctx.validate()?;
+
+    match cli.command {
+        Commands::Accounts => {
+            let accounts = accounts::list_accounts()?;
+            emit(output, &accounts, &["ACCOUNT", "ACTIVE"], |a| {
+                vec![a.account.clone(), if a.is_active() { "*".to_string() } else { String::new() }]
+            })
+        }
+        Commands::Configurations => {
+            let configs = accounts::list_configurations()?;
+            emit(output, &configs, &["NAME", "ACTIVE", "ACCOUNT", "PROJECT"], |c| {
+                vec![
+                    c.name.clone(),
+                    if c.is_active { "*".to_string() } else { String::new() },
+                    c.account.clone().unwrap_or_default(),
+                    c.project.clone().unwrap_or_default(),
+                ]
+            })
+        }
+        Commands::Logout { account } => {
+            accounts::revoke_account(&account)?;
+            report(output, &account, "credentials revoked")
+        }
+        Commands::Projects(list) => {
+            let projects = block_on(gcloud::get_projects_paged_async(&ctx, &list.options()))?;
+            emit(output, &projects, &["PROJECT_ID", "NAME"], |p| {
+                vec![p.project_id.clone(), p.name.clone().unwrap_or_default()]
+            })
+        }
+        Commands::Tree { parent, depth, include_deleted } => {
+            let lifecycle = if include_deleted {
+                LifecycleFilter::IncludeDeleteRequested
+            } else {
+                LifecycleFilter::ActiveOnly
+            };
+            let nodes = block_on(async {
+                let mut browser = hierarchy::browser_for(&ctx, lifecycle).await?;
+                browser.subtree(parent.as_deref(), depth).await
+            })?;
+
+            if output == OutputFormat::Json {
+                let nodes: Vec<_> = nodes
+                    .iter()
+                    .map(|(depth, node)| serde_json::json!({ "depth": depth, "node": node }))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&nodes)?);
+                return Ok(());
+            }
+            for (depth, node) in &nodes {
+                let marker = if node.state == "ACTIVE" { String::new() } else { format!(" [{}]", node.state) };
+                match &node.project_id {
+                    Some(project_id) => println!("{}{}{}", "  ".repeat(*depth), project_id, marker),
+                    None => println!("{}{}/ ({}){}", "  ".repeat(*depth), node.display_name, node.name, marker),
+                }
+            }
+            Ok(())
+        }
+        Commands::Instances { project, list } => {
+            let instances = block_on(gcloud::get_instances_paged_async(&ctx, &project, &list.options()))?;
+            emit(
+                output,
+                &instances,
+                &["NAME", "ZONE", "STATUS", "MACHINE_TYPE", "CPUS", "MEMORY_MB", "DISK_GB"],
+                |i| {
+                    vec![
+                        i.name.clone(),
+                        i.zone.clone(),
+                        i.status.clone(),
+                        i.machine_type.clone(),
+                        opt(i.cpu_count),
+                        opt(i.memory_mb),
+                        opt(i.disk_gb),
+                    ]
+                },
+            )
+        }
+        Commands::Search { name, projects, status, zone, labels, concurrency, list } => {
+            let filter = InstanceFilter { status, zone, name_regex: name, labels: labels.into_iter().collect() };
+            let options = InventoryOptions { concurrency, list: list.options() };
+            let projects = (!projects.is_empty()).then_some(projects);
+            let backend = GcloudCliBackend::new(ctx);
+            let inventory = block_on(inventory::collect_inventory(&backend, projects, &filter, &options))?;
+
+            if output == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&inventory)?);
+                return Ok(());
+            }
+            for failure in &inventory.failures {
+                eprintln!("warning: {}: {}", failure.project_id, failure.error);
+            }
+            emit(output, &inventory.entries, &["PROJECT", "NAME", "ZONE", "STATUS", "MACHINE_TYPE"], |e| {
+                vec![
+                    e.project_id.clone(),
+                    e.instance.name.clone(),
+                    e.instance.zone.clone(),
+                    e.instance.status.clone(),
+                    e.instance.machine_type.clone(),
+                ]
+            })
+        }
+        Commands::Cost { projects, concurrency, refresh_prices } => {
+            if refresh_prices {
+                let table = block_on(cost::refresh_price_table(&ctx))?;
+                eprintln!("Price table updated ({})", table.updated);
+            }
+            let table = PriceTable::load_default();
+            let options = InventoryOptions { concurrency, ..Default::default() };
+            let projects = (!projects.is_empty()).then_some(projects);
+            let backend = GcloudCliBackend::new(ctx);
+            let fleet = block_on(cost::estimate_fleet_cost(&backend, &table, projects, &options, chrono::Utc::now()))?;
+
+            if output == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&fleet)?);
+                return Ok(());
+            }
+            for failure in &fleet.failures {
+                eprintln!("warning: {}: {}", failure.project_id, failure.error);
+            }
+            let rows: Vec<(&str, &cost::InstanceCost)> = fleet
+                .projects
+                .iter()
+                .flat_map(|p| p.instances.iter().map(move |i| (p.project_id.as_str(), i)))
+                .collect();
+            emit(output, &rows, &["PROJECT", "NAME", "STATUS", "MACHINE_TYPE", "PER_HOUR", "SESSION"], |(project, i)| {
+                let approximate = if i.estimate.is_approximate() { "~" } else { "" };
+                vec![
+                    project.to_string(),
+                    i.instance.clone(),
+                    i.status.clone(),
+                    format!("{}{}", i.estimate.machine_type, if i.estimate.spot { " (spot)" } else { "" }),
+                    format!("{}{:.4}", approximate, i.estimate.current_hourly),
+                    opt(i.estimate.session_cost.map(|c| format!("{:.2}", c))),
+                ]
+            })?;
+            println!();
+            for project in &fleet.projects {
+                println!(
+                    "{}: {:.4} {}/h now, {:.2} {} this session ({} running)",
+                    project.project_id,
+                    project.current_hourly,
+                    fleet.currency,
+                    project.session_total,
+                    fleet.currency,
+                    project.running_instances
+                );
+                for unpriced in &project.unpriced {
+                    eprintln!("warning: {}: {}", project.project_id, unpriced);
+                }
+            }
+            println!("Prices from {} (~ = approximate, see --output json)", fleet.prices_updated);
+            Ok(())
+        }
+        Commands::Describe(args) => {
+            let details = block_on(gcloud::describe_instance_details_async(&ctx, &args.project, &args.zone, &args.instance))?;
+            if output == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&details)?);
+                return Ok(());
+            }
+            emit(output, &describe_rows(&details), &["FIELD", "VALUE"], |(k, v)| vec![k.clone(), v.clone()])
+        }
+        Commands::Start(args) => {
+            block_on(gcloud::start_instance_async(&ctx, &args.project, &args.zone, &args.instance))?;
+            report(output, &args.instance, "started")
+        }
+        Commands::Stop(args) => {
+            block_on(gcloud::stop_instance_async(&ctx, &args.project, &args.zone, &args.instance))?;
+            report(output, &args.instance, "stopped")
+        }
+        Commands::Reset(args) => {
+            block_on(gcloud::reset_instance_async(&ctx, &args.project, &args.zone, &args.instance))?;
+            report(output, &args.instance, "reset")
+        }
+        Commands::Suspend(args) => {
+            block_on(gcloud::suspend_instance_async(&ctx, &args.project, &args.zone, &args.instance))?;
+            report(output, &args.instance, "suspended")
+        }
+        Commands::Resume(args) => {
+            block_on(gcloud::resume_instance_async(&ctx, &args.project, &args.zone, &args.instance))?;
+            report(output, &args.instance, "resumed")
+        }
+        Commands::Resize { instance: args, machine_type } => {
+            let backend = GcloudCliBackend::new(ctx);
+            let outcome = block_on(backend.resize_instance(&args.project, &args.zone, &args.instance, &machine_type))?;
+            let message = if !outcome.changed {
+                format!("already {}", outcome.machine_type)
+            } else if outcome.restarted {
+                format!("resized {} -> {} and restarted", outcome.previous_machine_type, outcome.machine_type)
+            } else {
+                format!("resized {} -> {}", outcome.previous_machine_type, outcome.machine_type)
+            };
+            report(output, &args.instance, &message)
+        }
+        Commands::DeletionProtection { instance: args, state } => {
+            let enabled = state == Toggle::On;
+            block_on(gcloud::set_deletion_protection_async(&ctx, &args.project, &args.zone, &args.instance, enabled))?;
+            report(output, &args.instance, if enabled { "deletion protection on" } else { "deletion protection off" })
+        }
+        Commands::Bulk { action, targets, projects, labels, name, status, concurrency } => {
+            let target = if !targets.is_empty() {
+                BulkTarget::Instances(targets)
+            } else if labels.is_empty() && name.is_none() && status.is_none() {
+                return Err(anyhow!("Give --target instances or a selector (--label, --name, --status)"));
+            } else {
+                BulkTarget::Selector {
+                    projects: (!projects.is_empty()).then_some(projects),
+                    filter: InstanceFilter { status, zone: None, name_regex: name, labels: labels.into_iter().collect() },
+                }
+            };
+            let options = BulkOptions { concurrency, ..Default::default() };
+            let backend = GcloudCliBackend::new(ctx);
+
+            let report = block_on(async {
+                // Ctrl-C stops dispatching; operations already sent are waited for
+                let cancel = CancelHandle::new();
+                let on_signal = cancel.clone();
+                tokio::spawn(async move {
+                    if tokio::signal::ctrl_c().await.is_ok() {
+                        eprintln!("cancelling: waiting for operations in flight...");
+                        on_signal.cancel();
+                    }
+                });
+
+                let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+                let printer = tokio::spawn(async move {
+                    while let Some(outcome) = rx.recv().await {
+                        if output == OutputFormat::Table {
+                            let InstanceOutcome { target, status, elapsed_ms } = outcome;
+                            let result = match status {
+                                OutcomeStatus::Succeeded => "ok".to_string(),
+                                OutcomeStatus::Failed(error) => format!("FAILED: {}", error.trim()),
+                                OutcomeStatus::Cancelled => "cancelled".to_string(),
+                            };
+                            println!("{}/{}/{}: {} ({:.1}s)", target.project, target.zone, target.instance, result, elapsed_ms as f64 / 1000.0);
+                        }
+                    }
+                });
+                let report = run_bulk_action(&backend, action.into(), target, &options, &cancel, Some(&tx)).await;
+                drop(tx);
+                let _ = printer.await;
+                report
+            })?;
+
+            match output {
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                OutputFormat::Table => {
+                    for project in &report.skipped_projects {
+                        eprintln!("warning: could not list {}", project);
+                    }
+                    println!(
+                        "{} succeeded, {} failed, {} cancelled",
+                        report.succeeded(),
+                        report.failed(),
+                        report.cancelled()
+                    );
+                }
+            }
+            if report.failed() > 0 {
+                return Err(anyhow!("{} of {} operations failed", report.failed(), report.outcomes.len()));
+            }
+            Ok(())
+        }
+        Commands::Schedule(cmd) => run_schedule(output, &ctx, cmd),
+        Commands::Tunnel(cmd) => run_tunnel(output, &ctx, cmd),
+        Commands::Sftp(cmd) => run_sftp(output, &ctx, cmd),
+        Commands::ProxyCommand { project, zone, instance, port, backend } => {
+            tunnel::proxy_stdio(&project, &zone, &instance, port, backend.into(), &ctx)
+        }
+        Commands::SshConfig { project, user, identity_file, alias, in_place, remove } => {
+            let target = if in_place { SshConfigTarget::MainConfig } else { SshConfigTarget::IncludeFile };
+            if remove {
+                let removed = ssh_config::remove_project_block(&project, target)?;
+                return report(output, &project, if removed { "ssh config block removed" } else { "no managed block" });
+            }
+            // Point ProxyCommand at this very binary so it works outside $PATH
+            let proxy_command_bin = std::env::current_exe()
+                .ok()
+                .and_then(|p| p.to_str().map(|s| s.to_string()))
+                .unwrap_or_else(|| "lcc".to_string());
+            let opts = SshConfigOptions {
+                user,
+                identity_file,
+                alias_prefix: alias,
+                proxy_command_bin,
+                context: ctx.clone(),
+                target,
+            };
+            let update = ssh_config::sync_ssh_config(&project, &opts)?;
+            emit(output, &update.hosts, &["HOST"], |h| vec![h.clone()])?;
+            eprintln!(
+                "{} {}",
+                if update.changed { "Updated" } else { "Unchanged:" },
+                update.path.display()
+            );
+            Ok(())
+        }
+        Commands::Rdp { instance, remote_port, ports, username, domain, fullscreen } => {
+            let local_port = tunnel::open_tunnel(TunnelSpec {
+                key: instance.key(remote_port, &ctx),
+                backend: TunnelBackendKind::Auto,
+                local_port: ports.spec(),
+            })?
+            .local_port;
+            let settings = RdpSettings { username, domain, fullscreen, ..Default::default() };
+            remmina::launch_remmina(local_port, &instance.instance, settings)?;
+            eprintln!("RDP tunnel open on 127.0.0.1:{} - press Ctrl-C to close", local_port);
+            hold_tunnel(&instance.key(remote_port, &ctx), local_port)
+        }
+    }
+}
+
+fn run_tunnel(output: OutputFormat, ctx: &GcloudContext, cmd: TunnelCommand) -> Result<()> {
+    match cmd {
+        TunnelCommand::Start { instance, remote_port, backend, ports, bind, supervise, metrics } => {
+            let key = instance.key(remote_port, ctx).with_bind_address(bind);
+            let local_port = tunnel::open_tunnel(TunnelSpec {
+                key: key.clone(),
+                backend: backend.into(),
+                local_port: ports.spec(),
+            })?
+            .local_port;
+            if supervise {
+                supervisor::start_supervisor(SupervisorConfig::default())?;
+            }
+            if let Some(address) = metrics {
+                let address = metrics::start_metrics_server(address)?;
+                eprintln!("Metrics on http://{}/metrics", address);
+            }
+
+            let record = TunnelRecord {
+                pid: std::process::id(),
+                project: instance.project.clone(),
+                zone: instance.zone.clone(),
+                instance: instance.instance.clone(),
+                remote_port,
+                local_port,
+                bind_address: bind,
+            };
+            emit(output, std::slice::from_ref(&record), &["INSTANCE", "REMOTE_PORT", "LOCAL"], |r| {
+                vec![r.instance.clone(), r.remote_port.to_string(), r.local_address()]
+            })?;
+
+            let record_path = write_record(&record)?;
+            let result = hold_tunnel(&key, local_port);
+            let _ = std::fs::remove_file(record_path);
+            let _ = supervisor::stop_supervisor();
+            let _ = metrics::stop_metrics_server();
+            result
+        }
+        TunnelCommand::Stop { instance, remote_port, project, zone } => {
+            let mut matching: Vec<TunnelRecord> = read_records()?
+                .into_iter()
+                .filter(|r| r.instance == instance && r.remote_port == remote_port)
+                .filter(|r| project.as_ref().is_none_or(|p| &r.project == p))
+                .filter(|r| zone.as_ref().is_none_or(|z| &r.zone == z))
+                .collect();
+            let record = match matching.len() {
+                0 => return Err(anyhow!("No lcc tunnel for {}:{}", instance, remote_port)),
+                1 => matching.remove(0),
+                _ => {
+                    let owners: Vec<String> = matching.iter().map(|r| format!("{}/{}", r.project, r.zone)).collect();
+                    return Err(anyhow!(
+                        "Several lcc tunnels for {}:{} ({}); pass --project/--zone",
+                        instance,
+                        remote_port,
+                        owners.join(", ")
+                    ));
+                }
+            };
+            tunnel_state::terminate_process(record.pid)?;
+            report(output, &instance, "tunnel stopped")
+        }
+        TunnelCommand::List => {
+            let records = read_records()?;
+            emit(output, &records, &["PID", "PROJECT", "ZONE", "INSTANCE", "REMOTE_PORT", "LOCAL"], |r| {
+                vec![
+                    r.pid.to_string(),
+                    r.project.clone(),
+                    r.zone.clone(),
+                    r.instance.clone(),
+                    r.remote_port.to_string(),
+                    r.local_address(),
+                ]
+            })
+        }
+    }
+}
+
+fn run_schedule(output: OutputFormat, ctx: &GcloudContext, cmd: ScheduleCommand) -> Result<()> {
+    match cmd {
+        ScheduleCommand::List => {
+            let now = chrono::Utc::now();
+            let schedules = schedules::list_schedules()?;
+            emit(output, &schedules, &["ID", "START", "STOP", "TIME_ZONE", "ENABLED", "NEXT"], |s| {
+                let next = s.upcoming(now, 1).ok().and_then(|runs| runs.into_iter().next()).filter(|_| s.enabled);
+                vec![
+                    s.id.clone(),
+                    opt(s.start.as_ref().map(CronExpr::as_str)),
+                    opt(s.stop.as_ref().map(CronExpr::as_str)),
+                    s.time_zone.clone(),
+                    s.enabled.to_string(),
+                    opt(next.map(|run| format!("{} {}", run.action.verb(), run.at))),
+                ]
+            })
+        }
+        ScheduleCommand::Add { id, start, stop, time_zone, targets, projects, labels, description, disabled } => {
+            let target = if !targets.is_empty() {
+                BulkTarget::Instances(targets)
+            } else {
+                BulkTarget::Selector {
+                    projects: (!projects.is_empty()).then_some(projects),
+                    filter: InstanceFilter { labels: labels.into_iter().collect(), ..Default::default() },
+                }
+            };
+            let schedule = Schedule {
+                id: id.clone(),
+                description: description.unwrap_or_default(),
+                target,
+                time_zone,
+                start: start.as_deref().map(CronExpr::parse).transpose()?,
+                stop: stop.as_deref().map(CronExpr::parse).transpose()?,
+                enabled: !disabled,
+                last_evaluated: None,
+            };
+            let upcoming = schedule.upcoming(chrono::Utc::now(), 4)?;
+            schedules::save_schedule(schedule)?;
+            report(output, &id, "schedule saved")?;
+            if output == OutputFormat::Table {
+                for run in upcoming {
+                    println!("  next: {} {}", run.action.verb(), run.at);
+                }
+            }
+            Ok(())
+        }
+        ScheduleCommand::Remove { id } => {
+            let removed = schedules::delete_schedule(&id)?;
+            report(output, &id, if removed { "schedule removed" } else { "no such schedule" })
+        }
+        ScheduleCommand::Sync { id } => {
+            let sync = schedules::sync_schedule_to_compute(ctx, &id)?;
+            let mut message = format!("synced to {} ({} instances)", sync.policies.join(", "), sync.instances_attached);
+            if !sync.updated.is_empty() {
+                message.push_str(&format!("; recreated {} with the new schedule", sync.updated.join(", ")));
+            }
+            report(output, &id, &message)
+        }
+        ScheduleCommand::Run => {
+            let events = schedules::subscribe_schedule_events();
+            schedules::start_scheduler(ApiMethod::GcloudCli, ctx.clone(), SchedulerConfig::default())?;
+            eprintln!("Evaluating schedules - press Ctrl-C to stop");
+            std::thread::spawn(move || {
+                for event in events {
+                    if let Ok(line) = serde_json::to_string(&event) {
+                        println!("{}", line);
+                    }
+                }
+            });
+            // Also stops cleanly under systemd (SIGTERM)
+            block_on(async {
+                let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                    .map_err(|e| anyhow!("Failed to install SIGTERM handler: {}", e))?;
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+                Ok(())
+            })?;
+            schedules::stop_scheduler()
+        }
+    }
+}
+
+fn run_sftp(output: OutputFormat, ctx: &GcloudContext, cmd: SftpCommand) -> Result<()> {
+    let target = match &cmd {
+        SftpCommand::Ls { target, .. } | SftpCommand::Get { target, .. } | SftpCommand::Put { target, .. } => target.clone(),
+    };
+    let user = match target.user {
+        Some(user) => user,
+        None => sftp::get_current_username()?,
+    };
+    let args = &target.instance;
+
+    // SFTP rides on a short-lived SSH tunnel owned by this process
+    let port = tunnel::start_tunnel_with_backend(
+        &args.project,
+        &args.zone,
+        &args.instance,
+        22,
+        TunnelBackendKind::Auto,
+        ctx,
+    )?;
+    let host = "127.0.0.1".to_string();
+
+    let result = match cmd {
+        SftpCommand::Ls { remote_path, .. } => {
+            sftp::sftp_list_directory(host, port, user, remote_path).and_then(|entries| {
+                emit(output, &entries, &["TYPE", "SIZE", "NAME"], |e| {
+                    vec![
+                        if e.is_directory { "d".to_string() } else { "-".to_string() },
+                        e.size.to_string(),
+                        e.name.clone(),
+                    ]
+                })
+            })
+        }
+        SftpCommand::Get { remote_path, local_path, .. } => {
+            sftp::sftp_download_file(host, port, user, remote_path, local_path)
+                .and_then(|bytes| report(output, &args.instance, &format!("downloaded {} bytes", bytes)))
+        }
+        SftpCommand::Put { local_path, remote_path, .. } => {
+            sftp::sftp_upload_file(host, port, user, local_path, remote_path)
+                .and_then(|bytes| report(output, &args.instance, &format!("uploaded {} bytes", bytes)))
+        }
+    };
+
+    let _ = tunnel::stop_tunnel(&args.key(22, ctx));
+    result
+}
+
+/// Keep the process (and therefore the tunnel) alive until SIGINT/SIGTERM
+fn hold_tunnel(key: &TunnelKey, local_port: u16) -> Result<()> {
+    block_on(async {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .map_err(|e| anyhow!("Failed to install SIGTERM handler: {}", e))?;
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+        }
+        Ok::<_, anyhow::Error>(())
+    })?;
+
+    tracing::info!(tunnel = %key, local_port = local_port, "Closing tunnel");
+    tunnel::stop_tunnel(key)
+}
+
+/// Field/value rows for `lcc describe` in table mode
+fn describe_rows(d: &InstanceDetails) -> Vec<(String, String)> {
+    let mut rows = vec![
+        ("name".to_string(), d.name.clone()),
+        ("status".to_string(), d.status.clone()),
+        ("zone".to_string(), d.zone.clone()),
+        ("machine_type".to_string(), d.machine_type.clone()),
+        ("created".to_string(), opt(d.creation_timestamp.as_ref())),
+        ("last_start".to_string(), opt(d.last_start_timestamp.as_ref())),
+        ("deletion_protection".to_string(), d.deletion_protection.to_string()),
+        ("spot".to_string(), d.scheduling.is_spot().to_string()),
+        ("on_host_maintenance".to_string(), opt(d.scheduling.on_host_maintenance.as_ref())),
+    ];
+    for nic in &d.network_interfaces {
+        rows.push((
+            nic.name.clone(),
+            format!(
+                "{} ext={} net={} subnet={}",
+                opt(nic.internal_ip.as_ref()),
+                if nic.external_ips.is_empty() { "-".to_string() } else { nic.external_ips.join(",") },
+                opt(nic.network.as_ref()),
+                opt(nic.subnetwork.as_ref()),
+            ),
+        ));
+    }
+    if !d.labels.is_empty() {
+        let labels: Vec<String> = d.labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        rows.push(("labels".to_string(), labels.join(",")));
+    }
+    if !d.tags.is_empty() {
+        rows.push(("tags".to_string(), d.tags.join(",")));
+    }
+    for sa in &d.service_accounts {
+        rows.push(("service_account".to_string(), format!("{} ({} scopes)", sa.email, sa.scopes.len())));
+    }
+    if !d.metadata_keys.is_empty() {
+        rows.push(("metadata_keys".to_string(), d.metadata_keys.join(",")));
+    }
+    if let Some(shielded) = &d.shielded_vm {
+        rows.push((
+            "shielded_vm".to_string(),
+            format!(
+                "secure_boot={} vtpm={} integrity={}",
+                shielded.secure_boot, shielded.vtpm, shielded.integrity_monitoring
+            ),
+        ));
+    }
+    for gpu in &d.guest_accelerators {
+        rows.push(("accelerator".to_string(), format!("{} x{}", gpu.accelerator_type, gpu.count)));
+    }
+    for disk in &d.disks {
+        rows.push((
+            if disk.boot { "boot_disk".to_string() } else { "disk".to_string() },
+            format!(
+                "{} {}GB {}",
+                opt(disk.source.as_ref().or(disk.device_name.as_ref())),
+                opt(disk.disk_size_gb),
+                opt(disk.kind.as_ref()),
+            ),
+        ));
+    }
+    rows
+}
+
+fn records_dir() -> Result<PathBuf> {
+    let base = dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .ok_or_else(|| anyhow!("Could not determine runtime directory"))?;
+    Ok(base.join("linux_cloud_connector").join("lcc-tunnels"))
+}
+
+fn write_record(record: &TunnelRecord) -> Result<PathBuf> {
+    let dir = records_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.json", record.pid));
+    std::fs::write(&path, serde_json::to_vec_pretty(record)?)?;
+    Ok(path)
+}
+
+/// Read tunnel records, pruning those whose owning process is gone
+///
+/// The pid must still be an `lcc tunnel start` for the same tunnel, since
+/// it may have been reused after the owner died.
+fn read_records() -> Result<Vec<TunnelRecord>> {
+    let dir = records_dir()?;
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let mut records = Vec::new();
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let record: Option<TunnelRecord> = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        match record {
+            Some(record) if tunnel_state::is_lcc_tunnel_process(record.pid, &record.key()) => records.push(record),
+            _ => {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+    records.sort_by(|a, b| (&a.instance, a.remote_port).cmp(&(&b.instance, b.remote_port)));
+    Ok(records)
+}
+
+fn parse_instance_ref(value: &str) -> std::result::Result<InstanceRef, String> {
+    match value.split('/').collect::<Vec<_>>().as_slice() {
+        [project, zone, instance] => Ok(InstanceRef {
+            project: project.to_string(),
+            zone: zone.to_string(),
+            instance: instance.to_string(),
+        }),
+        _ => Err(format!("expected PROJECT/ZONE/INSTANCE, got '{}'", value)),
+    }
+}
+
+fn parse_port_range(value: &str) -> std::result::Result<(u16, u16), String> {
+    let (start, end) = value
+        .split_once('-')
+        .ok_or_else(|| format!("expected START-END, got '{}'", value))?;
+    let port = |p: &str| p.trim().parse::<u16>().map_err(|_| format!("invalid port '{}'", p));
+    Ok((port(start)?, port(end)?))
+}
+
+fn parse_label(value: &str) -> std::result::Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))
+}
+
+fn opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+fn report(output: OutputFormat, instance: &str, message: &str) -> Result<()> {
+    match output {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({ "instance": instance, "result": message })
+        ),
+        OutputFormat::Table => println!("{}: {}", instance, message),
+    }
+    Ok(())
+}
+
+/// Print `items` as pretty JSON or as an aligned table
+fn emit<T: Serialize>(
+    output: OutputFormat,
+    items: &[T],
+    headers: &[&str],
+    row: impl Fn(&T) -> Vec<String>,
+) -> Result<()> {
+    match output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(items)?),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = items.iter().map(row).collect();
+            let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+            for r in &rows {
+                for (i, cell) in r.iter().enumerate() {
+                    widths[i] = widths[i].max(cell.len());
+                }
+            }
+            let line = |cells: Vec<String>| {
+                cells
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| format!("{:<width$}", c, width = widths[i]))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            };
+            println!("{}", line(headers.iter().map(|h| h.to_string()).collect()));
+            for r in rows {
+                println!("{}", line(r));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,316 @@
+//! Bulk lifecycle operations
+//!
+//! Runs start/stop/reset/suspend/resume over many instances at once, either
+//! an explicit list or everything matching a label/status selector, with a
+//! bounded number of operations in flight. Every instance gets its own
+//! outcome; one failure never aborts the batch.
+//!
+//! Cancelling stops dispatching: instances not started yet are reported as
+//! cancelled, operations already sent to GCE are waited for.
+
+use anyhow::{Result, anyhow};
+use futures_util::{StreamExt, stream};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+use crate::accounts::GcloudContext;
+use crate::backend::{backend_for, ApiMethod, CloudBackend, InstanceAction};
+use crate::inventory::{collect_inventory, InstanceFilter, InventoryOptions};
+use crate::runtime;
+
+lazy_static! {
+    /// Cancel handles of running FFI batches, by caller-chosen job id
+    static ref BULK_JOBS: Mutex<HashMap<String, CancelHandle>> = Mutex::new(HashMap::new());
+}
+
+/// One instance
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InstanceRef {
+    pub project: String,
+    pub zone: String,
+    pub instance: String,
+}
+
+/// Which instances a batch acts on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BulkTarget {
+    Instances(Vec<InstanceRef>),
+    /// Everything matching `filter` in `projects` (`None` = all accessible)
+    Selector {
+        projects: Option<Vec<String>>,
+        filter: InstanceFilter,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct BulkOptions {
+    /// Operations in flight at the same time
+    pub concurrency: usize,
+    /// Used to resolve [`BulkTarget::Selector`]
+    pub inventory: InventoryOptions,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        Self { concurrency: 5, inventory: InventoryOptions::default() }
+    }
+}
+
+/// Stops a batch from dispatching further operations
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "error", rename_all = "snake_case")]
+pub enum OutcomeStatus {
+    Succeeded,
+    Failed(String),
+    /// Never dispatched because the batch was cancelled
+    Cancelled,
+}
+
+/// Result for one instance, sent on the progress channel as it completes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceOutcome {
+    pub target: InstanceRef,
+    pub status: OutcomeStatus,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkReport {
+    pub action: InstanceAction,
+    /// In the order the targets were given
+    pub outcomes: Vec<InstanceOutcome>,
+    /// Projects the selector could not list
+    pub skipped_projects: Vec<String>,
+}
+
+impl BulkReport {
+    pub fn succeeded(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.status == OutcomeStatus::Succeeded).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.iter().filter(|o| matches!(o.status, OutcomeStatus::Failed(_))).count()
+    }
+
+    pub fn cancelled(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.status == OutcomeStatus::Cancelled).count()
+    }
+}
+
+/// Run `action` on every instance of `target`
+///
+/// Errors only if the selector cannot be resolved at all; per-instance
+/// failures are in the report.
+pub async fn run_bulk_action(
+    backend: &dyn CloudBackend,
+    action: InstanceAction,
+    target: BulkTarget,
+    options: &BulkOptions,
+    cancel: &CancelHandle,
+    progress: Option<&mpsc::UnboundedSender<InstanceOutcome>>,
+) -> Result<BulkReport> {
+    let (targets, skipped_projects) = match target {
+        BulkTarget::Instances(targets) => (targets, Vec::new()),
+        BulkTarget::Selector { projects, filter } => {
+            let inventory = collect_inventory(backend, projects, &filter, &options.inventory).await?;
+            let targets = inventory
+                .entries
+                .into_iter()
+                .map(|e| InstanceRef { project: e.project_id, zone: e.instance.zone, instance: e.instance.name })
+                .collect();
+            (targets, inventory.failures.into_iter().map(|f| f.project_id).collect())
+        }
+    };
+
+    tracing::info!(
+        action = action.verb(),
+        instances = targets.len(),
+        concurrency = options.concurrency,
+        backend = backend.name(),
+        "Starting bulk operation"
+    );
+
+    let mut outcomes: Vec<(usize, InstanceOutcome)> = stream::iter(targets.into_iter().enumerate())
+        .map(|(index, target)| async move {
+            let start = Instant::now();
+            let status = if cancel.is_cancelled() {
+                OutcomeStatus::Cancelled
+            } else {
+                match backend.run_action(action, &target.project, &target.zone, &target.instance).await {
+                    Ok(()) => OutcomeStatus::Succeeded,
+                    Err(e) => {
+                        tracing::warn!(instance = %target.instance, action = action.verb(), error = %e, "Bulk operation failed for instance");
+                        OutcomeStatus::Failed(e.to_string())
+                    }
+                }
+            };
+            let outcome = InstanceOutcome { target, status, elapsed_ms: start.elapsed().as_millis() as u64 };
+            if let Some(tx) = progress {
+                let _ = tx.send(outcome.clone());
+            }
+            (index, outcome)
+        })
+        .buffer_unordered(options.concurrency.max(1))
+        .collect()
+        .await;
+
+    outcomes.sort_by_key(|(index, _)| *index);
+    let report = BulkReport {
+        action,
+        outcomes: outcomes.into_iter().map(|(_, o)| o).collect(),
+        skipped_projects,
+    };
+    tracing::info!(
+        action = action.verb(),
+        succeeded = report.succeeded(),
+        failed = report.failed(),
+        cancelled = report.cancelled(),
+        "Bulk operation finished"
+    );
+    Ok(report)
+}
+
+/// Synchronous wrapper for FFI bridge
+///
+/// `job_id` lets another call stop the batch with [`cancel_bulk_action`];
+/// `on_outcome` runs as each instance finishes.
+pub fn run_bulk_action_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    job_id: &str,
+    action: InstanceAction,
+    target: BulkTarget,
+    options: &BulkOptions,
+    mut on_outcome: impl FnMut(InstanceOutcome),
+) -> Result<BulkReport> {
+    let cancel = CancelHandle::new();
+    BULK_JOBS
+        .lock()
+        .map_err(|_| anyhow!("Bulk job registry lock poisoned"))?
+        .insert(job_id.to_string(), cancel.clone());
+
+    let result = runtime::block_on(async {
+        let backend = backend_for(method, context).await?;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let run = run_bulk_action(backend.as_ref(), action, target, options, &cancel, Some(&tx));
+        tokio::pin!(run);
+        loop {
+            tokio::select! {
+                Some(outcome) = rx.recv() => on_outcome(outcome),
+                report = &mut run => {
+                    while let Ok(outcome) = rx.try_recv() {
+                        on_outcome(outcome);
+                    }
+                    return report;
+                }
+            }
+        }
+    });
+
+    if let Ok(mut jobs) = BULK_JOBS.lock() {
+        jobs.remove(job_id);
+    }
+    result
+}
+
+/// Stop a batch started with [`run_bulk_action_via`]; false if it isn't running
+pub fn cancel_bulk_action(job_id: &str) -> bool {
+    match BULK_JOBS.lock().ok().and_then(|jobs| jobs.get(job_id).cloned()) {
+        Some(cancel) => {
+            tracing::info!(job_id = job_id, "Cancelling bulk operation");
+            cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{test_instance, test_target, FakeBackend};
+
+    #[tokio::test]
+    async fn test_bulk_reports_each_instance() {
+        let fake = FakeBackend::new();
+        fake.add_instance("dev-project", test_instance("dev-1", "europe-west1-b", "RUNNING"));
+        fake.add_instance("dev-project", test_instance("dev-2", "europe-west1-b", "TERMINATED"));
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let report = run_bulk_action(
+            &fake,
+            InstanceAction::Reset,
+            BulkTarget::Instances(vec![test_target("dev-1"), test_target("dev-2"), test_target("missing")]),
+            &BulkOptions { concurrency: 2, ..Default::default() },
+            &CancelHandle::new(),
+            Some(&tx),
+        )
+        .await
+        .unwrap();
+        drop(tx);
+
+        assert_eq!(report.succeeded(), 1);
+        assert_eq!(report.failed(), 2);
+        assert_eq!(report.outcomes[0].target.instance, "dev-1");
+        let mut streamed = 0;
+        while rx.recv().await.is_some() {
+            streamed += 1;
+        }
+        assert_eq!(streamed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_selector_and_cancellation() {
+        let fake = FakeBackend::new();
+        fake.add_instance("dev-project", test_instance("dev-1", "europe-west1-b", "RUNNING"));
+        fake.add_instance("dev-project", test_instance("dev-2", "europe-west1-b", "RUNNING"));
+        fake.add_instance("dev-project", test_instance("prod-1", "europe-west1-b", "RUNNING"));
+
+        let selector = BulkTarget::Selector {
+            projects: Some(vec!["dev-project".to_string()]),
+            filter: InstanceFilter { name_regex: Some("^dev-".to_string()), ..Default::default() },
+        };
+        let report = run_bulk_action(&fake, InstanceAction::Stop, selector, &BulkOptions::default(), &CancelHandle::new(), None)
+            .await
+            .unwrap();
+        assert_eq!(report.succeeded(), 2);
+        assert_eq!(fake.describe_instance("dev-project", "europe-west1-b", "prod-1").await.unwrap().status, "RUNNING");
+
+        let cancel = CancelHandle::new();
+        cancel.cancel();
+        let report = run_bulk_action(
+            &fake,
+            InstanceAction::Start,
+            BulkTarget::Instances(vec![test_target("dev-1")]),
+            &BulkOptions::default(),
+            &cancel,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.cancelled(), 1);
+        assert_eq!(fake.describe_instance("dev-project", "europe-west1-b", "dev-1").await.unwrap().status, "TERMINATED");
+    }
+}
@@ -0,0 +1,683 @@
+//! Running-cost estimates
+//!
+//! Prices an instance from its machine type, accelerators, disks and
+//! provisioning model (spot vs standard), and what it has cost since its
+//! last start. Prices come from a versioned JSON table bundled with the app
+//! (`data/gce_prices.json`); a refreshed copy can be written to the config
+//! directory from the Cloud Billing Catalog API and takes precedence.
+//!
+//! These are list-price estimates: committed-use and sustained-use
+//! discounts, licences, network egress and snapshots are not included.
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, stream};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::accounts::GcloudContext;
+use crate::backend::{backend_for, ApiMethod, CloudBackend};
+use crate::gcloud_client_poc::{CloudBillingClient, GcpAuthClient};
+use crate::instance_details::InstanceDetails;
+use crate::inventory::{collect_inventory, InstanceFilter, InventoryOptions, ProjectFailure};
+use crate::machine_types::{offline_spec, MachineSpec, SpecSource};
+use crate::pagination::ListOptions;
+use crate::runtime;
+
+/// Version of the price file layout this build understands
+pub const SUPPORTED_FORMAT_VERSION: u32 = 1;
+
+/// Disk prices are per GB-month; GCE bills a month as 730 hours
+const HOURS_PER_MONTH: f64 = 730.0;
+
+/// Size of one local SSD partition when the API does not report it
+const LOCAL_SSD_GB: u64 = 375;
+
+const BUNDLED_PRICES: &str = include_str!("../data/gce_prices.json");
+
+lazy_static! {
+    /// Machine series SKUs: "E2 Instance Core running in Americas",
+    /// "Spot Preemptible N2 Instance Ram running in EMEA", "Compute optimized Core ..."
+    static ref SERIES_SKU_REGEX: Regex = Regex::new(
+        r"^(?:(?:Spot )?Preemptible )?(E2|N1 Predefined|N2|N2D AMD|N4|T2D AMD|C2D AMD|C3|A2|G2|Compute optimized) (?:Instance )?(Core|Ram) running in "
+    ).unwrap();
+    /// Zonal persistent disk and local SSD capacity SKUs
+    static ref DISK_SKU_REGEX: Regex = Regex::new(
+        r"^(Storage PD Capacity|Balanced PD Capacity|SSD backed PD Capacity|SSD backed Local Storage)( in .*)?$"
+    ).unwrap();
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SeriesPrice {
+    pub vcpu_hour: f64,
+    /// Per GiB of memory
+    pub memory_gb_hour: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spot_vcpu_hour: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spot_memory_gb_hour: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GpuPrice {
+    pub hour: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spot_hour: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegionPrices {
+    /// By machine series (`e2`, `n2d`, ...)
+    #[serde(default)]
+    pub series: BTreeMap<String, SeriesPrice>,
+    /// Shared-core types are priced per machine, not per vCPU
+    #[serde(default)]
+    pub shared_core: BTreeMap<String, f64>,
+    /// By accelerator type (`nvidia-tesla-t4`, ...)
+    #[serde(default)]
+    pub gpus: BTreeMap<String, GpuPrice>,
+    /// By disk type (`pd-balanced`, `local-ssd`, ...)
+    #[serde(default)]
+    pub disks_gb_month: BTreeMap<String, f64>,
+}
+
+/// Contents of `gce_prices.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceTable {
+    pub format_version: u32,
+    pub currency: String,
+    /// Date the prices were taken (YYYY-MM-DD)
+    pub updated: String,
+    pub source: String,
+    /// Used for regions the table does not list
+    pub default_region: String,
+    /// Spot price as a fraction of on-demand when no spot price is listed
+    pub spot_factor: f64,
+    /// Multiplier for custom machine shapes
+    pub custom_premium: f64,
+    /// Persistent disks are priced as this type (the instance does not report it)
+    pub default_disk_type: String,
+    pub regions: BTreeMap<String, RegionPrices>,
+}
+
+impl PriceTable {
+    /// The table shipped with this build
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_PRICES.as_bytes()).expect("bundled price table is valid")
+    }
+
+    pub fn parse(json: &[u8]) -> Result<Self> {
+        let table: PriceTable = serde_json::from_slice(json)
+            .map_err(|e| anyhow!("Failed to parse price table: {}", e))?;
+        if table.format_version != SUPPORTED_FORMAT_VERSION {
+            return Err(anyhow!(
+                "Unsupported price table format version {} (expected {})",
+                table.format_version,
+                SUPPORTED_FORMAT_VERSION
+            ));
+        }
+        if !table.regions.contains_key(&table.default_region) {
+            return Err(anyhow!("Price table has no prices for its default region {}", table.default_region));
+        }
+        Ok(table)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&bytes).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// `<config dir>/linux_cloud_connector/gce_prices.json`
+    pub fn override_path() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or_else(|| anyhow!("Could not determine config directory"))?
+            .join("linux_cloud_connector")
+            .join("gce_prices.json"))
+    }
+
+    /// The refreshed table from the config directory, or the bundled one
+    ///
+    /// An unreadable or outdated override is logged and ignored.
+    pub fn load_default() -> Self {
+        if let Ok(path) = Self::override_path() {
+            if path.exists() {
+                match Self::load(&path) {
+                    Ok(table) => return table,
+                    Err(e) => tracing::warn!(error = %e, "Ignoring price table override"),
+                }
+            }
+        }
+        Self::bundled()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Cost of one instance
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    pub currency: String,
+    pub machine_type: String,
+    /// Region of the instance
+    pub region: String,
+    /// Region whose prices were used (differs when the table lacks `region`)
+    pub priced_region: String,
+    pub spot: bool,
+    /// vCPUs and memory, per hour while running
+    pub compute_hourly: f64,
+    pub accelerator_hourly: f64,
+    /// Persistent disks and local SSD, per hour
+    pub disk_hourly: f64,
+    pub running_hourly: f64,
+    /// What the instance keeps costing while stopped (persistent disks)
+    pub stopped_hourly: f64,
+    /// `running_hourly` or `stopped_hourly` depending on the status
+    pub current_hourly: f64,
+    /// Start of the current session (`lastStartTimestamp`), if running
+    pub running_since: Option<DateTime<Utc>>,
+    pub session_hours: Option<f64>,
+    pub session_cost: Option<f64>,
+    /// Why the estimate is approximate; empty when every price was found
+    pub notes: Vec<String>,
+}
+
+impl CostEstimate {
+    pub fn is_approximate(&self) -> bool {
+        !self.notes.is_empty()
+    }
+}
+
+/// Region of a zone (`europe-west1-b` -> `europe-west1`)
+fn region_of(zone: &str) -> &str {
+    zone.rsplit_once('-').map(|(region, _)| region).unwrap_or(zone)
+}
+
+/// Series of a machine type (`n2d-standard-8` -> `n2d`, `custom-4-8192` -> `n1`)
+fn series_of(machine_type: &str) -> &str {
+    match machine_type.split('-').next() {
+        Some("custom") | None => "n1",
+        Some(series) => series,
+    }
+}
+
+/// Price `details` with `table`
+///
+/// `spec` is the shape of the machine type; without it only accelerators
+/// and disks can be priced.
+pub fn estimate_instance(
+    table: &PriceTable,
+    details: &InstanceDetails,
+    spec: Option<&MachineSpec>,
+    now: DateTime<Utc>,
+) -> CostEstimate {
+    let mut notes = Vec::new();
+    let region = region_of(&details.zone).to_string();
+    let (priced_region, prices) = match table.regions.get(&region) {
+        Some(prices) => (region.clone(), prices),
+        None => {
+            notes.push(format!("No prices for {}; using {}", region, table.default_region));
+            (table.default_region.clone(), &table.regions[&table.default_region])
+        }
+    };
+    let spot = details.scheduling.is_spot();
+    let spot_price = |on_demand: f64, listed: Option<f64>| {
+        if spot { listed.unwrap_or(on_demand * table.spot_factor) } else { on_demand }
+    };
+
+    let compute_hourly = if let Some(&hourly) = prices.shared_core.get(&details.machine_type) {
+        spot_price(hourly, None)
+    } else {
+        let series = series_of(&details.machine_type);
+        match (spec, prices.series.get(series)) {
+            (Some(spec), Some(price)) => {
+                let cpus = spec.guest_cpus as f64;
+                let memory_gb = spec.memory_mb as f64 / 1024.0;
+                let hourly = cpus * spot_price(price.vcpu_hour, price.spot_vcpu_hour)
+                    + memory_gb * spot_price(price.memory_gb_hour, price.spot_memory_gb_hour);
+                if spec.source == SpecSource::CustomName { hourly * table.custom_premium } else { hourly }
+            }
+            (None, _) => {
+                notes.push(format!("Unknown machine type {}", details.machine_type));
+                0.0
+            }
+            (Some(_), None) => {
+                notes.push(format!("No prices for the {} series in {}", series, priced_region));
+                0.0
+            }
+        }
+    };
+
+    // Bundled GPUs (a2, g2, ...) are listed on the instance too; the spec is the fallback
+    let accelerators: Vec<(&str, u32)> = if details.guest_accelerators.is_empty() {
+        spec.map(|s| s.accelerators.iter().map(|a| (a.accelerator_type.as_str(), a.count)).collect())
+            .unwrap_or_default()
+    } else {
+        details.guest_accelerators.iter().map(|a| (a.accelerator_type.as_str(), a.count)).collect()
+    };
+    let mut accelerator_hourly = 0.0;
+    for (kind, count) in accelerators {
+        match prices.gpus.get(kind) {
+            Some(price) => accelerator_hourly += count as f64 * spot_price(price.hour, price.spot_hour),
+            None => notes.push(format!("No price for accelerator {} in {}", kind, priced_region)),
+        }
+    }
+
+    let (mut persistent_gb, mut local_gb) = (0u64, 0u64);
+    for disk in &details.disks {
+        if disk.kind.as_deref() == Some("SCRATCH") {
+            local_gb += disk.disk_size_gb.unwrap_or(LOCAL_SSD_GB);
+        } else {
+            persistent_gb += disk.disk_size_gb.unwrap_or(0);
+        }
+    }
+    let disk_price = |kind: &str, notes: &mut Vec<String>| {
+        prices.disks_gb_month.get(kind).copied().unwrap_or_else(|| {
+            notes.push(format!("No price for {} disks in {}", kind, priced_region));
+            0.0
+        }) / HOURS_PER_MONTH
+    };
+    let mut stopped_hourly = 0.0;
+    if persistent_gb > 0 {
+        notes.push(format!("Persistent disks priced as {}", table.default_disk_type));
+        stopped_hourly = persistent_gb as f64 * disk_price(&table.default_disk_type, &mut notes);
+    }
+    let local_hourly = if local_gb > 0 { local_gb as f64 * disk_price("local-ssd", &mut notes) } else { 0.0 };
+    let disk_hourly = stopped_hourly + local_hourly;
+
+    let running_hourly = compute_hourly + accelerator_hourly + disk_hourly;
+    let running = details.status == "RUNNING";
+    let running_since = details
+        .last_start_timestamp
+        .as_deref()
+        .filter(|_| running)
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+        .map(|ts| ts.with_timezone(&Utc));
+    let session_hours = running_since.map(|since| (now - since).num_seconds().max(0) as f64 / 3600.0);
+
+    CostEstimate {
+        currency: table.currency.clone(),
+        machine_type: details.machine_type.clone(),
+        region,
+        priced_region,
+        spot,
+        compute_hourly,
+        accelerator_hourly,
+        disk_hourly,
+        running_hourly,
+        stopped_hourly,
+        current_hourly: if running { running_hourly } else { stopped_hourly },
+        running_since,
+        session_hours,
+        session_cost: session_hours.map(|hours| hours * running_hourly),
+        notes,
+    }
+}
+
+/// Describe one instance through `backend` and price it
+///
+/// Machine types missing from the offline table are looked up in the zone.
+pub async fn estimate_instance_cost(
+    backend: &dyn CloudBackend,
+    table: &PriceTable,
+    project: &str,
+    zone: &str,
+    instance: &str,
+    now: DateTime<Utc>,
+) -> Result<CostEstimate> {
+    let details = backend.describe_instance_details(project, zone, instance).await?;
+    let spec = match offline_spec(&details.machine_type) {
+        Some(spec) => Some(spec),
+        None => backend.machine_type_in_zone(project, zone, &details.machine_type).await.unwrap_or_else(|e| {
+            tracing::warn!(machine_type = %details.machine_type, error = %e, "Could not look up machine type for pricing");
+            None
+        }),
+    };
+    Ok(estimate_instance(table, &details, spec.as_ref(), now))
+}
+
+/// One instance in a [`ProjectCost`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceCost {
+    pub instance: String,
+    pub zone: String,
+    pub status: String,
+    #[serde(flatten)]
+    pub estimate: CostEstimate,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectCost {
+    pub project_id: String,
+    /// Sorted by instance name
+    pub instances: Vec<InstanceCost>,
+    /// Sum of what every instance costs per hour right now
+    pub current_hourly: f64,
+    /// Sum of the running sessions so far
+    pub session_total: f64,
+    pub running_instances: usize,
+    /// Instances that could not be described, with the error
+    pub unpriced: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FleetCost {
+    pub currency: String,
+    /// Date of the price table used
+    pub prices_updated: String,
+    /// Sorted by project
+    pub projects: Vec<ProjectCost>,
+    pub failures: Vec<ProjectFailure>,
+    pub current_hourly: f64,
+    pub session_total: f64,
+}
+
+/// Price every instance of `projects` (`None` = every active project)
+///
+/// Instances are described `options.concurrency` at a time; projects and
+/// instances that fail are reported, not fatal.
+pub async fn estimate_fleet_cost(
+    backend: &dyn CloudBackend,
+    table: &PriceTable,
+    projects: Option<Vec<String>>,
+    options: &InventoryOptions,
+    now: DateTime<Utc>,
+) -> Result<FleetCost> {
+    let inventory = collect_inventory(backend, projects, &InstanceFilter::default(), options).await?;
+
+    let results: Vec<_> = stream::iter(inventory.entries)
+        .map(|entry| async move {
+            let estimate = estimate_instance_cost(
+                backend,
+                table,
+                &entry.project_id,
+                &entry.instance.zone,
+                &entry.instance.name,
+                now,
+            )
+            .await;
+            (entry, estimate)
+        })
+        .buffer_unordered(options.concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut by_project: BTreeMap<String, ProjectCost> = BTreeMap::new();
+    for (entry, estimate) in results {
+        let project = by_project
+            .entry(entry.project_id.clone())
+            .or_insert_with(|| ProjectCost { project_id: entry.project_id.clone(), ..Default::default() });
+        match estimate {
+            Ok(estimate) => {
+                project.current_hourly += estimate.current_hourly;
+                project.session_total += estimate.session_cost.unwrap_or(0.0);
+                if entry.instance.status == "RUNNING" {
+                    project.running_instances += 1;
+                }
+                project.instances.push(InstanceCost {
+                    instance: entry.instance.name,
+                    zone: entry.instance.zone,
+                    status: entry.instance.status,
+                    estimate,
+                });
+            }
+            Err(e) => {
+                tracing::warn!(project = %entry.project_id, instance = %entry.instance.name, error = %e, "Could not price instance");
+                project.unpriced.push(format!("{}: {}", entry.instance.name, e));
+            }
+        }
+    }
+
+    let mut fleet = FleetCost {
+        currency: table.currency.clone(),
+        prices_updated: table.updated.clone(),
+        failures: inventory.failures,
+        ..Default::default()
+    };
+    for (_, mut project) in by_project {
+        project.instances.sort_by(|a, b| a.instance.cmp(&b.instance));
+        fleet.current_hourly += project.current_hourly;
+        fleet.session_total += project.session_total;
+        fleet.projects.push(project);
+    }
+    Ok(fleet)
+}
+
+/// Synchronous wrapper for FFI bridge: one instance, with the default price table
+pub fn estimate_instance_cost_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    project: &str,
+    zone: &str,
+    instance: &str,
+) -> Result<CostEstimate> {
+    let table = PriceTable::load_default();
+    runtime::block_on(async {
+        let backend = backend_for(method, context).await?;
+        estimate_instance_cost(backend.as_ref(), &table, project, zone, instance, Utc::now()).await
+    })
+}
+
+/// Synchronous wrapper for FFI bridge: per-project totals, with the default price table
+pub fn estimate_fleet_cost_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    projects: Option<Vec<String>>,
+    options: &InventoryOptions,
+) -> Result<FleetCost> {
+    let table = PriceTable::load_default();
+    runtime::block_on(async {
+        let backend = backend_for(method, context).await?;
+        estimate_fleet_cost(backend.as_ref(), &table, projects, options, Utc::now()).await
+    })
+}
+
+/// Price of one SKU in its last tier (`units` + `nanos`)
+fn sku_unit_price(sku: &serde_json::Value) -> Option<f64> {
+    let rate = sku["pricingInfo"][0]["pricingExpression"]["tieredRates"].as_array()?.last()?;
+    let price = &rate["unitPrice"];
+    let units = match &price["units"] {
+        serde_json::Value::String(s) => s.parse::<f64>().ok()?,
+        other => other.as_f64().unwrap_or(0.0),
+    };
+    Some(units + price["nanos"].as_f64().unwrap_or(0.0) / 1e9)
+}
+
+/// Update `table` with Compute Engine SKUs from the Cloud Billing Catalog API
+///
+/// Only regions already in the table are touched. Machine series prices and
+/// zonal disk capacity are read; shared-core types and GPUs keep their
+/// bundled prices. Returns how many prices changed.
+pub fn apply_billing_skus(table: &mut PriceTable, skus: &[serde_json::Value]) -> usize {
+    let mut updated = 0;
+    for sku in skus {
+        let description = sku["description"].as_str().unwrap_or_default();
+        let spot = match sku["category"]["usageType"].as_str() {
+            Some("OnDemand") => false,
+            Some("Preemptible") => true,
+            _ => continue,
+        };
+        let Some(price) = sku_unit_price(sku) else { continue };
+        let regions: Vec<&str> = sku["serviceRegions"]
+            .as_array()
+            .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default();
+
+        if let Some(caps) = SERIES_SKU_REGEX.captures(description) {
+            let series = match &caps[1] {
+                "Compute optimized" => "c2".to_string(),
+                name => name.split(' ').next().unwrap_or(name).to_lowercase(),
+            };
+            let core = &caps[2] == "Core";
+            for region in &regions {
+                let Some(prices) = table.regions.get_mut(*region) else { continue };
+                let entry = prices.series.entry(series.clone()).or_default();
+                match (core, spot) {
+                    (true, false) => entry.vcpu_hour = price,
+                    (false, false) => entry.memory_gb_hour = price,
+                    (true, true) => entry.spot_vcpu_hour = Some(price),
+                    (false, true) => entry.spot_memory_gb_hour = Some(price),
+                }
+                updated += 1;
+            }
+        } else if let Some(caps) = DISK_SKU_REGEX.captures(description) {
+            if spot {
+                continue;
+            }
+            let kind = match &caps[1] {
+                "Storage PD Capacity" => "pd-standard",
+                "Balanced PD Capacity" => "pd-balanced",
+                "SSD backed PD Capacity" => "pd-ssd",
+                _ => "local-ssd",
+            };
+            for region in &regions {
+                if let Some(prices) = table.regions.get_mut(*region) {
+                    prices.disks_gb_month.insert(kind.to_string(), price);
+                    updated += 1;
+                }
+            }
+        }
+    }
+
+    // A series seen only as spot (or only as memory) cannot be priced
+    for prices in table.regions.values_mut() {
+        prices.series.retain(|_, p| p.vcpu_hour > 0.0 && p.memory_gb_hour > 0.0);
+    }
+    updated
+}
+
+/// Refresh the price table from the Cloud Billing Catalog API and save it as the override
+pub async fn refresh_price_table(context: &GcloudContext) -> Result<PriceTable> {
+    let auth = GcpAuthClient::for_context(context).await?;
+    let skus = CloudBillingClient::with_auth(auth)
+        .list_compute_skus(&ListOptions::default())
+        .await?;
+
+    let mut table = PriceTable::load_default();
+    let updated = apply_billing_skus(&mut table, &skus);
+    if updated == 0 {
+        return Err(anyhow!("Cloud Billing Catalog returned no usable Compute Engine prices"));
+    }
+    table.updated = Utc::now().format("%Y-%m-%d").to_string();
+    table.source = "Cloud Billing Catalog API (on-demand list prices)".to_string();
+    table.save(&PriceTable::override_path()?)?;
+    tracing::info!(skus = skus.len(), prices = updated, "Refreshed price table");
+    Ok(table)
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn refresh_price_table_sync(context: &GcloudContext) -> Result<PriceTable> {
+    runtime::block_on(refresh_price_table(context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance_details::{AcceleratorDetails, AttachedDiskDetails, SchedulingDetails};
+    use serde_json::json;
+
+    fn details(machine_type: &str, zone: &str) -> InstanceDetails {
+        InstanceDetails {
+            name: "dev-1".to_string(),
+            status: "RUNNING".to_string(),
+            zone: zone.to_string(),
+            machine_type: machine_type.to_string(),
+            last_start_timestamp: Some("2025-06-01T08:00:00.000+00:00".to_string()),
+            disks: vec![AttachedDiskDetails {
+                boot: true,
+                kind: Some("PERSISTENT".to_string()),
+                disk_size_gb: Some(73),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_estimate_standard_spot_and_shared_core() {
+        let table = PriceTable::bundled();
+        let now = DateTime::parse_from_rfc3339("2025-06-01T18:00:00Z").unwrap().with_timezone(&Utc);
+        let e2 = &table.regions["us-central1"].series["e2"];
+
+        let vm = details("e2-standard-4", "us-central1-a");
+        let estimate = estimate_instance(&table, &vm, offline_spec("e2-standard-4").as_ref(), now);
+        assert!(close(estimate.compute_hourly, 4.0 * e2.vcpu_hour + 16.0 * e2.memory_gb_hour));
+        // 73 GB of pd-balanced at 0.1/GB-month = 0.01/h
+        assert!(close(estimate.disk_hourly, 0.01));
+        assert!(close(estimate.running_hourly, estimate.compute_hourly + 0.01));
+        assert_eq!(estimate.session_hours, Some(10.0));
+        assert!(close(estimate.session_cost.unwrap(), 10.0 * estimate.running_hourly));
+        assert_eq!(estimate.notes, vec!["Persistent disks priced as pd-balanced"]);
+
+        let spot = InstanceDetails {
+            scheduling: SchedulingDetails { provisioning_model: Some("SPOT".to_string()), ..Default::default() },
+            guest_accelerators: vec![AcceleratorDetails { accelerator_type: "nvidia-tesla-t4".to_string(), count: 2 }],
+            status: "TERMINATED".to_string(),
+            ..vm
+        };
+        let estimate = estimate_instance(&table, &spot, offline_spec("e2-standard-4").as_ref(), now);
+        assert!(estimate.spot);
+        assert!(close(estimate.compute_hourly, 4.0 * e2.spot_vcpu_hour.unwrap() + 16.0 * e2.spot_memory_gb_hour.unwrap()));
+        assert!(close(estimate.accelerator_hourly, 2.0 * 0.14));
+        assert!(close(estimate.current_hourly, 0.01));
+        assert_eq!(estimate.session_cost, None);
+
+        // Unknown region falls back to the default one; shared-core priced per machine
+        let micro = details("e2-micro", "me-central2-a");
+        let estimate = estimate_instance(&table, &micro, offline_spec("e2-micro").as_ref(), now);
+        assert_eq!(estimate.priced_region, "us-central1");
+        assert!(close(estimate.compute_hourly, 0.008376));
+        assert!(estimate.notes[0].starts_with("No prices for me-central2"));
+    }
+
+    #[test]
+    fn test_price_table_version_and_billing_skus() {
+        let mut raw: serde_json::Value = serde_json::from_str(BUNDLED_PRICES).unwrap();
+        raw["format_version"] = json!(2);
+        assert!(PriceTable::parse(raw.to_string().as_bytes()).is_err());
+
+        let sku = |description: &str, usage: &str, regions: &[&str], units: &str, nanos: i64| {
+            json!({
+                "description": description,
+                "category": { "usageType": usage },
+                "serviceRegions": regions,
+                "pricingInfo": [{ "pricingExpression": { "tieredRates": [
+                    { "unitPrice": { "units": "0", "nanos": 0 } },
+                    { "unitPrice": { "units": units, "nanos": nanos } }
+                ] } }]
+            })
+        };
+        let mut table = PriceTable::bundled();
+        let skus = vec![
+            sku("N2D AMD Instance Core running in EMEA", "OnDemand", &["europe-west1"], "0", 30000000),
+            sku("Spot Preemptible E2 Instance Ram running in EMEA", "Preemptible", &["europe-west1"], "0", 1000000),
+            sku("Compute optimized Core running in Americas", "Commit1Yr", &["us-central1"], "0", 1),
+            sku("SSD backed PD Capacity in Belgium", "OnDemand", &["europe-west1"], "0", 200000000),
+            sku("Balanced PD Capacity", "OnDemand", &["mars-north1"], "1", 0),
+            sku("N4 Instance Core running in EMEA", "OnDemand", &["europe-west1"], "0", 40000000),
+        ];
+        assert_eq!(apply_billing_skus(&mut table, &skus), 4);
+
+        let eu = &table.regions["europe-west1"];
+        assert!(close(eu.series["n2d"].vcpu_hour, 0.03));
+        assert_eq!(eu.series["e2"].spot_memory_gb_hour, Some(0.001));
+        assert!(close(eu.disks_gb_month["pd-ssd"], 0.2));
+        // Cores without a memory price are dropped
+        assert!(!eu.series.contains_key("n4"));
+        assert!(!table.regions.contains_key("mars-north1"));
+    }
+}
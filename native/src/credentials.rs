@@ -0,0 +1,527 @@
+//! Credential discovery and token exchange
+//!
+//! Follows the Application Default Credentials lookup order:
+//! 1. `GOOGLE_APPLICATION_CREDENTIALS`
+//! 2. `~/.config/gcloud/application_default_credentials.json` (or `$CLOUDSDK_CONFIG`)
+//! 3. `gcloud auth print-access-token` as a last resort
+//!
+//! Supported credential files: `authorized_user`, `service_account`,
+//! `impersonated_service_account` and `external_account` (workload identity
+//! federation with file or URL subject tokens).
+
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+
+use crate::accounts::GcloudContext;
+use crate::gcloud_client_poc::{CachedToken, http_client};
+
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const JWT_BEARER_GRANT: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// Lifetime assumed for tokens printed by gcloud (it does not report expiry)
+const GCLOUD_TOKEN_LIFETIME: Duration = Duration::from_secs(900);
+
+/// Parsed credentials file, keyed on its `type` field
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CredentialsFile {
+    AuthorizedUser {
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+    },
+    ServiceAccount {
+        client_email: String,
+        private_key: String,
+        #[serde(default)]
+        private_key_id: Option<String>,
+        #[serde(default)]
+        token_uri: Option<String>,
+    },
+    ImpersonatedServiceAccount {
+        service_account_impersonation_url: String,
+        source_credentials: Box<CredentialsFile>,
+        #[serde(default)]
+        delegates: Vec<String>,
+    },
+    ExternalAccount {
+        audience: String,
+        subject_token_type: String,
+        token_url: String,
+        credential_source: ExternalCredentialSource,
+        #[serde(default)]
+        service_account_impersonation_url: Option<String>,
+    },
+}
+
+/// Where an external account reads its subject token from
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExternalCredentialSource {
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub environment_id: Option<String>,
+    #[serde(default)]
+    pub format: Option<SubjectTokenFormat>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubjectTokenFormat {
+    /// "text" or "json"
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub subject_token_field_name: Option<String>,
+}
+
+/// A discovered credential and where it came from
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    File { path: PathBuf, file: Box<CredentialsFile> },
+    /// `gcloud auth print-access-token`, optionally for a pinned account/configuration
+    GcloudCli(GcloudContext),
+}
+
+impl Credentials {
+    /// Locate credentials following the ADC order
+    pub fn discover() -> Result<Self> {
+        if let Ok(path) = std::env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+            if !path.is_empty() {
+                // An explicit setting that points nowhere is a configuration error,
+                // not something to silently skip
+                return Self::from_path(Path::new(&path)).map_err(|e| {
+                    anyhow!("GOOGLE_APPLICATION_CREDENTIALS={} could not be used: {}", path, e)
+                });
+            }
+        }
+
+        if let Some(path) = well_known_adc_path() {
+            if path.exists() {
+                return Self::from_path(&path);
+            }
+        }
+
+        tracing::info!("No ADC file found, falling back to `gcloud auth print-access-token`");
+        Ok(Credentials::GcloudCli(GcloudContext::default()))
+    }
+
+    /// Credentials matching a gcloud context
+    ///
+    /// ADC belongs to a single identity, so a pinned account or configuration
+    /// always goes through gcloud itself.
+    pub fn for_context(ctx: &GcloudContext) -> Result<Self> {
+        if ctx.is_active_default() {
+            return Self::discover();
+        }
+        ctx.validate()?;
+        Ok(Credentials::GcloudCli(ctx.clone()))
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read credentials {}: {}", path.display(), e))?;
+        let file: CredentialsFile = serde_json::from_str(&json)
+            .map_err(|e| anyhow!("Unsupported credentials file {}: {}", path.display(), e))?;
+        Ok(Credentials::File { path: path.to_path_buf(), file: Box::new(file) })
+    }
+
+    /// Stable key for sharing a token cache between clients
+    pub fn cache_key(&self) -> String {
+        match self {
+            Credentials::File { path, .. } => path.display().to_string(),
+            Credentials::GcloudCli(ctx) => format!("gcloud-cli:{}", ctx.label()),
+        }
+    }
+
+    /// Short description for logs ("service_account foo@bar", ...)
+    pub fn describe(&self) -> String {
+        match self {
+            Credentials::File { path, file } => format!("{} ({})", file.describe(), path.display()),
+            Credentials::GcloudCli(ctx) => format!("gcloud CLI ({})", ctx.label()),
+        }
+    }
+
+    /// Obtain a fresh access token
+    pub async fn fetch_token(&self) -> Result<CachedToken> {
+        match self {
+            Credentials::File { file, .. } => file.fetch_token().await,
+            Credentials::GcloudCli(ctx) => gcloud_print_access_token(ctx).await,
+        }
+    }
+}
+
+impl CredentialsFile {
+    fn describe(&self) -> String {
+        match self {
+            CredentialsFile::AuthorizedUser { .. } => "authorized_user".to_string(),
+            CredentialsFile::ServiceAccount { client_email, .. } => format!("service_account {}", client_email),
+            CredentialsFile::ImpersonatedServiceAccount { service_account_impersonation_url, .. } => {
+                format!("impersonated_service_account {}", service_account_impersonation_url)
+            }
+            CredentialsFile::ExternalAccount { audience, .. } => format!("external_account {}", audience),
+        }
+    }
+
+    /// Obtain a fresh access token for this credential
+    pub async fn fetch_token(&self) -> Result<CachedToken> {
+        match self {
+            CredentialsFile::AuthorizedUser { client_id, client_secret, refresh_token } => {
+                let params = [
+                    ("client_id", client_id.as_str()),
+                    ("client_secret", client_secret.as_str()),
+                    ("refresh_token", refresh_token.as_str()),
+                    ("grant_type", "refresh_token"),
+                ];
+                oauth_form_exchange(DEFAULT_TOKEN_URI, &params).await.map_err(|e| {
+                    tracing::debug!(error = %e, "Refresh token exchange failed");
+                    anyhow!("Authentication failed. Please try logging in again with: gcloud auth application-default login")
+                })
+            }
+            CredentialsFile::ServiceAccount { client_email, private_key, private_key_id, token_uri } => {
+                let token_uri = token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI);
+                let assertion = service_account_assertion(
+                    client_email,
+                    private_key,
+                    private_key_id.as_deref(),
+                    token_uri,
+                    unix_now()?,
+                )?;
+                let params = [("grant_type", JWT_BEARER_GRANT), ("assertion", assertion.as_str())];
+                oauth_form_exchange(token_uri, &params)
+                    .await
+                    .map_err(|e| anyhow!("Service account {} token exchange failed: {}", client_email, e))
+            }
+            CredentialsFile::ImpersonatedServiceAccount {
+                service_account_impersonation_url,
+                source_credentials,
+                delegates,
+            } => {
+                let source = Box::pin(source_credentials.fetch_token()).await?;
+                impersonate(service_account_impersonation_url, &source.access_token, delegates).await
+            }
+            CredentialsFile::ExternalAccount {
+                audience,
+                subject_token_type,
+                token_url,
+                credential_source,
+                service_account_impersonation_url,
+            } => {
+                let subject_token = read_subject_token(credential_source).await?;
+                let params = [
+                    ("grant_type", TOKEN_EXCHANGE_GRANT),
+                    ("audience", audience.as_str()),
+                    ("scope", CLOUD_PLATFORM_SCOPE),
+                    ("requested_token_type", ACCESS_TOKEN_TYPE),
+                    ("subject_token", subject_token.as_str()),
+                    ("subject_token_type", subject_token_type.as_str()),
+                ];
+                let federated = oauth_form_exchange(token_url, &params)
+                    .await
+                    .map_err(|e| anyhow!("STS token exchange failed: {}", e))?;
+
+                match service_account_impersonation_url {
+                    Some(url) => impersonate(url, &federated.access_token, &[]).await,
+                    None => Ok(federated),
+                }
+            }
+        }
+    }
+}
+
+fn well_known_adc_path() -> Option<PathBuf> {
+    if let Ok(dir) = std::env::var("CLOUDSDK_CONFIG") {
+        return Some(PathBuf::from(dir).join("application_default_credentials.json"));
+    }
+    dirs::home_dir().map(|home| {
+        home.join(".config")
+            .join("gcloud")
+            .join("application_default_credentials.json")
+    })
+}
+
+fn unix_now() -> Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| anyhow!("System clock before epoch: {}", e))?
+        .as_secs())
+}
+
+/// POST a form to an OAuth2/STS token endpoint and read `access_token`/`expires_in`
+async fn oauth_form_exchange(url: &str, params: &[(&str, &str)]) -> Result<CachedToken> {
+    let requested_at = Instant::now();
+    let response = http_client()
+        .post(url)
+        .form(params)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Token request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        // SECURITY: Token endpoints may echo request details; keep them out of user-facing errors
+        tracing::debug!(%status, error = %error_text, "Token endpoint rejected request");
+        return Err(anyhow!("token endpoint returned {}", status));
+    }
+
+    let body: serde_json::Value = response.json().await?;
+    let access_token = body["access_token"]
+        .as_str()
+        .ok_or_else(|| anyhow!("No access_token in response"))?
+        .to_string();
+    let expires_in = body["expires_in"].as_u64().unwrap_or(3600);
+
+    Ok(CachedToken {
+        access_token,
+        expires_at: requested_at + Duration::from_secs(expires_in),
+    })
+}
+
+/// Build the signed RS256 JWT used in the jwt-bearer grant
+fn service_account_assertion(
+    client_email: &str,
+    private_key_pem: &str,
+    private_key_id: Option<&str>,
+    audience: &str,
+    issued_at: u64,
+) -> Result<String> {
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::sign::Signer;
+
+    let mut header = serde_json::json!({ "alg": "RS256", "typ": "JWT" });
+    if let Some(kid) = private_key_id {
+        header["kid"] = serde_json::Value::String(kid.to_string());
+    }
+    let claims = serde_json::json!({
+        "iss": client_email,
+        "scope": CLOUD_PLATFORM_SCOPE,
+        "aud": audience,
+        "iat": issued_at,
+        "exp": issued_at + 3600,
+    });
+
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+    );
+
+    let key = PKey::private_key_from_pem(private_key_pem.as_bytes())
+        .map_err(|e| anyhow!("Invalid service account private key: {}", e))?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)
+        .map_err(|e| anyhow!("Failed to initialise signer: {}", e))?;
+    signer.update(signing_input.as_bytes())?;
+    let signature = signer.sign_to_vec()?;
+
+    Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+/// Exchange `source_token` for a token of the target service account
+/// (IAM Credentials generateAccessToken)
+async fn impersonate(url: &str, source_token: &str, delegates: &[String]) -> Result<CachedToken> {
+    let body = serde_json::json!({
+        "scope": [CLOUD_PLATFORM_SCOPE],
+        "delegates": delegates,
+        "lifetime": "3600s",
+    });
+
+    let response = http_client()
+        .post(url)
+        .bearer_auth(source_token)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Impersonation request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        // SECURITY: Same as token endpoints; the body stays out of user-facing errors
+        tracing::debug!(%status, error = %error_text, "IAM Credentials rejected impersonation");
+        return Err(anyhow!("Service account impersonation failed ({})", status));
+    }
+
+    let body: serde_json::Value = response.json().await?;
+    let access_token = body["accessToken"]
+        .as_str()
+        .ok_or_else(|| anyhow!("No accessToken in impersonation response"))?
+        .to_string();
+    let expires_at = body["expireTime"]
+        .as_str()
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .and_then(|t| (t.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok())
+        .map(|remaining| Instant::now() + remaining)
+        .unwrap_or_else(|| Instant::now() + Duration::from_secs(3600));
+
+    Ok(CachedToken { access_token, expires_at })
+}
+
+/// Read the third-party token an external account exchanges at STS
+async fn read_subject_token(source: &ExternalCredentialSource) -> Result<String> {
+    if source.environment_id.as_deref().is_some_and(|id| id.starts_with("aws")) {
+        return Err(anyhow!("AWS workload identity federation is not supported yet"));
+    }
+
+    let raw = if let Some(file) = &source.file {
+        tokio::fs::read_to_string(file)
+            .await
+            .map_err(|e| anyhow!("Failed to read subject token file {}: {}", file, e))?
+    } else if let Some(url) = &source.url {
+        let mut request = http_client().get(url);
+        for (name, value) in &source.headers {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to fetch subject token: {}", e))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Subject token URL returned {}", response.status()));
+        }
+        response.text().await?
+    } else {
+        return Err(anyhow!("external_account credential_source needs `file` or `url`"));
+    };
+
+    parse_subject_token(&raw, source.format.as_ref())
+}
+
+fn parse_subject_token(raw: &str, format: Option<&SubjectTokenFormat>) -> Result<String> {
+    match format {
+        Some(format) if format.kind == "json" => {
+            let field = format
+                .subject_token_field_name
+                .as_deref()
+                .ok_or_else(|| anyhow!("JSON subject token format needs subject_token_field_name"))?;
+            let json: serde_json::Value = serde_json::from_str(raw)
+                .map_err(|e| anyhow!("Subject token is not valid JSON: {}", e))?;
+            json[field]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("Subject token JSON has no '{}' field", field))
+        }
+        _ => Ok(raw.trim().to_string()),
+    }
+}
+
+/// Ask gcloud for a token (active account unless the context pins one)
+async fn gcloud_print_access_token(ctx: &GcloudContext) -> Result<CachedToken> {
+    // SECURITY: Account/configuration end up on the command line
+    ctx.validate()?;
+
+    let output = Command::new("gcloud")
+        .args(["auth", "print-access-token"])
+        .args(ctx.args())
+        .output()
+        .await
+        .map_err(|e| anyhow!("No credentials found and gcloud is not available: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        tracing::debug!(stderr = %stderr, "gcloud auth print-access-token failed");
+        return Err(anyhow!(
+            "No usable credentials. Set GOOGLE_APPLICATION_CREDENTIALS or run: gcloud auth application-default login"
+        ));
+    }
+
+    let access_token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if access_token.is_empty() {
+        return Err(anyhow!("gcloud returned an empty access token"));
+    }
+
+    Ok(CachedToken {
+        access_token,
+        expires_at: Instant::now() + GCLOUD_TOKEN_LIFETIME,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_credential_types() {
+        let impersonated = r#"{
+            "type": "impersonated_service_account",
+            "service_account_impersonation_url": "https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/ci@p.iam.gserviceaccount.com:generateAccessToken",
+            "delegates": [],
+            "source_credentials": {
+                "type": "authorized_user",
+                "client_id": "id",
+                "client_secret": "secret",
+                "refresh_token": "refresh"
+            }
+        }"#;
+        let parsed: CredentialsFile = serde_json::from_str(impersonated).unwrap();
+        match parsed {
+            CredentialsFile::ImpersonatedServiceAccount { source_credentials, .. } => {
+                assert!(matches!(*source_credentials, CredentialsFile::AuthorizedUser { .. }));
+            }
+            other => panic!("unexpected credentials: {:?}", other),
+        }
+
+        let external = r#"{
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/1/locations/global/workloadIdentityPools/ci/providers/gh",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": "https://sts.googleapis.com/v1/token",
+            "credential_source": { "file": "/var/run/token", "format": { "type": "text" } }
+        }"#;
+        let parsed: CredentialsFile = serde_json::from_str(external).unwrap();
+        assert!(matches!(parsed, CredentialsFile::ExternalAccount { .. }));
+
+        assert!(serde_json::from_str::<CredentialsFile>(r#"{"type": "unknown_kind"}"#).is_err());
+    }
+
+    #[test]
+    fn test_service_account_assertion_is_signed() {
+        use openssl::hash::MessageDigest;
+        use openssl::pkey::PKey;
+        use openssl::rsa::Rsa;
+        use openssl::sign::Verifier;
+
+        let rsa = Rsa::generate(2048).unwrap();
+        let key = PKey::from_rsa(rsa).unwrap();
+        let pem = String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+
+        let jwt = service_account_assertion("ci@p.iam.gserviceaccount.com", &pem, Some("kid-1"), DEFAULT_TOKEN_URI, 1_700_000_000).unwrap();
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+
+        let claims: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(claims["iss"], "ci@p.iam.gserviceaccount.com");
+        assert_eq!(claims["exp"], 1_700_003_600u64);
+
+        let signature = URL_SAFE_NO_PAD.decode(parts[2]).unwrap();
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+        verifier.update(format!("{}.{}", parts[0], parts[1]).as_bytes()).unwrap();
+        assert!(verifier.verify(&signature).unwrap());
+    }
+
+    #[test]
+    fn test_parse_subject_token_formats() {
+        assert_eq!(parse_subject_token("abc\n", None).unwrap(), "abc");
+
+        let format = SubjectTokenFormat {
+            kind: "json".to_string(),
+            subject_token_field_name: Some("id_token".to_string()),
+        };
+        assert_eq!(parse_subject_token(r#"{"id_token": "xyz"}"#, Some(&format)).unwrap(), "xyz");
+        assert!(parse_subject_token(r#"{"other": "xyz"}"#, Some(&format)).is_err());
+    }
+}
@@ -2,18 +2,30 @@ use std::process::Command;
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
 use tracing;
-use crate::validation::{validate_project_id, validate_zone, validate_instance_name, sanitize_zone_from_url};
+use crate::accounts::GcloudContext;
+use crate::instance_details::InstanceDetails;
+use crate::inventory::InstanceFilter;
+use crate::pagination::ListOptions;
+use crate::machine_types::{offline_spec, fetch_machine_types_cli, refine_instances, MachineSpec, MachineTypeCache, RawMachineType, DEFAULT_CACHE_TTL};
+use crate::validation::{validate_project_id, validate_zone, validate_instance_name, validate_machine_type, validate_region, sanitize_zone_from_url};
 use tokio::process::Command as TokioCommand;
 use tokio::time::{timeout, Duration};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Project model shared by the gcloud CLI and REST backends
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")] // gcloud JSON often uses camelCase
 pub struct GcpProject {
     pub project_id: String,
     pub name: Option<String>,
+    #[serde(default)]
+    pub project_number: Option<String>,
+    /// Lifecycle state (ACTIVE, DELETE_REQUESTED, ...)
+    #[serde(default, alias = "lifecycleState")]
+    pub state: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Instance model shared by the gcloud CLI and REST backends
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct GcpInstance {
     pub name: String,
     pub status: String,
@@ -31,8 +43,10 @@ struct RawDisk {
     boot: Option<bool>,
 }
 
+/// Instance resource as returned by both `gcloud --format=json` and the
+/// Compute REST API (they share the same JSON representation)
 #[derive(Deserialize)]
-struct RawInstance {
+pub(crate) struct RawInstance {
     name: String,
     status: String,
     zone: String,
@@ -41,6 +55,49 @@ struct RawInstance {
     disks: Option<Vec<RawDisk>>,
 }
 
+impl From<RawInstance> for GcpInstance {
+    fn from(raw: RawInstance) -> Self {
+        // Use sanitize_zone_from_url instead of fragile split
+        let zone_name = sanitize_zone_from_url(&raw.zone)
+            .unwrap_or_else(|_| {
+                // Fallback to old method if validation fails
+                raw.zone.rsplit('/').next().unwrap_or(&raw.zone).to_string()
+            });
+
+        let machine_type = raw.machine_type
+            .as_deref()
+            .map(|url| url.rsplit('/').next().unwrap_or(url))
+            .unwrap_or("Unknown")
+            .to_string();
+
+        // Offline estimate; refined from the machineTypes API after listing
+        let spec = offline_spec(&machine_type);
+        let cpu_count = spec.as_ref().map(|s| s.guest_cpus);
+        let memory_mb = spec.as_ref().map(|s| s.memory_mb);
+
+        // Extract disk size from boot disk (first disk marked as boot=true)
+        let disk_gb = raw.disks
+            .as_ref()
+            .and_then(|disks| {
+                disks.iter()
+                    .find(|d| d.boot.unwrap_or(false))
+                    .or_else(|| disks.first())
+            })
+            .and_then(|disk| disk.disk_size_gb.as_ref())
+            .and_then(|size_str| size_str.parse::<u32>().ok());
+
+        GcpInstance {
+            name: raw.name,
+            status: raw.status,
+            zone: zone_name,
+            machine_type,
+            cpu_count,
+            memory_mb,
+            disk_gb,
+        }
+    }
+}
+
 pub fn is_gcloud_installed() -> bool {
     Command::new("gcloud")
         .arg("--version")
@@ -65,16 +122,28 @@ pub fn is_gcloud_authenticated() -> bool {
 }
 
 /// Async version with timeout
-pub async fn get_projects_async() -> Result<Vec<GcpProject>> {
-    // 10 second timeout for listing projects
+pub async fn get_projects_async(ctx: &GcloudContext) -> Result<Vec<GcpProject>> {
+    get_projects_paged_async(ctx, &ListOptions::default()).await
+}
+
+/// List projects with an explicit page size and overall timeout
+///
+/// gcloud follows the page tokens itself and prints one JSON array at the
+/// end, so this path yields a single batch.
+pub async fn get_projects_paged_async(ctx: &GcloudContext, options: &ListOptions) -> Result<Vec<GcpProject>> {
+    // SECURITY: Account/configuration end up on the command line
+    ctx.validate()?;
+
     let output = timeout(
-        Duration::from_secs(10),
+        options.timeout,
         TokioCommand::new("gcloud")
             .args(["projects", "list", "--format=json"])
+            .arg(format!("--page-size={}", options.page_size))
+            .args(ctx.args())
             .output()
     )
     .await
-    .map_err(|_| anyhow!("Timeout: gcloud projects list took longer than 10 seconds"))?
+    .map_err(|_| anyhow!("Timeout: gcloud projects list took longer than {:?}", options.timeout))?
     .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
 
     if !output.status.success() {
@@ -91,149 +160,126 @@ pub fn get_projects() -> Result<Vec<GcpProject>> {
     // Create a tokio runtime for this call
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
-    rt.block_on(get_projects_async())
-}
-
-/// Extract CPU and memory specs from machine type name
-/// Supports standard machine types by parsing their pattern (e.g., "e2-standard-4" → 4 CPUs)
-/// Returns (cpu_count, memory_mb) if the machine type can be determined
-fn get_machine_specs(machine_type: &str) -> Option<(u32, u32)> {
-    // Special cases (micro, small, medium)
-    match machine_type {
-        "e2-micro" => return Some((2, 1024)),
-        "e2-small" => return Some((2, 2048)),
-        "e2-medium" => return Some((2, 4096)),
-        "f1-micro" => return Some((1, 614)),
-        "g1-small" => return Some((1, 1740)),
-        _ => {}
+    rt.block_on(get_projects_async(&GcloudContext::default()))
+}
+
+/// Async version with timeout
+pub async fn get_instances_async(ctx: &GcloudContext, project_id: &str) -> Result<Vec<GcpInstance>> {
+    get_instances_paged_async(ctx, project_id, &ListOptions::default()).await
+}
+
+/// List instances with an explicit page size and overall timeout
+pub async fn get_instances_paged_async(ctx: &GcloudContext, project_id: &str, options: &ListOptions) -> Result<Vec<GcpInstance>> {
+    get_instances_filtered_async(ctx, project_id, &InstanceFilter::default(), options).await
+}
+
+/// List the instances matching `filter` (evaluated by gcloud)
+pub async fn get_instances_filtered_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    filter: &InstanceFilter,
+    options: &ListOptions,
+) -> Result<Vec<GcpInstance>> {
+    // Validate project ID before passing to shell command
+    validate_project_id(project_id)?;
+    ctx.validate()?;
+    filter.validate()?;
+
+    let mut command = TokioCommand::new("gcloud");
+    command
+        .args(["compute", "instances", "list", "--project", project_id, "--format=json"])
+        .arg(format!("--page-size={}", options.page_size))
+        .args(ctx.args());
+    if let Some(expression) = filter.gcloud_expression() {
+        command.arg(format!("--filter={}", expression));
+    }
+    if let Some(zone) = &filter.zone {
+        command.arg(format!("--zones={}", zone));
+    }
+
+    let output = timeout(options.timeout, command.output())
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud instances list took longer than {:?}", options.timeout))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("gcloud error: {}", String::from_utf8_lossy(&output.stderr)));
     }
 
-    // Parse standard machine types: {series}-{type}-{cpus}
-    // Examples: e2-standard-4, n1-standard-8, n2-highmem-16, c2-standard-30
-    let parts: Vec<&str> = machine_type.split('-').collect();
-
-    if parts.len() >= 3 {
-        let series = parts[0];      // e2, n1, n2, n2d, c2, c3, t2d, etc.
-        let type_name = parts[1];   // standard, highmem, highcpu, custom
-
-        // Try to parse CPU count from the last part
-        if let Ok(cpu_count) = parts[2].parse::<u32>() {
-            // Calculate memory based on type and series
-            let memory_mb = match (series, type_name) {
-                // E2 series (cost-optimized): 4GB per vCPU for standard
-                ("e2", "standard") => cpu_count * 4096,
-                ("e2", "highmem") => cpu_count * 8192,
-                ("e2", "highcpu") => cpu_count * 1024,
-
-                // N1 series: 3.75GB per vCPU for standard
-                ("n1", "standard") => cpu_count * 3840,
-                ("n1", "highmem") => cpu_count * 6656,
-                ("n1", "highcpu") => cpu_count * 922,
-
-                // N2/N2D series: 4GB per vCPU for standard
-                ("n2" | "n2d", "standard") => cpu_count * 4096,
-                ("n2" | "n2d", "highmem") => cpu_count * 8192,
-                ("n2" | "n2d", "highcpu") => cpu_count * 1024,
-
-                // C2 series (compute-optimized): 4GB per vCPU
-                ("c2", "standard") => cpu_count * 4096,
-
-                // C3 series (newer compute): 4GB per vCPU
-                ("c3", "standard") => cpu_count * 4096,
-                ("c3", "highmem") => cpu_count * 8192,
-                ("c3", "highcpu") => cpu_count * 2048,
-
-                // T2D series (AMD): 4GB per vCPU
-                ("t2d", "standard") => cpu_count * 4096,
-
-                // M1/M2/M3 series (memory-optimized): Much higher memory
-                ("m1", "megamem") => cpu_count * 14336,  // 14GB per vCPU
-                ("m1", "ultramem") => cpu_count * 24576, // 24GB per vCPU
-                ("m2", "megamem") => cpu_count * 14336,
-                ("m2", "ultramem") => cpu_count * 24576,
-                ("m3", "megamem") => cpu_count * 14336,
-                ("m3", "ultramem") => cpu_count * 24576,
-
-                // A2 series (GPU): 12GB per vCPU
-                ("a2", _) => cpu_count * 12288,
-
-                // Default fallback: assume 4GB per vCPU (most common)
-                _ => cpu_count * 4096,
-            };
-
-            return Some((cpu_count, memory_mb));
+    let raw_instances: Vec<RawInstance> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse instances JSON: {}", e))?;
+
+    let mut instances: Vec<GcpInstance> = raw_instances.into_iter().map(GcpInstance::from).collect();
+
+    // Exact CPU/memory from the machineTypes API (cached on disk)
+    match MachineTypeCache::for_project(project_id, DEFAULT_CACHE_TTL) {
+        Ok(mut cache) => {
+            refine_instances(&mut instances, &mut cache, |names| async move {
+                fetch_machine_types_cli(ctx, project_id, &names).await
+            })
+            .await?;
         }
+        Err(e) => tracing::warn!(error = %e, "Machine type cache unavailable"),
     }
 
-    // If we can't parse it, return None
-    None
+    Ok(instances)
 }
 
-/// Async version with timeout
-async fn get_instances_async(project_id: &str) -> Result<Vec<GcpInstance>> {
-    // Validate project ID before passing to shell command
+/// Raw `gcloud compute instances describe --format=json` output
+async fn describe_instance_json(ctx: &GcloudContext, project_id: &str, zone: &str, instance_name: &str) -> Result<Vec<u8>> {
+    // SECURITY: Validate all inputs
     validate_project_id(project_id)?;
+    validate_zone(zone)?;
+    validate_instance_name(instance_name)?;
+    ctx.validate()?;
 
-    // 10 second timeout for listing instances
     let output = timeout(
         Duration::from_secs(10),
         TokioCommand::new("gcloud")
-            .args(["compute", "instances", "list", "--project", project_id, "--format=json"])
+            .args([
+                "compute", "instances", "describe", instance_name,
+                "--zone", zone,
+                "--project", project_id,
+                "--format=json",
+            ])
+            .args(ctx.args())
             .output()
     )
     .await
-    .map_err(|_| anyhow!("Timeout: gcloud instances list took longer than 10 seconds"))?
+    .map_err(|_| anyhow!("Timeout: gcloud instances describe took longer than 10 seconds"))?
     .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
 
     if !output.status.success() {
         return Err(anyhow!("gcloud error: {}", String::from_utf8_lossy(&output.stderr)));
     }
 
-    let raw_instances: Vec<RawInstance> = serde_json::from_slice(&output.stdout)
-        .map_err(|e| anyhow!("Failed to parse instances JSON: {}", e))?;
-
-    let instances = raw_instances.into_iter().map(|raw| {
-        // Use sanitize_zone_from_url instead of fragile split
-        let zone_name = sanitize_zone_from_url(&raw.zone)
-            .unwrap_or_else(|_| {
-                // Fallback to old method if validation fails
-                raw.zone.split('/').last().unwrap_or(&raw.zone).to_string()
-            });
-
-        let machine_type = raw.machine_type
-            .as_deref()
-            .map(|url| url.split('/').last().unwrap_or(url))
-            .unwrap_or("Unknown")
-            .to_string();
-
-        // Extract CPU and memory from machine type
-        let (cpu_count, memory_mb) = get_machine_specs(&machine_type)
-            .map(|(cpu, mem)| (Some(cpu), Some(mem)))
-            .unwrap_or((None, None));
+    Ok(output.stdout)
+}
 
-        // Extract disk size from boot disk (first disk marked as boot=true)
-        let disk_gb = raw.disks
-            .as_ref()
-            .and_then(|disks| {
-                disks.iter()
-                    .find(|d| d.boot.unwrap_or(false))
-                    .or_else(|| disks.first())
-            })
-            .and_then(|disk| disk.disk_size_gb.as_ref())
-            .and_then(|size_str| size_str.parse::<u32>().ok());
+/// Describe a single instance
+pub async fn describe_instance_async(ctx: &GcloudContext, project_id: &str, zone: &str, instance_name: &str) -> Result<GcpInstance> {
+    let json = describe_instance_json(ctx, project_id, zone, instance_name).await?;
+    let raw: RawInstance = serde_json::from_slice(&json)
+        .map_err(|e| anyhow!("Failed to parse instance JSON: {}", e))?;
+    Ok(raw.into())
+}
 
-        GcpInstance {
-            name: raw.name,
-            status: raw.status,
-            zone: zone_name,
-            machine_type,
-            cpu_count,
-            memory_mb,
-            disk_gb,
-        }
-    }).collect();
+/// Full description of a single instance (network, identity, scheduling, disks...)
+pub async fn describe_instance_details_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    zone: &str,
+    instance_name: &str,
+) -> Result<InstanceDetails> {
+    let json = describe_instance_json(ctx, project_id, zone, instance_name).await?;
+    InstanceDetails::from_json(&json)
+}
 
-    Ok(instances)
+/// Synchronous wrapper for FFI bridge
+pub fn describe_instance_details(project_id: &str, zone: &str, instance_name: &str) -> Result<InstanceDetails> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
+    rt.block_on(describe_instance_details_async(&GcloudContext::default(), project_id, zone, instance_name))
 }
 
 /// Synchronous wrapper for FFI bridge
@@ -241,7 +287,7 @@ pub fn get_instances(project_id: &str) -> Result<Vec<GcpInstance>> {
     // Create a tokio runtime for this call
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
-    rt.block_on(get_instances_async(project_id))
+    rt.block_on(get_instances_async(&GcloudContext::default(), project_id))
 }
 
 pub fn execute_login() -> Result<()> {
@@ -315,6 +361,7 @@ pub fn launch_sftp_browser(port: u16, username: Option<String>) -> anyhow::Resul
     Ok(())
 }
 
+/// Revoke every stored account; use `accounts::revoke_account` for just one
 pub fn execute_logout() -> Result<()> {
     tracing::info!("Revoking all gcloud credentials");
 
@@ -329,11 +376,12 @@ pub fn execute_logout() -> Result<()> {
 // VM Lifecycle Management Functions
 
 /// Start a stopped instance
-async fn start_instance_async(project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
+pub async fn start_instance_async(ctx: &GcloudContext, project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
     // SECURITY: Validate all inputs
     validate_project_id(project_id)?;
     validate_zone(zone)?;
     validate_instance_name(instance_name)?;
+    ctx.validate()?;
 
     tracing::info!(
         project_id = project_id,
@@ -356,6 +404,7 @@ async fn start_instance_async(project_id: &str, zone: &str, instance_name: &str)
                 project_id,
                 "--quiet",
             ])
+            .args(ctx.args())
             .output()
     )
     .await
@@ -383,15 +432,16 @@ async fn start_instance_async(project_id: &str, zone: &str, instance_name: &str)
 pub fn start_instance(project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
-    rt.block_on(start_instance_async(project_id, zone, instance_name))
+    rt.block_on(start_instance_async(&GcloudContext::default(), project_id, zone, instance_name))
 }
 
 /// Stop a running instance
-async fn stop_instance_async(project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
+pub async fn stop_instance_async(ctx: &GcloudContext, project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
     // SECURITY: Validate all inputs
     validate_project_id(project_id)?;
     validate_zone(zone)?;
     validate_instance_name(instance_name)?;
+    ctx.validate()?;
 
     tracing::info!(
         project_id = project_id,
@@ -414,6 +464,7 @@ async fn stop_instance_async(project_id: &str, zone: &str, instance_name: &str)
                 project_id,
                 "--quiet",
             ])
+            .args(ctx.args())
             .output()
     )
     .await
@@ -441,15 +492,16 @@ async fn stop_instance_async(project_id: &str, zone: &str, instance_name: &str)
 pub fn stop_instance(project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
-    rt.block_on(stop_instance_async(project_id, zone, instance_name))
+    rt.block_on(stop_instance_async(&GcloudContext::default(), project_id, zone, instance_name))
 }
 
 /// Reset (restart) a running instance
-async fn reset_instance_async(project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
+pub async fn reset_instance_async(ctx: &GcloudContext, project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
     // SECURITY: Validate all inputs
     validate_project_id(project_id)?;
     validate_zone(zone)?;
     validate_instance_name(instance_name)?;
+    ctx.validate()?;
 
     tracing::info!(
         project_id = project_id,
@@ -472,6 +524,7 @@ async fn reset_instance_async(project_id: &str, zone: &str, instance_name: &str)
                 project_id,
                 "--quiet",
             ])
+            .args(ctx.args())
             .output()
     )
     .await
@@ -499,6 +552,338 @@ async fn reset_instance_async(project_id: &str, zone: &str, instance_name: &str)
 pub fn reset_instance(project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
     let rt = tokio::runtime::Runtime::new()
         .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
-    rt.block_on(reset_instance_async(project_id, zone, instance_name))
+    rt.block_on(reset_instance_async(&GcloudContext::default(), project_id, zone, instance_name))
 }
 
+
+/// Run `gcloud compute instances VERB INSTANCE ...` and wait for it
+async fn run_instance_command(
+    ctx: &GcloudContext,
+    verb: &str,
+    extra_args: &[String],
+    project_id: &str,
+    zone: &str,
+    instance_name: &str,
+    timeout_secs: u64,
+) -> Result<()> {
+    // SECURITY: Validate all inputs
+    validate_project_id(project_id)?;
+    validate_zone(zone)?;
+    validate_instance_name(instance_name)?;
+    ctx.validate()?;
+
+    tracing::info!(
+        project_id = project_id,
+        zone = zone,
+        instance_name = instance_name,
+        verb = verb,
+        "Running instance command"
+    );
+
+    let output = timeout(
+        Duration::from_secs(timeout_secs),
+        TokioCommand::new("gcloud")
+            .args(["compute", "instances", verb, instance_name, "--zone", zone, "--project", project_id, "--quiet"])
+            .args(extra_args)
+            .args(ctx.args())
+            .output()
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud compute instances {} took longer than {} seconds", verb, timeout_secs))?
+    .map_err(|e| anyhow!("Failed to execute gcloud {} command: {}", verb, e))?;
+
+    if output.status.success() {
+        tracing::info!(instance_name = instance_name, verb = verb, "Instance command succeeded");
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        tracing::error!(instance_name = instance_name, verb = verb, stderr = %stderr, "Instance command failed");
+        Err(anyhow!("Failed to {} instance: {}", verb, stderr.trim()))
+    }
+}
+
+/// Suspend a running instance (memory is preserved on disk)
+pub async fn suspend_instance_async(ctx: &GcloudContext, project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
+    run_instance_command(ctx, "suspend", &[], project_id, zone, instance_name, 300).await
+}
+
+/// Resume a suspended instance
+pub async fn resume_instance_async(ctx: &GcloudContext, project_id: &str, zone: &str, instance_name: &str) -> Result<()> {
+    run_instance_command(ctx, "resume", &[], project_id, zone, instance_name, 300).await
+}
+
+/// Change the machine type of a stopped instance
+pub async fn set_machine_type_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    zone: &str,
+    instance_name: &str,
+    machine_type: &str,
+) -> Result<()> {
+    validate_machine_type(machine_type)?;
+    let args = [format!("--machine-type={}", machine_type)];
+    run_instance_command(ctx, "set-machine-type", &args, project_id, zone, instance_name, 120).await
+}
+
+/// Turn deletion protection on or off
+pub async fn set_deletion_protection_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    zone: &str,
+    instance_name: &str,
+    enabled: bool,
+) -> Result<()> {
+    let flag = if enabled { "--deletion-protection" } else { "--no-deletion-protection" };
+    run_instance_command(ctx, "update", &[flag.to_string()], project_id, zone, instance_name, 60).await
+}
+
+/// Spec of `machine_type` in `zone`, or `None` if the zone doesn't offer it
+pub async fn machine_type_in_zone_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    zone: &str,
+    machine_type: &str,
+) -> Result<Option<MachineSpec>> {
+    validate_project_id(project_id)?;
+    validate_zone(zone)?;
+    validate_machine_type(machine_type)?;
+    ctx.validate()?;
+
+    let output = timeout(
+        Duration::from_secs(30),
+        TokioCommand::new("gcloud")
+            .args(["compute", "machine-types", "describe", machine_type, "--zone", zone, "--project", project_id, "--format=json"])
+            .args(ctx.args())
+            .output()
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud machine-types describe took longer than 30 seconds"))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("was not found") || stderr.contains("notFound") {
+            return Ok(None);
+        }
+        return Err(anyhow!("gcloud error: {}", stderr.trim()));
+    }
+
+    let raw: RawMachineType = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse machine type JSON: {}", e))?;
+    Ok(Some(raw.into_zone_spec().1))
+}
+
+/// Start/stop settings of an instance schedule resource policy
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct InstanceSchedulePolicy {
+    #[serde(rename = "vmStartSchedule", default, deserialize_with = "schedule_cron")]
+    pub vm_start_schedule: Option<String>,
+    #[serde(rename = "vmStopSchedule", default, deserialize_with = "schedule_cron")]
+    pub vm_stop_schedule: Option<String>,
+    #[serde(rename = "timeZone", default)]
+    pub time_zone: String,
+}
+
+/// `{"schedule": "0 8 * * 1-5"}` -> `"0 8 * * 1-5"`
+fn schedule_cron<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    struct RawSchedule {
+        schedule: Option<String>,
+    }
+    Ok(Option::<RawSchedule>::deserialize(deserializer)?.and_then(|s| s.schedule))
+}
+
+#[derive(Deserialize)]
+struct RawResourcePolicy {
+    #[serde(rename = "instanceSchedulePolicy")]
+    instance_schedule_policy: Option<InstanceSchedulePolicy>,
+}
+
+/// Settings of the instance schedule policy `name`, or `None` if it doesn't exist
+pub async fn describe_instance_schedule_policy_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    region: &str,
+    name: &str,
+) -> Result<Option<InstanceSchedulePolicy>> {
+    // SECURITY: Validate all inputs
+    validate_project_id(project_id)?;
+    validate_region(region)?;
+    validate_instance_name(name)?;
+    ctx.validate()?;
+
+    let output = timeout(
+        Duration::from_secs(30),
+        TokioCommand::new("gcloud")
+            .args(["compute", "resource-policies", "describe", name])
+            .arg(format!("--region={}", region))
+            .arg(format!("--project={}", project_id))
+            .arg("--format=json")
+            .args(ctx.args())
+            .output()
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud resource-policies describe took longer than 30 seconds"))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("was not found") || stderr.contains("notFound") {
+            return Ok(None);
+        }
+        return Err(anyhow!("gcloud error: {}", stderr.trim()));
+    }
+
+    let raw: RawResourcePolicy = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse resource policy JSON: {}", e))?;
+    raw.instance_schedule_policy
+        .map(Some)
+        .ok_or_else(|| anyhow!("Resource policy {} is not an instance schedule", name))
+}
+
+/// Delete a resource policy; it must not be attached to any instance
+pub async fn delete_resource_policy_async(ctx: &GcloudContext, project_id: &str, region: &str, name: &str) -> Result<()> {
+    // SECURITY: Validate all inputs
+    validate_project_id(project_id)?;
+    validate_region(region)?;
+    validate_instance_name(name)?;
+    ctx.validate()?;
+
+    let output = timeout(
+        Duration::from_secs(120),
+        TokioCommand::new("gcloud")
+            .args(["compute", "resource-policies", "delete", name, "--quiet"])
+            .arg(format!("--region={}", region))
+            .arg(format!("--project={}", project_id))
+            .args(ctx.args())
+            .output()
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud resource-policies delete took longer than 120 seconds"))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("Failed to delete resource policy {}: {}", name, stderr.trim()));
+    }
+    tracing::info!(project_id = project_id, region = region, policy = name, "Resource policy deleted");
+    Ok(())
+}
+
+/// Instances of `project_id` that have the policy `region/name` attached
+pub async fn instances_with_resource_policy_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    region: &str,
+    name: &str,
+) -> Result<Vec<GcpInstance>> {
+    // SECURITY: Validate all inputs
+    validate_project_id(project_id)?;
+    validate_region(region)?;
+    validate_instance_name(name)?;
+    ctx.validate()?;
+
+    let output = timeout(
+        Duration::from_secs(60),
+        TokioCommand::new("gcloud")
+            .args(["compute", "instances", "list"])
+            .arg(format!("--project={}", project_id))
+            .arg(format!("--filter=resourcePolicies~/regions/{}/resourcePolicies/{}$", region, name))
+            .arg("--format=json")
+            .args(ctx.args())
+            .output()
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud instances list took longer than 60 seconds"))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("gcloud error: {}", stderr.trim()));
+    }
+    let raw: Vec<RawInstance> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse instances JSON: {}", e))?;
+    Ok(raw.into_iter().map(GcpInstance::from).collect())
+}
+
+/// Create an instance schedule resource policy in `region`
+///
+/// Returns false if a policy called `name` already exists there; it is left
+/// as is. The cron expressions and time zone must be validated by the caller.
+pub async fn create_instance_schedule_policy_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    region: &str,
+    name: &str,
+    vm_start_schedule: Option<&str>,
+    vm_stop_schedule: Option<&str>,
+    time_zone: &str,
+) -> Result<bool> {
+    // SECURITY: Validate all inputs
+    validate_project_id(project_id)?;
+    validate_region(region)?;
+    validate_instance_name(name)?;
+    ctx.validate()?;
+
+    let mut args = vec![
+        "compute".to_string(),
+        "resource-policies".to_string(),
+        "create".to_string(),
+        "instance-schedule".to_string(),
+        name.to_string(),
+        format!("--region={}", region),
+        format!("--project={}", project_id),
+        format!("--timezone={}", time_zone),
+    ];
+    if let Some(schedule) = vm_start_schedule {
+        args.push(format!("--vm-start-schedule={}", schedule));
+    }
+    if let Some(schedule) = vm_stop_schedule {
+        args.push(format!("--vm-stop-schedule={}", schedule));
+    }
+
+    let output = timeout(
+        Duration::from_secs(120),
+        TokioCommand::new("gcloud").args(&args).args(ctx.args()).output()
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud resource-policies create took longer than 120 seconds"))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if output.status.success() {
+        tracing::info!(project_id = project_id, region = region, policy = name, "Instance schedule policy created");
+        return Ok(true);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("already exists") {
+        tracing::warn!(project_id = project_id, region = region, policy = name, "Instance schedule policy already exists, reusing it");
+        return Ok(false);
+    }
+    Err(anyhow!("Failed to create resource policy {}: {}", name, stderr.trim()))
+}
+
+/// Attach a resource policy (from the instance's region) to an instance
+pub async fn add_resource_policy_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    zone: &str,
+    instance_name: &str,
+    policy: &str,
+) -> Result<()> {
+    validate_instance_name(policy)?;
+    let args = [format!("--resource-policies={}", policy)];
+    run_instance_command(ctx, "add-resource-policies", &args, project_id, zone, instance_name, 120).await
+}
+
+/// Detach a resource policy from an instance
+pub async fn remove_resource_policy_async(
+    ctx: &GcloudContext,
+    project_id: &str,
+    zone: &str,
+    instance_name: &str,
+    policy: &str,
+) -> Result<()> {
+    validate_instance_name(policy)?;
+    let args = [format!("--resource-policies={}", policy)];
+    run_instance_command(ctx, "remove-resource-policies", &args, project_id, zone, instance_name, 120).await
+}
@@ -9,109 +9,177 @@
 /// 3. Comparar performance vs gcloud CLI
 
 use anyhow::{Result, anyhow};
-use serde::{Deserialize, Serialize};
-use std::time::Instant;
-use std::path::PathBuf;
-use std::env;
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex as StdMutex};
 use tracing::{info, debug, error};
 
+use crate::backend::InstanceAction;
+use crate::accounts::GcloudContext;
+use crate::credentials::Credentials;
+use crate::gcloud::{GcpInstance, GcpProject, RawInstance};
+use crate::hierarchy::{HierarchySource, LifecycleFilter, ResourceKind, ResourceNode};
+use crate::instance_details::InstanceDetails;
+use crate::inventory::InstanceFilter;
+use crate::machine_types::{name_filter, refine_instances, MachineSpec, MachineTypeCache, RawMachineType, DEFAULT_CACHE_TTL};
+use crate::operations::{OperationWaitConfig, TrackedOperation, ZoneOperation};
+use crate::pagination::{collect_pages, ListOptions, Page, PageSender};
+use crate::validation::{validate_machine_type, validate_project_id, validate_resource_parent};
+
 // ==========================================
 // AUTENTICACIÓN - Versión Simplificada
 // ==========================================
 
+/// Refresh tokens this long before Google says they expire
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+lazy_static! {
+    /// Shared HTTP client (connection pool) for every REST call
+    ///
+    /// Pooled connections are driven by the runtime that opened them, so
+    /// sync callers go through [`crate::runtime::block_on`].
+    static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::new();
+
+    /// Token caches shared by every GcpAuthClient using the same credentials
+    static ref TOKEN_CACHES: StdMutex<HashMap<String, Arc<TokenCache>>> = StdMutex::new(HashMap::new());
+}
+
+/// Shared reqwest client; cloning is cheap and reuses connections
+pub(crate) fn http_client() -> reqwest::Client {
+    HTTP_CLIENT.clone()
+}
+
+/// GET autenticado que devuelve el cuerpo JSON
+async fn get_json(auth: &GcpAuthClient, url: &str, query: &[(&str, String)]) -> Result<serde_json::Value> {
+    // Access token cacheado entre páginas
+    let token = auth.get_access_token().await?;
+
+    debug!("Making request to: {}", url);
+
+    let response = http_client()
+        .get(url)
+        .bearer_auth(&token)
+        .query(query)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to send request: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        error!("API request failed: {} - {}", status, error_text);
+        return Err(anyhow!("API error {}: {}", status, error_text));
+    }
+
+    response.json().await.map_err(|e| anyhow!("Failed to parse JSON: {}", e))
+}
+
+/// Access token plus the instant it stops being valid
+#[derive(Debug, Clone)]
+pub struct CachedToken {
+    pub access_token: String,
+    pub expires_at: Instant,
+}
+
+impl CachedToken {
+    /// Still usable and not inside the proactive refresh window
+    fn is_fresh(&self, now: Instant) -> bool {
+        now + TOKEN_REFRESH_MARGIN < self.expires_at
+    }
+}
+
+/// Single-slot token cache
+///
+/// The async mutex is held while refreshing, so concurrent callers wait for
+/// the in-flight exchange instead of starting their own.
+#[derive(Default)]
+pub struct TokenCache {
+    slot: tokio::sync::Mutex<Option<CachedToken>>,
+}
+
+impl TokenCache {
+    /// Return the cached token, or run `refresh` if it is missing or about to expire
+    pub async fn get_or_refresh<F, Fut>(&self, refresh: F) -> Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<CachedToken>>,
+    {
+        let mut slot = self.slot.lock().await;
+        if let Some(cached) = slot.as_ref() {
+            if cached.is_fresh(Instant::now()) {
+                return Ok(cached.access_token.clone());
+            }
+        }
+
+        let token = refresh().await?;
+        let access_token = token.access_token.clone();
+        *slot = Some(token);
+        Ok(access_token)
+    }
+
+    /// Drop the cached token (e.g. after a 401)
+    pub async fn invalidate(&self) {
+        *self.slot.lock().await = None;
+    }
+}
+
 /// Gestor de autenticación para Google Cloud
 ///
-/// Usa gcloud Application Default Credentials existentes
+/// Credentials are discovered like ADC (see `credentials.rs`). Access tokens
+/// are cached per credential source and shared by every client in the process.
+#[derive(Clone)]
 pub struct GcpAuthClient {
-    credentials_path: PathBuf,
+    credentials: Arc<Credentials>,
+    cache: Arc<TokenCache>,
 }
 
 impl GcpAuthClient {
     /// Inicializar cliente de autenticación
-    /// Usa las credenciales que ya tiene gcloud configuradas
     pub async fn new() -> Result<Self> {
-        info!("🔐 Initializing GCP authentication (using gcloud ADC)");
-
-        // Buscar credenciales en ~/.config/gcloud/application_default_credentials.json
-        let home = env::var("HOME")
-            .or_else(|_| env::var("USERPROFILE"))
-            .map_err(|_| anyhow!("Could not determine home directory"))?;
-
-        let creds_path = PathBuf::from(home)
-            .join(".config")
-            .join("gcloud")
-            .join("application_default_credentials.json");
-
-        if !creds_path.exists() {
-            return Err(anyhow!(
-                "Application Default Credentials not found.\n\n\
-                 Please run: gcloud auth application-default login\n\
-                 \n\
-                 This will create credentials at:\n\
-                 {}",
-                creds_path.display()
-            ));
-        }
+        info!("🔐 Initializing GCP authentication");
+        Self::from_credentials(Credentials::discover()?)
+    }
 
-        info!("✓ Found credentials at: {}", creds_path.display());
+    /// Cliente para una cuenta/configuración de gcloud concreta
+    pub async fn for_context(ctx: &GcloudContext) -> Result<Self> {
+        Self::from_credentials(Credentials::for_context(ctx)?)
+    }
+
+    /// Build a client for explicitly chosen credentials
+    pub fn from_credentials(credentials: Credentials) -> Result<Self> {
+        info!("✓ Using credentials: {}", credentials.describe());
+
+        let cache = TOKEN_CACHES
+            .lock()
+            .map_err(|_| anyhow!("Token cache lock poisoned"))?
+            .entry(credentials.cache_key())
+            .or_default()
+            .clone();
 
         Ok(Self {
-            credentials_path: creds_path,
+            credentials: Arc::new(credentials),
+            cache,
         })
     }
 
-    /// Obtener access token usando yup-oauth2
+    /// Obtener access token (cacheado hasta poco antes de expirar)
     pub async fn get_access_token(&self) -> Result<String> {
-        debug!("Getting access token from gcloud credentials");
-
-        // Leer el archivo de credenciales
-        let creds_json = std::fs::read_to_string(&self.credentials_path)
-            .map_err(|e| anyhow!("Failed to read credentials: {}", e))?;
-
-        // Parse credentials
-        let creds: serde_json::Value = serde_json::from_str(&creds_json)?;
-
-        // Para este PoC, vamos a usar el refresh token para obtener un access token
-        // usando la API de OAuth2 directamente
-        let client_id = creds["client_id"].as_str()
-            .ok_or_else(|| anyhow!("No client_id in credentials"))?;
-        let client_secret = creds["client_secret"].as_str()
-            .ok_or_else(|| anyhow!("No client_secret in credentials"))?;
-        let refresh_token = creds["refresh_token"].as_str()
-            .ok_or_else(|| anyhow!("No refresh_token in credentials"))?;
-
-        debug!("Using OAuth2 credentials to get access token");
-
-        // Usar reqwest para hacer el token exchange
-        let client = reqwest::Client::new();
-        let params = [
-            ("client_id", client_id),
-            ("client_secret", client_secret),
-            ("refresh_token", refresh_token),
-            ("grant_type", "refresh_token"),
-        ];
-
-        let response = client
-            .post("https://oauth2.googleapis.com/token")
-            .form(&params)
-            .send()
+        self.cache
+            .get_or_refresh(|| async {
+                debug!("Refreshing access token");
+                let token = self.credentials.fetch_token().await?;
+                debug!("✓ Got valid access token");
+                Ok(token)
+            })
             .await
-            .map_err(|e| anyhow!("Token request failed: {}", e))?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            // SECURITY: Log full error for debugging but return sanitized error to user
-            debug!("Token exchange failed with error: {}", error_text);
-            return Err(anyhow!("Authentication failed. Please try logging in again with: gcloud auth application-default login"));
-        }
-
-        let token_response: serde_json::Value = response.json().await?;
-        let access_token = token_response["access_token"].as_str()
-            .ok_or_else(|| anyhow!("No access_token in response"))?
-            .to_string();
+    }
 
-        debug!("✓ Got valid access token");
-        Ok(access_token)
+    /// Forget the cached token so the next call performs a fresh exchange
+    pub async fn invalidate_token(&self) {
+        self.cache.invalidate().await;
     }
 }
 
@@ -120,44 +188,33 @@ impl GcpAuthClient {
 // ==========================================
 
 /// Google Cloud Project (Client Library version)
-/// This struct is used by the Client Libraries implementation
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GcpProjectClientLib {
-    pub project_id: String,
-    pub name: Option<String>,
-    pub project_number: String,
-    pub state: String,
-}
-
-// Keep internal version for backwards compatibility
-type GcpProject = GcpProjectClientLib;
+/// Same model as the gcloud CLI path; kept as an alias for existing callers
+pub type GcpProjectClientLib = GcpProject;
 
 /// Google Cloud Compute Instance (Client Library version)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GcpInstanceClientLib {
-    pub name: String,
-    pub status: String,
-    pub zone: String,
-    pub machine_type: String,
-    pub cpu_count: Option<u32>,
-    pub memory_mb: Option<u32>,
-    pub disk_gb: Option<u32>,
-}
+/// Same model as the gcloud CLI path; kept as an alias for existing callers
+pub type GcpInstanceClientLib = GcpInstance;
 
 /// Cliente para interactuar con Resource Manager API
 pub struct ResourceManagerClient {
     auth: GcpAuthClient,
     base_url: String,
+    /// v3 has the folder/organization hierarchy
+    v3_base_url: String,
 }
 
 impl ResourceManagerClient {
     pub async fn new() -> Result<Self> {
-        let auth = GcpAuthClient::new().await?;
+        Ok(Self::with_auth(GcpAuthClient::new().await?))
+    }
 
-        Ok(Self {
+    /// Cliente con credenciales explícitas (p.ej. otra cuenta)
+    pub fn with_auth(auth: GcpAuthClient) -> Self {
+        Self {
             auth,
             base_url: "https://cloudresourcemanager.googleapis.com/v1".to_string(),
-        })
+            v3_base_url: "https://cloudresourcemanager.googleapis.com/v3".to_string(),
+        }
     }
 
     /// Listar todos los proyectos accesibles
@@ -170,69 +227,45 @@ impl ResourceManagerClient {
     /// AHORA (Client Library):
     /// Hace request directo a Resource Manager API
     pub async fn list_projects(&self) -> Result<Vec<GcpProject>> {
+        self.list_projects_paged(&ListOptions::default(), None).await
+    }
+
+    /// Listar proyectos página a página
+    ///
+    /// GET https://cloudresourcemanager.googleapis.com/v1/projects?pageSize=N&pageToken=T
+    ///
+    /// Each page is sent to `pages` as it arrives.
+    pub async fn list_projects_paged(
+        &self,
+        options: &ListOptions,
+        pages: Option<&PageSender<GcpProject>>,
+    ) -> Result<Vec<GcpProject>> {
         info!("📁 Listing GCP projects via REST API");
         let start = Instant::now();
 
-        // Obtener access token
-        let token = self.auth.get_access_token().await?;
-
-        // Crear HTTP client
-        let client = reqwest::Client::new();
-
-        // Request a la API
         let url = format!("{}/projects", self.base_url);
-
-        debug!("Making request to: {}", url);
-
-        let response = client
-            .get(&url)
-            .bearer_auth(&token)
-            .header("Content-Type", "application/json")
-            .send()
-            .await
-            .map_err(|e| anyhow!("Failed to send request: {}", e))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            error!("API request failed: {} - {}", status, error_text);
-            return Err(anyhow!("API error {}: {}", status, error_text));
-        }
-
-        // Parse response
-        let response_text = response.text().await?;
-        debug!("Response received: {} bytes", response_text.len());
-
-        let response_json: serde_json::Value = serde_json::from_str(&response_text)
-            .map_err(|e| anyhow!("Failed to parse JSON: {}", e))?;
-
-        // Extraer proyectos del response (API v1 format)
-        let projects_array = response_json["projects"]
-            .as_array()
-            .ok_or_else(|| anyhow!("No projects array in response"))?;
-
-        let mut projects = Vec::new();
-
-        for project_obj in projects_array {
-            projects.push(GcpProject {
-                project_id: project_obj["projectId"]
-                    .as_str()
-                    .unwrap_or_default()
-                    .to_string(),
-                name: project_obj["name"]
-                    .as_str()
-                    .map(|s| s.to_string()),
-                project_number: project_obj["projectNumber"]
-                    .as_u64()
-                    .map(|n| n.to_string())
-                    .or_else(|| project_obj["projectNumber"].as_str().map(|s| s.to_string()))
-                    .unwrap_or_default(),
-                state: project_obj["lifecycleState"]
-                    .as_str()
-                    .unwrap_or("UNKNOWN")
-                    .to_string(),
-            });
-        }
+        let projects = collect_pages(options, pages, |page_token| {
+            let url = url.clone();
+            async move {
+                let mut query = vec![("pageSize", options.page_size.to_string())];
+                if let Some(token) = page_token {
+                    query.push(("pageToken", token));
+                }
+                let response_json = get_json(&self.auth, &url, &query).await?;
+
+                // Una organización sin proyectos devuelve `{}`
+                let items = response_json["projects"]
+                    .as_array()
+                    .map(|projects| projects.iter().map(project_from_json).collect())
+                    .unwrap_or_default();
+
+                Ok(Page {
+                    items,
+                    next_page_token: response_json["nextPageToken"].as_str().map(str::to_string),
+                })
+            }
+        })
+        .await?;
 
         let elapsed = start.elapsed();
         info!(
@@ -245,6 +278,124 @@ impl ResourceManagerClient {
     }
 }
 
+impl ResourceManagerClient {
+    /// GET paginado sobre la API v3; `key` es el array de cada página
+    async fn list_v3(&self, path: &str, key: &str, params: Vec<(&'static str, String)>) -> Result<Vec<ResourceNode>> {
+        let url = format!("{}/{}", self.v3_base_url, path);
+        collect_pages(&ListOptions::default(), None, |page_token| {
+            let (url, mut query) = (url.clone(), params.clone());
+            async move {
+                query.push(("pageSize", ListOptions::default().page_size.to_string()));
+                if let Some(token) = page_token {
+                    query.push(("pageToken", token));
+                }
+                let page = get_json(&self.auth, &url, &query).await?;
+                let items = page[key]
+                    .as_array()
+                    .map(|items| items.iter().filter_map(node_from_v3_json).collect())
+                    .unwrap_or_default();
+                Ok(Page {
+                    items,
+                    next_page_token: page["nextPageToken"].as_str().map(str::to_string),
+                })
+            }
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl HierarchySource for ResourceManagerClient {
+    /// GET https://cloudresourcemanager.googleapis.com/v3/organizations:search
+    async fn organizations(&self) -> Result<Vec<ResourceNode>> {
+        self.list_v3("organizations:search", "organizations", Vec::new()).await
+    }
+
+    /// GET https://cloudresourcemanager.googleapis.com/v3/folders?parent=...
+    async fn folders(&self, parent: &str, lifecycle: LifecycleFilter) -> Result<Vec<ResourceNode>> {
+        validate_resource_parent(parent)?;
+        let show_deleted = lifecycle == LifecycleFilter::IncludeDeleteRequested;
+        self.list_v3(
+            "folders",
+            "folders",
+            vec![("parent", parent.to_string()), ("showDeleted", show_deleted.to_string())],
+        )
+        .await
+    }
+
+    /// GET https://cloudresourcemanager.googleapis.com/v3/folders:search
+    async fn search_folders(&self) -> Result<Vec<ResourceNode>> {
+        self.list_v3("folders:search", "folders", Vec::new()).await
+    }
+
+    /// GET https://cloudresourcemanager.googleapis.com/v3/projects:search?query=parent:...
+    async fn projects(&self, parent: Option<&str>, lifecycle: LifecycleFilter) -> Result<Vec<ResourceNode>> {
+        let state = match lifecycle {
+            LifecycleFilter::ActiveOnly => "state:ACTIVE",
+            LifecycleFilter::IncludeDeleteRequested => "(state:ACTIVE OR state:DELETE_REQUESTED)",
+        };
+        let query = match parent {
+            Some(parent) => {
+                validate_resource_parent(parent)?;
+                format!("parent:{} {}", parent, state)
+            }
+            None => state.to_string(),
+        };
+
+        self.list_v3("projects:search", "projects", vec![("query", query)]).await
+    }
+}
+
+/// Organization, folder o proyecto en formato v3
+fn node_from_v3_json(item: &serde_json::Value) -> Option<ResourceNode> {
+    let name = item["name"].as_str()?.to_string();
+    let kind = match name.split('/').next()? {
+        "organizations" => ResourceKind::Organization,
+        "folders" => ResourceKind::Folder,
+        "projects" => ResourceKind::Project,
+        _ => return None,
+    };
+    let project_id = item["projectId"].as_str().map(str::to_string);
+    let display_name = item["displayName"]
+        .as_str()
+        .filter(|d| !d.is_empty())
+        .map(str::to_string)
+        .or_else(|| project_id.clone())
+        .unwrap_or_else(|| name.clone());
+
+    Some(ResourceNode {
+        kind,
+        display_name,
+        parent: item["parent"].as_str().filter(|p| !p.is_empty()).map(str::to_string),
+        project_id,
+        state: item["state"].as_str().unwrap_or("STATE_UNSPECIFIED").to_string(),
+        name,
+    })
+}
+
+/// Proyecto en formato Resource Manager v1
+fn project_from_json(project_obj: &serde_json::Value) -> GcpProject {
+    GcpProject {
+        project_id: project_obj["projectId"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        name: project_obj["name"]
+            .as_str()
+            .map(|s| s.to_string()),
+        project_number: project_obj["projectNumber"]
+            .as_u64()
+            .map(|n| n.to_string())
+            .or_else(|| project_obj["projectNumber"].as_str().map(|s| s.to_string())),
+        state: Some(
+            project_obj["lifecycleState"]
+                .as_str()
+                .unwrap_or("UNKNOWN")
+                .to_string(),
+        ),
+    }
+}
+
 // ==========================================
 // FUNCIONES PÚBLICAS PARA FFI BRIDGE
 // ==========================================
@@ -267,7 +418,7 @@ pub async fn benchmark_list_projects_async() -> Result<String> {
     info!("\n=== Test 2: gcloud CLI (existing implementation) ===");
     let start_cli = Instant::now();
 
-    let projects_cli = crate::gcloud::get_projects_async().await?;
+    let projects_cli = crate::gcloud::get_projects_async(&GcloudContext::default()).await?;
 
     let time_cli = start_cli.elapsed();
     info!("✓ gcloud CLI: {} projects in {:?}", projects_cli.len(), time_cli);
@@ -337,82 +488,8 @@ pub async fn list_projects_simple_async() -> Result<Vec<GcpProject>> {
 // COMPUTE ENGINE API - INSTANCIAS
 // ==========================================
 
-/// Extract CPU and memory specs from machine type name
-/// Supports standard machine types by parsing their pattern (e.g., "e2-standard-4" → 4 CPUs)
-/// Returns (cpu_count, memory_mb) if the machine type can be determined
-fn get_machine_specs(machine_type: &str) -> Option<(u32, u32)> {
-    // Special cases (micro, small, medium)
-    match machine_type {
-        "e2-micro" => return Some((2, 1024)),
-        "e2-small" => return Some((2, 2048)),
-        "e2-medium" => return Some((2, 4096)),
-        "f1-micro" => return Some((1, 614)),
-        "g1-small" => return Some((1, 1740)),
-        _ => {}
-    }
-
-    // Parse standard machine types: {series}-{type}-{cpus}
-    // Examples: e2-standard-4, n1-standard-8, n2-highmem-16, c2-standard-30
-    let parts: Vec<&str> = machine_type.split('-').collect();
-
-    if parts.len() >= 3 {
-        let series = parts[0];      // e2, n1, n2, n2d, c2, c3, t2d, etc.
-        let type_name = parts[1];   // standard, highmem, highcpu, custom
-
-        // Try to parse CPU count from the last part
-        if let Ok(cpu_count) = parts[2].parse::<u32>() {
-            // Calculate memory based on type and series
-            let memory_mb = match (series, type_name) {
-                // E2 series (cost-optimized): 4GB per vCPU for standard
-                ("e2", "standard") => cpu_count * 4096,
-                ("e2", "highmem") => cpu_count * 8192,
-                ("e2", "highcpu") => cpu_count * 1024,
-
-                // N1 series: 3.75GB per vCPU for standard
-                ("n1", "standard") => cpu_count * 3840,
-                ("n1", "highmem") => cpu_count * 6656,
-                ("n1", "highcpu") => cpu_count * 922,
-
-                // N2/N2D series: 4GB per vCPU for standard
-                ("n2" | "n2d", "standard") => cpu_count * 4096,
-                ("n2" | "n2d", "highmem") => cpu_count * 8192,
-                ("n2" | "n2d", "highcpu") => cpu_count * 1024,
-
-                // C2 series (compute-optimized): 4GB per vCPU
-                ("c2", "standard") => cpu_count * 4096,
-
-                // C3 series (newer compute): 4GB per vCPU
-                ("c3", "standard") => cpu_count * 4096,
-                ("c3", "highmem") => cpu_count * 8192,
-                ("c3", "highcpu") => cpu_count * 2048,
-
-                // T2D series (AMD): 4GB per vCPU
-                ("t2d", "standard") => cpu_count * 4096,
-
-                // M1/M2/M3 series (memory-optimized): Much higher memory
-                ("m1", "megamem") => cpu_count * 14336,  // 14GB per vCPU
-                ("m1", "ultramem") => cpu_count * 24576, // 24GB per vCPU
-                ("m2", "megamem") => cpu_count * 14336,
-                ("m2", "ultramem") => cpu_count * 24576,
-                ("m3", "megamem") => cpu_count * 14336,
-                ("m3", "ultramem") => cpu_count * 24576,
-
-                // A2 series (GPU): 12GB per vCPU
-                ("a2", _) => cpu_count * 12288,
-
-                // Default fallback: assume 4GB per vCPU (most common)
-                _ => cpu_count * 4096,
-            };
-
-            return Some((cpu_count, memory_mb));
-        }
-    }
-
-    // If we can't parse it, return None
-    None
-}
-
 /// Cliente para interactuar con Compute Engine API
+#[derive(Clone)]
 pub struct ComputeEngineClient {
     auth: GcpAuthClient,
     base_url: String,
@@ -420,12 +497,15 @@ pub struct ComputeEngineClient {
 
 impl ComputeEngineClient {
     pub async fn new() -> Result<Self> {
-        let auth = GcpAuthClient::new().await?;
+        Ok(Self::with_auth(GcpAuthClient::new().await?))
+    }
 
-        Ok(Self {
+    /// Cliente con credenciales explícitas (p.ej. otra cuenta)
+    pub fn with_auth(auth: GcpAuthClient) -> Self {
+        Self {
             auth,
             base_url: "https://compute.googleapis.com/compute/v1".to_string(),
-        })
+        }
     }
 
     /// Listar todas las instancias en un proyecto
@@ -438,24 +518,159 @@ impl ComputeEngineClient {
     /// AHORA (Client Library):
     /// GET https://compute.googleapis.com/compute/v1/projects/{project}/aggregatedList/instances
     pub async fn list_instances(&self, project: &str) -> Result<Vec<GcpInstanceClientLib>> {
+        self.list_instances_paged(project, &ListOptions::default(), None).await
+    }
+
+    /// Listar instancias página a página (aggregatedList, todas las zonas)
+    ///
+    /// GET .../projects/{project}/aggregated/instances?maxResults=N&pageToken=T
+    ///
+    /// Pages sent to `pages` carry the offline CPU/memory estimates; the
+    /// returned list is refined through the machineTypes cache.
+    pub async fn list_instances_paged(
+        &self,
+        project: &str,
+        options: &ListOptions,
+        pages: Option<&PageSender<GcpInstance>>,
+    ) -> Result<Vec<GcpInstanceClientLib>> {
+        self.list_instances_filtered(project, &InstanceFilter::default(), options, pages).await
+    }
+
+    /// Listar instancias aplicando el filtro en el servidor (status y labels)
+    ///
+    /// Name and zone are not part of the API filter; callers check them with
+    /// [`InstanceFilter::matches`].
+    pub async fn list_instances_filtered(
+        &self,
+        project: &str,
+        filter: &InstanceFilter,
+        options: &ListOptions,
+        pages: Option<&PageSender<GcpInstance>>,
+    ) -> Result<Vec<GcpInstanceClientLib>> {
+        filter.validate()?;
+        let api_filter = filter.api_expression();
         info!("🖥️  Listing instances for project: {}", project);
         let start = Instant::now();
 
-        // Obtener access token
-        let token = self.auth.get_access_token().await?;
+        let url = format!("{}/projects/{}/aggregated/instances", self.base_url, project);
+        let mut instances = collect_pages(options, pages, |page_token| {
+            let (url, api_filter) = (url.clone(), api_filter.clone());
+            async move {
+                let mut query = vec![
+                    ("maxResults", options.page_size.to_string()),
+                    ("returnPartialSuccess", "true".to_string()),
+                ];
+                if let Some(expression) = &api_filter {
+                    query.push(("filter", expression.clone()));
+                }
+                if let Some(token) = page_token {
+                    query.push(("pageToken", token));
+                }
+                let response_json = get_json(&self.auth, &url, &query).await?;
+
+                // Extraer instancias del response (aggregatedList format)
+                let mut items = Vec::new();
+                if let Some(zones) = response_json["items"].as_object() {
+                    for zone_data in zones.values() {
+                        for instance_obj in zone_data["instances"].as_array().into_iter().flatten() {
+                            // The REST representation matches gcloud's JSON output
+                            let raw: RawInstance = serde_json::from_value(instance_obj.clone())
+                                .map_err(|e| anyhow!("Failed to parse instance: {}", e))?;
+                            items.push(GcpInstance::from(raw));
+                        }
+                    }
+                }
+                for unreachable in response_json["unreachables"].as_array().into_iter().flatten() {
+                    tracing::warn!(zone = %unreachable, project = project, "Zone unreachable while listing instances");
+                }
 
-        // Crear HTTP client
-        let client = reqwest::Client::new();
+                Ok(Page {
+                    items,
+                    next_page_token: response_json["nextPageToken"].as_str().map(str::to_string),
+                })
+            }
+        })
+        .await?;
+
+        // CPU/memory exactos desde machineTypes (cache en disco)
+        match MachineTypeCache::for_project(project, DEFAULT_CACHE_TTL) {
+            Ok(mut cache) => {
+                refine_instances(&mut instances, &mut cache, |names| async move {
+                    self.list_machine_types(project, &names).await
+                })
+                .await?;
+            }
+            Err(e) => tracing::warn!(error = %e, "Machine type cache unavailable"),
+        }
 
-        // Request a la API (aggregatedList para obtener de todas las zonas)
-        let url = format!("{}/projects/{}/aggregated/instances", self.base_url, project);
+        let elapsed = start.elapsed();
+        info!(
+            "✓ Listed {} instances in {:?} (Client Library)",
+            instances.len(),
+            elapsed
+        );
+
+        Ok(instances)
+    }
+
+    /// Specs de los machine types indicados, en todas las zonas
+    ///
+    /// GET https://compute.googleapis.com/compute/v1/projects/{project}/aggregated/machineTypes
+    ///
+    /// Returns (zone, spec) pairs; follows `nextPageToken`.
+    pub async fn list_machine_types(&self, project: &str, names: &BTreeSet<String>) -> Result<Vec<(String, MachineSpec)>> {
+        validate_project_id(project)?;
+        let url = format!("{}/projects/{}/aggregated/machineTypes", self.base_url, project);
+        let filter = name_filter(names);
+
+        let specs = collect_pages(&ListOptions::default(), None, |page_token| {
+            let (url, filter) = (url.clone(), filter.clone());
+            async move {
+                let mut query = vec![("filter", filter)];
+                if let Some(token) = page_token {
+                    query.push(("pageToken", token));
+                }
+                let page = get_json(&self.auth, &url, &query).await?;
+
+                let mut items = Vec::new();
+                if let Some(zones) = page["items"].as_object() {
+                    for zone_data in zones.values() {
+                        for machine_type in zone_data["machineTypes"].as_array().into_iter().flatten() {
+                            let raw: RawMachineType = serde_json::from_value(machine_type.clone())
+                                .map_err(|e| anyhow!("Failed to parse machine type: {}", e))?;
+                            items.push(raw.into_zone_spec());
+                        }
+                    }
+                }
+                Ok(Page {
+                    items,
+                    next_page_token: page["nextPageToken"].as_str().map(str::to_string),
+                })
+            }
+        })
+        .await?;
+
+        debug!("Resolved {} machine type specs for {}", specs.len(), project);
+        Ok(specs)
+    }
+
+    /// Obtener una instancia concreta (JSON del recurso Instance)
+    ///
+    /// GET https://compute.googleapis.com/compute/v1/projects/{project}/zones/{zone}/instances/{instance}
+    async fn get_instance_json(&self, project: &str, zone: &str, instance: &str) -> Result<serde_json::Value> {
+        debug!("Describing instance: {} in {}/{}", instance, project, zone);
 
-        debug!("Making request to: {}", url);
+        let token = self.auth.get_access_token().await?;
+        let client = http_client();
+
+        let url = format!(
+            "{}/projects/{}/zones/{}/instances/{}",
+            self.base_url, project, zone, instance
+        );
 
         let response = client
             .get(&url)
             .bearer_auth(&token)
-            .header("Content-Type", "application/json")
             .send()
             .await
             .map_err(|e| anyhow!("Failed to send request: {}", e))?;
@@ -463,112 +678,44 @@ impl ComputeEngineClient {
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            error!("API request failed: {} - {}", status, error_text);
             return Err(anyhow!("API error {}: {}", status, error_text));
         }
 
-        // Parse response
-        let response_text = response.text().await?;
-        debug!("Response received: {} bytes", response_text.len());
-
-        let response_json: serde_json::Value = serde_json::from_str(&response_text)
-            .map_err(|e| anyhow!("Failed to parse JSON: {}", e))?;
-
-        // Extraer instancias del response (aggregatedList format)
-        let mut instances = Vec::new();
-
-        if let Some(items) = response_json["items"].as_object() {
-            for (_zone_name, zone_data) in items {
-                if let Some(zone_instances) = zone_data["instances"].as_array() {
-                    for instance_obj in zone_instances {
-                        let name = instance_obj["name"]
-                            .as_str()
-                            .unwrap_or_default()
-                            .to_string();
-
-                        let status = instance_obj["status"]
-                            .as_str()
-                            .unwrap_or("UNKNOWN")
-                            .to_string();
-
-                        // Extract zone from URL (e.g., "zones/us-central1-a" -> "us-central1-a")
-                        let zone = instance_obj["zone"]
-                            .as_str()
-                            .and_then(|z| z.split('/').last())
-                            .unwrap_or_default()
-                            .to_string();
-
-                        // Extract machine type from URL
-                        let machine_type = instance_obj["machineType"]
-                            .as_str()
-                            .and_then(|mt| mt.split('/').last())
-                            .unwrap_or_default()
-                            .to_string();
-
-                        // Extract disk size from boot disk
-                        let disk_gb = instance_obj["disks"]
-                            .as_array()
-                            .and_then(|disks| {
-                                disks.iter()
-                                    .find(|d| d["boot"].as_bool().unwrap_or(false))
-                                    .or_else(|| disks.first())
-                            })
-                            .and_then(|disk| disk["diskSizeGb"].as_str())
-                            .and_then(|size| size.parse::<u32>().ok());
-
-                        // Extract CPU and memory from machine type name
-                        let (cpu_count, memory_mb) = get_machine_specs(&machine_type)
-                            .map(|(cpu, mem)| (Some(cpu), Some(mem)))
-                            .unwrap_or((None, None));
-
-                        instances.push(GcpInstanceClientLib {
-                            name,
-                            status,
-                            zone,
-                            machine_type,
-                            cpu_count,
-                            memory_mb,
-                            disk_gb,
-                        });
-                    }
-                }
-            }
-        }
+        response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse instance JSON: {}", e))
+    }
 
-        let elapsed = start.elapsed();
-        info!(
-            "✓ Listed {} instances in {:?} (Client Library)",
-            instances.len(),
-            elapsed
-        );
+    /// Obtener una instancia concreta (modelo compacto)
+    pub async fn describe_instance(&self, project: &str, zone: &str, instance: &str) -> Result<GcpInstance> {
+        let json = self.get_instance_json(project, zone, instance).await?;
+        let raw: RawInstance = serde_json::from_value(json)
+            .map_err(|e| anyhow!("Failed to parse instance JSON: {}", e))?;
+        Ok(raw.into())
+    }
 
-        Ok(instances)
+    /// Obtener la descripción completa de una instancia
+    pub async fn describe_instance_details(&self, project: &str, zone: &str, instance: &str) -> Result<InstanceDetails> {
+        let json = self.get_instance_json(project, zone, instance).await?;
+        InstanceDetails::from_value(json)
     }
 
-    /// Iniciar una instancia detenida
+    /// Consultar el estado de una operación de zona
     ///
-    /// ANTES (gcloud CLI):
-    /// ```
-    /// gcloud compute instances start INSTANCE --zone=ZONE --project=PROJECT
-    /// ```
-    ///
-    /// AHORA (Client Library):
-    /// POST https://compute.googleapis.com/compute/v1/projects/{project}/zones/{zone}/instances/{instance}/start
-    pub async fn start_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
-        info!("▶️  Starting instance: {} in {}/{}", instance, project, zone);
-
+    /// GET https://compute.googleapis.com/compute/v1/projects/{project}/zones/{zone}/operations/{operation}
+    pub async fn get_zone_operation(&self, project: &str, zone: &str, operation: &str) -> Result<ZoneOperation> {
         let token = self.auth.get_access_token().await?;
-        let client = reqwest::Client::new();
+        let client = http_client();
 
         let url = format!(
-            "{}/projects/{}/zones/{}/instances/{}/start",
-            self.base_url, project, zone, instance
+            "{}/projects/{}/zones/{}/operations/{}",
+            self.base_url, project, zone, operation
         );
 
         let response = client
-            .post(&url)
+            .get(&url)
             .bearer_auth(&token)
-            .header("Content-Type", "application/json")
             .send()
             .await
             .map_err(|e| anyhow!("Failed to send request: {}", e))?;
@@ -579,28 +726,65 @@ impl ComputeEngineClient {
             return Err(anyhow!("API error {}: {}", status, error_text));
         }
 
-        info!("✓ Instance start operation initiated");
-        Ok(())
+        response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse operation JSON: {}", e))
     }
 
-    /// Detener una instancia en ejecución
+    /// Lanzar start/stop/reset/suspend/resume y seguir la operación resultante
     ///
-    /// POST https://compute.googleapis.com/compute/v1/projects/{project}/zones/{zone}/instances/{instance}/stop
-    pub async fn stop_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
-        info!("⏹️  Stopping instance: {} in {}/{}", instance, project, zone);
+    /// ANTES (gcloud CLI):
+    /// ```
+    /// gcloud compute instances start INSTANCE --zone=ZONE --project=PROJECT
+    /// ```
+    ///
+    /// AHORA (Client Library):
+    /// POST https://compute.googleapis.com/compute/v1/projects/{project}/zones/{zone}/instances/{instance}/{action}
+    ///
+    /// The returned operation resolves once GCE reports it DONE, so callers
+    /// know the VM actually reached RUNNING/TERMINATED/SUSPENDED.
+    pub async fn begin_instance_action(
+        &self,
+        project: &str,
+        zone: &str,
+        instance: &str,
+        action: InstanceAction,
+        config: OperationWaitConfig,
+    ) -> Result<TrackedOperation> {
+        info!("{:?} instance: {} in {}/{}", action, instance, project, zone);
+        self.begin_operation(project, zone, instance, action.verb(), &[], None, config).await
+    }
 
+    /// POST a un método de la instancia y seguir la operación resultante
+    #[allow(clippy::too_many_arguments)]
+    async fn begin_operation(
+        &self,
+        project: &str,
+        zone: &str,
+        instance: &str,
+        method: &str,
+        query: &[(&str, String)],
+        body: Option<serde_json::Value>,
+        config: OperationWaitConfig,
+    ) -> Result<TrackedOperation> {
         let token = self.auth.get_access_token().await?;
-        let client = reqwest::Client::new();
+        let client = http_client();
 
         let url = format!(
-            "{}/projects/{}/zones/{}/instances/{}/stop",
-            self.base_url, project, zone, instance
+            "{}/projects/{}/zones/{}/instances/{}/{}",
+            self.base_url, project, zone, instance, method
         );
 
-        let response = client
+        let mut request = client
             .post(&url)
             .bearer_auth(&token)
-            .header("Content-Type", "application/json")
+            .query(query)
+            .header("Content-Type", "application/json");
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request
             .send()
             .await
             .map_err(|e| anyhow!("Failed to send request: {}", e))?;
@@ -611,43 +795,192 @@ impl ComputeEngineClient {
             return Err(anyhow!("API error {}: {}", status, error_text));
         }
 
-        info!("✓ Instance stop operation initiated");
-        Ok(())
+        let operation: ZoneOperation = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse operation JSON: {}", e))?;
+        info!("✓ Instance {} operation initiated: {}", method, operation.name);
+
+        let poller = self.clone();
+        let (project, zone) = (project.to_string(), zone.to_string());
+        Ok(TrackedOperation::spawn(operation, config, move |name| {
+            let poller = poller.clone();
+            let (project, zone) = (project.clone(), zone.clone());
+            async move { poller.get_zone_operation(&project, &zone, &name).await }
+        }))
     }
 
-    /// Reiniciar una instancia
+    /// Cambiar el machine type (la instancia debe estar TERMINATED)
     ///
-    /// POST https://compute.googleapis.com/compute/v1/projects/{project}/zones/{zone}/instances/{instance}/reset
-    pub async fn reset_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
-        info!("🔄 Resetting instance: {} in {}/{}", instance, project, zone);
+    /// POST .../instances/{instance}/setMachineType {"machineType": "zones/{zone}/machineTypes/{type}"}
+    pub async fn begin_set_machine_type(
+        &self,
+        project: &str,
+        zone: &str,
+        instance: &str,
+        machine_type: &str,
+        config: OperationWaitConfig,
+    ) -> Result<TrackedOperation> {
+        validate_machine_type(machine_type)?;
+        info!("Setting machine type of {} to {}", instance, machine_type);
+        let body = serde_json::json!({ "machineType": format!("zones/{}/machineTypes/{}", zone, machine_type) });
+        self.begin_operation(project, zone, instance, "setMachineType", &[], Some(body), config).await
+    }
 
-        let token = self.auth.get_access_token().await?;
-        let client = reqwest::Client::new();
+    /// Activar o desactivar la protección contra borrado
+    ///
+    /// POST .../instances/{instance}/setDeletionProtection?deletionProtection=BOOL
+    pub async fn begin_set_deletion_protection(
+        &self,
+        project: &str,
+        zone: &str,
+        instance: &str,
+        enabled: bool,
+        config: OperationWaitConfig,
+    ) -> Result<TrackedOperation> {
+        let query = [("deletionProtection", enabled.to_string())];
+        self.begin_operation(project, zone, instance, "setDeletionProtection", &query, None, config).await
+    }
 
+    /// Spec de un machine type en una zona, `None` si la zona no lo ofrece
+    ///
+    /// GET https://compute.googleapis.com/compute/v1/projects/{project}/zones/{zone}/machineTypes/{type}
+    pub async fn get_machine_type(&self, project: &str, zone: &str, machine_type: &str) -> Result<Option<MachineSpec>> {
+        validate_machine_type(machine_type)?;
+        let token = self.auth.get_access_token().await?;
         let url = format!(
-            "{}/projects/{}/zones/{}/instances/{}/reset",
-            self.base_url, project, zone, instance
+            "{}/projects/{}/zones/{}/machineTypes/{}",
+            self.base_url, project, zone, machine_type
         );
 
-        let response = client
-            .post(&url)
+        let response = http_client()
+            .get(&url)
             .bearer_auth(&token)
-            .header("Content-Type", "application/json")
             .send()
             .await
             .map_err(|e| anyhow!("Failed to send request: {}", e))?;
 
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             return Err(anyhow!("API error {}: {}", status, error_text));
         }
 
-        info!("✓ Instance reset operation initiated");
+        let raw: RawMachineType = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse machine type JSON: {}", e))?;
+        Ok(Some(raw.into_zone_spec().1))
+    }
+
+    /// Iniciar una instancia detenida y esperar a que esté RUNNING
+    pub async fn start_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.begin_instance_action(project, zone, instance, InstanceAction::Start, OperationWaitConfig::default())
+            .await?
+            .await?;
+        info!("✓ Instance {} started", instance);
+        Ok(())
+    }
+
+    /// Detener una instancia en ejecución y esperar a que esté TERMINATED
+    pub async fn stop_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.begin_instance_action(project, zone, instance, InstanceAction::Stop, OperationWaitConfig::default())
+            .await?
+            .await?;
+        info!("✓ Instance {} stopped", instance);
+        Ok(())
+    }
+
+    /// Reiniciar una instancia y esperar a que termine el reset
+    pub async fn reset_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.begin_instance_action(project, zone, instance, InstanceAction::Reset, OperationWaitConfig::default())
+            .await?
+            .await?;
+        info!("✓ Instance {} reset", instance);
+        Ok(())
+    }
+
+    /// Suspender una instancia y esperar a que esté SUSPENDED
+    pub async fn suspend_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.begin_instance_action(project, zone, instance, InstanceAction::Suspend, OperationWaitConfig::default())
+            .await?
+            .await?;
+        info!("✓ Instance {} suspended", instance);
+        Ok(())
+    }
+
+    /// Reanudar una instancia suspendida
+    pub async fn resume_instance(&self, project: &str, zone: &str, instance: &str) -> Result<()> {
+        self.begin_instance_action(project, zone, instance, InstanceAction::Resume, OperationWaitConfig::default())
+            .await?
+            .await?;
+        info!("✓ Instance {} resumed", instance);
+        Ok(())
+    }
+
+    pub async fn set_machine_type(&self, project: &str, zone: &str, instance: &str, machine_type: &str) -> Result<()> {
+        self.begin_set_machine_type(project, zone, instance, machine_type, OperationWaitConfig::default())
+            .await?
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_deletion_protection(&self, project: &str, zone: &str, instance: &str, enabled: bool) -> Result<()> {
+        self.begin_set_deletion_protection(project, zone, instance, enabled, OperationWaitConfig::default())
+            .await?
+            .await?;
         Ok(())
     }
 }
 
+// ==========================================
+// CLOUD BILLING CATALOG
+// ==========================================
+
+/// Compute Engine service in the Cloud Billing Catalog
+pub const COMPUTE_BILLING_SERVICE: &str = "services/6F81-5844-456A";
+
+/// Cliente para leer precios públicos del Cloud Billing Catalog API
+pub struct CloudBillingClient {
+    auth: GcpAuthClient,
+    base_url: String,
+}
+
+impl CloudBillingClient {
+    /// Cliente con credenciales explícitas (p.ej. otra cuenta)
+    pub fn with_auth(auth: GcpAuthClient) -> Self {
+        Self {
+            auth,
+            base_url: "https://cloudbilling.googleapis.com/v1".to_string(),
+        }
+    }
+
+    /// Every SKU of the Compute Engine service, as raw JSON
+    ///
+    /// GET https://cloudbilling.googleapis.com/v1/services/6F81-5844-456A/skus
+    pub async fn list_compute_skus(&self, options: &ListOptions) -> Result<Vec<serde_json::Value>> {
+        let url = format!("{}/{}/skus", self.base_url, COMPUTE_BILLING_SERVICE);
+        collect_pages(options, None, |page_token| {
+            let url = url.clone();
+            async move {
+                let mut query = vec![("pageSize", options.page_size.to_string())];
+                if let Some(token) = page_token {
+                    query.push(("pageToken", token));
+                }
+                let page = get_json(&self.auth, &url, &query).await?;
+                Ok(Page {
+                    items: page["skus"].as_array().cloned().unwrap_or_default(),
+                    next_page_token: page["nextPageToken"].as_str().map(str::to_string),
+                })
+            }
+        })
+        .await
+    }
+}
+
 /// List instances using Client Libraries (public API for FFI)
 pub async fn list_instances_client_lib(project: &str) -> Result<Vec<GcpInstanceClientLib>> {
     let client = ComputeEngineClient::new().await?;
@@ -675,6 +1008,75 @@ pub async fn reset_instance_client_lib(project: &str, zone: &str, instance: &str
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn token(value: &str, ttl: Duration) -> CachedToken {
+        CachedToken { access_token: value.to_string(), expires_at: Instant::now() + ttl }
+    }
+
+    #[tokio::test]
+    async fn test_token_cache_dedupes_concurrent_refreshes() {
+        let cache = Arc::new(TokenCache::default());
+        let refreshes = Arc::new(AtomicU32::new(0));
+
+        let mut tasks = Vec::new();
+        for _ in 0..8 {
+            let (cache, refreshes) = (cache.clone(), refreshes.clone());
+            tasks.push(tokio::spawn(async move {
+                cache
+                    .get_or_refresh(|| async {
+                        refreshes.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        Ok(token("fresh", Duration::from_secs(3600)))
+                    })
+                    .await
+            }));
+        }
+        for task in tasks {
+            assert_eq!(task.await.unwrap().unwrap(), "fresh");
+        }
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_token_cache_refreshes_before_expiry() {
+        let cache = TokenCache::default();
+        // Inside the refresh margin: must not be reused
+        cache.get_or_refresh(|| async { Ok(token("old", Duration::from_secs(60))) }).await.unwrap();
+        let next = cache
+            .get_or_refresh(|| async { Ok(token("new", Duration::from_secs(3600))) })
+            .await
+            .unwrap();
+        assert_eq!(next, "new");
+
+        let cached = cache
+            .get_or_refresh(|| async { Err(anyhow!("should not refresh")) })
+            .await
+            .unwrap();
+        assert_eq!(cached, "new");
+
+        cache.invalidate().await;
+        assert!(cache.get_or_refresh(|| async { Err(anyhow!("refresh failed")) }).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_v3_nodes() {
+        let project = serde_json::json!({
+            "name": "projects/415104041262",
+            "parent": "folders/93198982071",
+            "projectId": "billing-prod",
+            "state": "ACTIVE",
+            "displayName": ""
+        });
+        let node = node_from_v3_json(&project).unwrap();
+        assert_eq!(node.kind, ResourceKind::Project);
+        assert_eq!(node.display_name, "billing-prod");
+        assert_eq!(node.parent.as_deref(), Some("folders/93198982071"));
+
+        let org = serde_json::json!({"name": "organizations/1", "displayName": "example.com", "state": "ACTIVE"});
+        assert_eq!(node_from_v3_json(&org).unwrap().parent, None);
+        assert!(node_from_v3_json(&serde_json::json!({"name": "tagKeys/1"})).is_none());
+    }
 
     #[tokio::test]
     async fn test_auth_initialization() {
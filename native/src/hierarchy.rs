@@ -0,0 +1,327 @@
+//! Organization / folder / project hierarchy
+//!
+//! Large estates are only navigable through their folder structure, so the
+//! project picker can browse Resource Manager v3 as a tree: organizations at
+//! the root, then folders and projects one level at a time. Children are
+//! fetched the first time a node is expanded and cached afterwards. Folders
+//! and projects whose parent the caller can't see are roots as well.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::accounts::GcloudContext;
+use crate::gcloud_client_poc::{GcpAuthClient, ResourceManagerClient};
+use crate::runtime;
+use crate::validation::validate_resource_parent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResourceKind {
+    Organization,
+    Folder,
+    Project,
+}
+
+/// One node of the tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceNode {
+    pub kind: ResourceKind,
+    /// Resource name: `organizations/123`, `folders/456`, `projects/789`
+    pub name: String,
+    pub display_name: String,
+    /// Resource name of the parent, `None` for organizations and orphan projects
+    pub parent: Option<String>,
+    /// Only set for projects
+    pub project_id: Option<String>,
+    /// ACTIVE, DELETE_REQUESTED, ...
+    pub state: String,
+}
+
+impl ResourceNode {
+    /// Organizations and folders can be expanded
+    pub fn is_container(&self) -> bool {
+        self.kind != ResourceKind::Project
+    }
+}
+
+/// Which lifecycle states to show
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LifecycleFilter {
+    #[default]
+    ActiveOnly,
+    /// Also show folders/projects pending deletion
+    IncludeDeleteRequested,
+}
+
+impl LifecycleFilter {
+    pub fn allows(&self, state: &str) -> bool {
+        match self {
+            LifecycleFilter::ActiveOnly => state == "ACTIVE",
+            LifecycleFilter::IncludeDeleteRequested => state == "ACTIVE" || state == "DELETE_REQUESTED",
+        }
+    }
+}
+
+/// Where the tree is read from (Resource Manager v3, or a fake in tests)
+#[async_trait]
+pub trait HierarchySource: Send + Sync {
+    /// Organizations visible to the caller (`organizations.search`)
+    async fn organizations(&self) -> Result<Vec<ResourceNode>>;
+
+    /// Direct sub-folders of `parent` (`folders.list`)
+    async fn folders(&self, parent: &str, lifecycle: LifecycleFilter) -> Result<Vec<ResourceNode>>;
+
+    /// Every folder visible to the caller, wherever it is (`folders.search`)
+    async fn search_folders(&self) -> Result<Vec<ResourceNode>>;
+
+    /// Projects directly under `parent`, or every visible project when `None` (`projects.search`)
+    async fn projects(&self, parent: Option<&str>, lifecycle: LifecycleFilter) -> Result<Vec<ResourceNode>>;
+}
+
+/// Key under which the roots are cached
+const ROOT: &str = "";
+
+/// Lazily expanded tree over a [`HierarchySource`]
+pub struct HierarchyBrowser<S> {
+    source: S,
+    lifecycle: LifecycleFilter,
+    children: HashMap<String, Vec<ResourceNode>>,
+}
+
+impl<S: HierarchySource> HierarchyBrowser<S> {
+    pub fn new(source: S, lifecycle: LifecycleFilter) -> Self {
+        Self { source, lifecycle, children: HashMap::new() }
+    }
+
+    /// Organizations, then folders and projects that can't be reached from them
+    ///
+    /// With only folder- or project-level IAM (typical for client projects)
+    /// the organization above is invisible, so the topmost visible folders
+    /// and the projects whose parent is invisible become roots themselves.
+    pub async fn roots(&mut self) -> Result<&[ResourceNode]> {
+        if !self.children.contains_key(ROOT) {
+            let (mut roots, folders, projects) = tokio::try_join!(
+                self.source.organizations(),
+                self.source.search_folders(),
+                self.source.projects(None, self.lifecycle),
+            )?;
+            sort_nodes(&mut roots);
+
+            // Anything under a visible container shows up when that is expanded
+            let visible: HashSet<String> = roots.iter().chain(&folders).map(|n| n.name.clone()).collect();
+            let unreachable = |nodes: Vec<ResourceNode>| {
+                let mut nodes: Vec<ResourceNode> = nodes
+                    .into_iter()
+                    .filter(|n| self.lifecycle.allows(&n.state))
+                    .filter(|n| n.parent.as_ref().is_none_or(|p| !visible.contains(p)))
+                    .collect();
+                sort_nodes(&mut nodes);
+                nodes
+            };
+            let (top_folders, loose_projects) = (unreachable(folders), unreachable(projects));
+            roots.extend(top_folders);
+            roots.extend(loose_projects);
+            self.children.insert(ROOT.to_string(), roots);
+        }
+        Ok(&self.children[ROOT])
+    }
+
+    /// Folders then projects under `parent`, fetched on first use
+    pub async fn expand(&mut self, parent: &str) -> Result<&[ResourceNode]> {
+        validate_resource_parent(parent)?;
+        if !self.children.contains_key(parent) {
+            let (folders, projects) = tokio::try_join!(
+                self.source.folders(parent, self.lifecycle),
+                self.source.projects(Some(parent), self.lifecycle),
+            )?;
+            let mut nodes: Vec<ResourceNode> = folders.into_iter().filter(|n| self.lifecycle.allows(&n.state)).collect();
+            sort_nodes(&mut nodes);
+            let mut projects: Vec<ResourceNode> = projects.into_iter().filter(|n| self.lifecycle.allows(&n.state)).collect();
+            sort_nodes(&mut projects);
+            nodes.extend(projects);
+
+            tracing::debug!(parent = parent, children = nodes.len(), "Expanded hierarchy node");
+            self.children.insert(parent.to_string(), nodes);
+        }
+        Ok(&self.children[parent])
+    }
+
+    /// Drop cached children of `parent` (or everything with `None`)
+    pub fn refresh(&mut self, parent: Option<&str>) {
+        match parent {
+            Some(parent) => {
+                self.children.remove(parent);
+            }
+            None => self.children.clear(),
+        }
+    }
+
+    /// Depth-first walk below `root` (`None` = the roots), as (depth, node)
+    ///
+    /// `max_depth` 0 returns only the first level.
+    pub async fn subtree(&mut self, root: Option<&str>, max_depth: usize) -> Result<Vec<(usize, ResourceNode)>> {
+        let first = match root {
+            Some(parent) => self.expand(parent).await?.to_vec(),
+            None => self.roots().await?.to_vec(),
+        };
+
+        let mut out = Vec::new();
+        let mut stack: Vec<(usize, ResourceNode)> = first.into_iter().rev().map(|n| (0, n)).collect();
+        while let Some((depth, node)) = stack.pop() {
+            if node.is_container() && depth < max_depth {
+                let children = self.expand(&node.name).await?.to_vec();
+                stack.extend(children.into_iter().rev().map(|n| (depth + 1, n)));
+            }
+            out.push((depth, node));
+        }
+        Ok(out)
+    }
+}
+
+fn sort_nodes(nodes: &mut [ResourceNode]) {
+    nodes.sort_by_key(|n| n.display_name.to_lowercase());
+}
+
+/// Tree over the REST API with the credentials of `context`
+pub async fn browser_for(
+    context: &GcloudContext,
+    lifecycle: LifecycleFilter,
+) -> Result<HierarchyBrowser<ResourceManagerClient>> {
+    let auth = GcpAuthClient::for_context(context).await?;
+    Ok(HierarchyBrowser::new(ResourceManagerClient::with_auth(auth), lifecycle))
+}
+
+/// Synchronous wrapper for FFI bridge: top level of the tree
+pub fn hierarchy_roots(context: &GcloudContext, lifecycle: LifecycleFilter) -> Result<Vec<ResourceNode>> {
+    runtime::block_on(async { Ok(browser_for(context, lifecycle).await?.roots().await?.to_vec()) })
+}
+
+/// Synchronous wrapper for FFI bridge: children of one organization/folder
+pub fn hierarchy_children(context: &GcloudContext, parent: &str, lifecycle: LifecycleFilter) -> Result<Vec<ResourceNode>> {
+    runtime::block_on(async { Ok(browser_for(context, lifecycle).await?.expand(parent).await?.to_vec()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn node(kind: ResourceKind, name: &str, display: &str, parent: Option<&str>, state: &str) -> ResourceNode {
+        ResourceNode {
+            kind,
+            name: name.to_string(),
+            display_name: display.to_string(),
+            parent: parent.map(str::to_string),
+            project_id: (kind == ResourceKind::Project).then(|| display.to_string()),
+            state: state.to_string(),
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeSource {
+        nodes: Vec<ResourceNode>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl HierarchySource for FakeSource {
+        async fn organizations(&self) -> Result<Vec<ResourceNode>> {
+            Ok(self.nodes.iter().filter(|n| n.kind == ResourceKind::Organization).cloned().collect())
+        }
+
+        async fn folders(&self, parent: &str, _lifecycle: LifecycleFilter) -> Result<Vec<ResourceNode>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(self
+                .nodes
+                .iter()
+                .filter(|n| n.kind == ResourceKind::Folder && n.parent.as_deref() == Some(parent))
+                .cloned()
+                .collect())
+        }
+
+        async fn search_folders(&self) -> Result<Vec<ResourceNode>> {
+            Ok(self.nodes.iter().filter(|n| n.kind == ResourceKind::Folder).cloned().collect())
+        }
+
+        async fn projects(&self, parent: Option<&str>, _lifecycle: LifecycleFilter) -> Result<Vec<ResourceNode>> {
+            Ok(self
+                .nodes
+                .iter()
+                .filter(|n| n.kind == ResourceKind::Project && (parent.is_none() || n.parent.as_deref() == parent))
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn estate() -> FakeSource {
+        use ResourceKind::*;
+        FakeSource {
+            nodes: vec![
+                node(Organization, "organizations/1", "example.com", None, "ACTIVE"),
+                node(Folder, "folders/10", "prod", Some("organizations/1"), "ACTIVE"),
+                node(Folder, "folders/11", "old", Some("organizations/1"), "DELETE_REQUESTED"),
+                node(Project, "projects/100", "billing-prod", Some("folders/10"), "ACTIVE"),
+                node(Project, "projects/101", "api-prod", Some("folders/10"), "ACTIVE"),
+                node(Project, "projects/102", "legacy", Some("folders/10"), "DELETE_REQUESTED"),
+                node(Project, "projects/200", "sandbox", None, "ACTIVE"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tree_expands_lazily_and_filters_lifecycle() {
+        let mut browser = HierarchyBrowser::new(estate(), LifecycleFilter::ActiveOnly);
+
+        let roots: Vec<String> = browser.roots().await.unwrap().iter().map(|n| n.name.clone()).collect();
+        assert_eq!(roots, vec!["organizations/1", "projects/200"]);
+
+        let tree: Vec<(usize, String)> = browser
+            .subtree(None, 2)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(depth, n)| (depth, n.display_name))
+            .collect();
+        assert_eq!(
+            tree,
+            vec![
+                (0, "example.com".to_string()),
+                (1, "prod".to_string()),
+                (2, "api-prod".to_string()),
+                (2, "billing-prod".to_string()),
+                (0, "sandbox".to_string()),
+            ]
+        );
+
+        // Cached: expanding again does not hit the source
+        let calls = browser.source.calls.load(Ordering::SeqCst);
+        browser.expand("folders/10").await.unwrap();
+        assert_eq!(browser.source.calls.load(Ordering::SeqCst), calls);
+
+        let mut with_deleted = HierarchyBrowser::new(estate(), LifecycleFilter::IncludeDeleteRequested);
+        assert_eq!(with_deleted.expand("organizations/1").await.unwrap().len(), 2);
+        assert!(with_deleted.expand("projects/100").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_projects_under_invisible_parents_become_roots() {
+        use ResourceKind::*;
+        let mut source = estate();
+        source.nodes.extend([
+            // Client estate: a folder of their org is shared, the org itself is not
+            node(Folder, "folders/30", "shared-with-us", Some("organizations/9"), "ACTIVE"),
+            node(Project, "projects/300", "client-app", Some("folders/30"), "ACTIVE"),
+            // Project-level IAM only, under a folder we can't see
+            node(Project, "projects/400", "client-db", Some("folders/40"), "ACTIVE"),
+            node(Project, "projects/401", "client-old", Some("folders/40"), "DELETE_REQUESTED"),
+        ]);
+        let mut browser = HierarchyBrowser::new(source, LifecycleFilter::ActiveOnly);
+
+        let roots: Vec<String> = browser.roots().await.unwrap().iter().map(|n| n.display_name.clone()).collect();
+        assert_eq!(roots, vec!["example.com", "shared-with-us", "client-db", "sandbox"]);
+        let shared: Vec<String> = browser.expand("folders/30").await.unwrap().iter().map(|n| n.display_name.clone()).collect();
+        assert_eq!(shared, vec!["client-app"]);
+    }
+}
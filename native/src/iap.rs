@@ -0,0 +1,887 @@
+//! Native IAP TCP-forwarding tunnel
+//!
+//! Implements the WebSocket relay protocol spoken by
+//! `gcloud compute start-iap-tunnel` so tunnels can be opened without
+//! spawning a Python interpreter per tunnel.
+//!
+//! Wire format (all integers big-endian):
+//! - `CONNECT_SUCCESS_SID` (0x0001): tag + u32 length + session id
+//! - `RECONNECT_SUCCESS_ACK` (0x0002): tag + u64 bytes acknowledged
+//! - `DATA` (0x0004): tag + u32 length + payload (max 16 KiB)
+//! - `ACK` (0x0007): tag + u64 bytes received
+
+use anyhow::{Result, anyhow};
+use futures_util::{SinkExt, StreamExt};
+use std::net::TcpListener as StdTcpListener;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::accounts::GcloudContext;
+use crate::gcloud_client_poc::GcpAuthClient;
+use crate::runtime;
+use crate::validation::{validate_project_id, validate_zone, validate_instance_name};
+
+/// Public IAP TCP-forwarding endpoint used by gcloud
+pub const IAP_TUNNEL_ENDPOINT: &str = "wss://tunnel.cloudproxy.app/v4/connect";
+
+/// WebSocket subprotocol negotiated with the IAP relay
+pub const IAP_SUBPROTOCOL: &str = "relay.tunnel.cloudproxy.app";
+
+const TAG_CONNECT_SUCCESS_SID: u16 = 0x0001;
+const TAG_RECONNECT_SUCCESS_ACK: u16 = 0x0002;
+const TAG_DATA: u16 = 0x0004;
+const TAG_ACK: u16 = 0x0007;
+
+/// Maximum payload of a single DATA frame
+pub const MAX_DATA_FRAME_SIZE: usize = 16 * 1024;
+
+/// How long to wait for the relay to confirm the backend connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Instance endpoint reached through IAP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IapTarget {
+    pub project: String,
+    pub zone: String,
+    pub instance: String,
+    pub interface: String,
+    pub port: u16,
+}
+
+impl IapTarget {
+    /// Build a validated target on the primary network interface (nic0)
+    pub fn new(project: &str, zone: &str, instance: &str, port: u16) -> Result<Self> {
+        // SECURITY: Validate all inputs before they end up in the relay URL
+        validate_project_id(project)?;
+        validate_zone(zone)?;
+        validate_instance_name(instance)?;
+
+        Ok(Self {
+            project: project.to_string(),
+            zone: zone.to_string(),
+            instance: instance.to_string(),
+            interface: "nic0".to_string(),
+            port,
+        })
+    }
+
+    /// Relay URL for this target against the given endpoint
+    fn connect_url(&self, endpoint: &str) -> String {
+        format!(
+            "{}?project={}&zone={}&instance={}&interface={}&port={}&newWebsocket=true",
+            endpoint, self.project, self.zone, self.instance, self.interface, self.port
+        )
+    }
+}
+
+/// A single relay subprotocol frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IapFrame {
+    ConnectSuccessSid(Vec<u8>),
+    ReconnectSuccessAck(u64),
+    Data(Vec<u8>),
+    Ack(u64),
+}
+
+impl IapFrame {
+    /// Serialize the frame into a WebSocket binary payload
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            IapFrame::ConnectSuccessSid(sid) => encode_with_len(TAG_CONNECT_SUCCESS_SID, sid),
+            IapFrame::Data(data) => encode_with_len(TAG_DATA, data),
+            IapFrame::ReconnectSuccessAck(ack) => encode_with_u64(TAG_RECONNECT_SUCCESS_ACK, *ack),
+            IapFrame::Ack(ack) => encode_with_u64(TAG_ACK, *ack),
+        }
+    }
+
+    /// Parse a WebSocket binary payload into a frame
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 2 {
+            return Err(anyhow!("IAP frame too short ({} bytes)", buf.len()));
+        }
+        let tag = u16::from_be_bytes([buf[0], buf[1]]);
+        let body = &buf[2..];
+
+        match tag {
+            TAG_CONNECT_SUCCESS_SID => Ok(IapFrame::ConnectSuccessSid(decode_len_prefixed(body)?)),
+            TAG_DATA => Ok(IapFrame::Data(decode_len_prefixed(body)?)),
+            TAG_RECONNECT_SUCCESS_ACK => Ok(IapFrame::ReconnectSuccessAck(decode_u64(body)?)),
+            TAG_ACK => Ok(IapFrame::Ack(decode_u64(body)?)),
+            other => Err(anyhow!("Unknown IAP frame tag 0x{:04x}", other)),
+        }
+    }
+}
+
+fn encode_with_len(tag: u16, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(6 + payload.len());
+    buf.extend_from_slice(&tag.to_be_bytes());
+    buf.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    buf.extend_from_slice(payload);
+    buf
+}
+
+fn encode_with_u64(tag: u16, value: u64) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(10);
+    buf.extend_from_slice(&tag.to_be_bytes());
+    buf.extend_from_slice(&value.to_be_bytes());
+    buf
+}
+
+fn decode_len_prefixed(body: &[u8]) -> Result<Vec<u8>> {
+    if body.len() < 4 {
+        return Err(anyhow!("IAP frame missing length prefix"));
+    }
+    let len = u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize;
+    let payload = &body[4..];
+    if payload.len() < len {
+        return Err(anyhow!(
+            "IAP frame truncated: expected {} bytes, got {}",
+            len,
+            payload.len()
+        ));
+    }
+    Ok(payload[..len].to_vec())
+}
+
+fn decode_u64(body: &[u8]) -> Result<u64> {
+    let bytes: [u8; 8] = body
+        .get(..8)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| anyhow!("IAP ack frame truncated"))?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Human readable description of IAP relay close codes
+fn describe_close_code(code: u16) -> &'static str {
+    match code {
+        4001 => "invalid relay request",
+        4003 => "failed to connect to backend (is the instance running and the firewall open for 35.235.240.0/20?)",
+        4004 => "relay session not found",
+        4033 => "not authorized (missing roles/iap.tunnelResourceAccessor?)",
+        4047 => "instance not found",
+        4080 => "connection to backend timed out",
+        _ => "connection closed by IAP relay",
+    }
+}
+
+/// Where the bearer token for the relay comes from
+#[derive(Clone)]
+pub enum TokenSource {
+    /// Application Default Credentials through `GcpAuthClient`
+    Adc(Arc<GcpAuthClient>),
+    /// Fixed token (used by tests and local stand-in relays)
+    Static(String),
+}
+
+impl TokenSource {
+    async fn token(&self) -> Result<String> {
+        match self {
+            TokenSource::Adc(auth) => auth.get_access_token().await,
+            TokenSource::Static(token) => Ok(token.clone()),
+        }
+    }
+}
+
+/// Opens relay connections to the IAP tunnel endpoint
+#[derive(Clone)]
+pub struct IapConnector {
+    endpoint: String,
+    token_source: TokenSource,
+}
+
+impl IapConnector {
+    /// Connector against the public IAP endpoint using gcloud ADC
+    pub async fn new() -> Result<Self> {
+        let auth = GcpAuthClient::new().await?;
+        Ok(Self {
+            endpoint: IAP_TUNNEL_ENDPOINT.to_string(),
+            token_source: TokenSource::Adc(Arc::new(auth)),
+        })
+    }
+
+    /// Connector using the credentials of a gcloud account/configuration
+    pub async fn for_context(ctx: &GcloudContext) -> Result<Self> {
+        let auth = GcpAuthClient::for_context(ctx).await?;
+        Ok(Self {
+            endpoint: IAP_TUNNEL_ENDPOINT.to_string(),
+            token_source: TokenSource::Adc(Arc::new(auth)),
+        })
+    }
+
+    /// Connector against a custom endpoint (e.g. a local stand-in relay)
+    pub fn with_endpoint(endpoint: &str, token_source: TokenSource) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            token_source,
+        }
+    }
+
+    /// Open a relay WebSocket and wait for the backend connection to be confirmed
+    pub async fn connect(&self, target: &IapTarget) -> Result<IapConnection> {
+        let token = self.token_source.token().await?;
+        let url = target.connect_url(&self.endpoint);
+
+        let mut request = url
+            .into_client_request()
+            .map_err(|e| anyhow!("Invalid IAP relay URL: {}", e))?;
+        let headers = request.headers_mut();
+        headers.insert("Sec-WebSocket-Protocol", HeaderValue::from_static(IAP_SUBPROTOCOL));
+        headers.insert("Origin", HeaderValue::from_static("bot:iap-tunneler"));
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|_| anyhow!("Access token contains invalid characters"))?,
+        );
+
+        tracing::debug!(
+            instance = %target.instance,
+            port = target.port,
+            "Opening IAP relay WebSocket"
+        );
+
+        let (mut ws, _response) = tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(|e| anyhow!("Failed to open IAP relay WebSocket: {}", e))?;
+
+        // The relay answers with CONNECT_SUCCESS_SID once the backend socket is open
+        let sid = tokio::time::timeout(CONNECT_TIMEOUT, async {
+            while let Some(msg) = ws.next().await {
+                match msg.map_err(|e| anyhow!("IAP relay error: {}", e))? {
+                    Message::Binary(buf) => match IapFrame::decode(&buf)? {
+                        IapFrame::ConnectSuccessSid(sid) => return Ok(sid),
+                        other => tracing::debug!(frame = ?other, "Ignoring frame before connect"),
+                    },
+                    Message::Close(frame) => {
+                        let code = frame.as_ref().map(|f| u16::from(f.code)).unwrap_or(0);
+                        return Err(anyhow!(
+                            "IAP relay refused connection ({}): {}",
+                            code,
+                            describe_close_code(code)
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            Err(anyhow!("IAP relay closed before confirming the connection"))
+        })
+        .await
+        .map_err(|_| anyhow!("Timeout waiting for IAP relay to connect to the instance"))??;
+
+        tracing::debug!(
+            instance = %target.instance,
+            sid_len = sid.len(),
+            "IAP relay connected"
+        );
+
+        Ok(IapConnection { ws, activity: None, half_close: false })
+    }
+}
+
+type RelaySocket = tokio_tungstenite::WebSocketStream<
+    tokio_tungstenite::MaybeTlsStream<TcpStream>,
+>;
+
+/// An established relay session with the instance
+pub struct IapConnection {
+    ws: RelaySocket,
+    activity: Option<Arc<TunnelActivity>>,
+    /// Keep reading from the instance after local EOF (see [`IapConnection::with_half_close`])
+    half_close: bool,
+}
+
+/// Byte counters for a finished relay session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+/// Live traffic counters of one tunnel, updated while sessions relay
+///
+/// Shared with whoever watches the tunnel (idle policy, UI).
+#[derive(Debug)]
+pub struct TunnelActivity {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    active_connections: AtomicU64,
+    total_connections: AtomicU64,
+    /// Unix milliseconds of the last byte or connection
+    last_activity_ms: AtomicU64,
+    started_ms: AtomicU64,
+    /// IAP sessions established, and how long the handshakes took
+    connects: AtomicU64,
+    connect_failures: AtomicU64,
+    connect_latency_total_ms: AtomicU64,
+    connect_latency_max_ms: AtomicU64,
+    connect_latency_last_ms: AtomicU64,
+}
+
+/// Point-in-time copy of [`TunnelActivity`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ActivitySnapshot {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub active_connections: u64,
+    pub total_connections: u64,
+    pub last_activity_unix_ms: u64,
+    /// When counting started (tunnel creation)
+    pub started_unix_ms: u64,
+    /// IAP sessions opened for clients; not known for gcloud tunnels
+    pub connects: u64,
+    pub connect_failures: u64,
+    pub connect_latency_total_ms: u64,
+    pub connect_latency_max_ms: u64,
+    pub connect_latency_last_ms: u64,
+}
+
+impl ActivitySnapshot {
+    pub fn last_activity(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.last_activity_unix_ms)
+    }
+
+    /// Mean time to open an IAP session, if any was opened
+    pub fn connect_latency_avg_ms(&self) -> Option<f64> {
+        (self.connects > 0).then(|| self.connect_latency_total_ms as f64 / self.connects as f64)
+    }
+}
+
+fn unix_ms_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+impl Default for TunnelActivity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TunnelActivity {
+    /// Counters start at zero, with the tunnel creation as last activity
+    pub fn new() -> Self {
+        Self {
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            active_connections: AtomicU64::new(0),
+            total_connections: AtomicU64::new(0),
+            last_activity_ms: AtomicU64::new(unix_ms_now()),
+            started_ms: AtomicU64::new(unix_ms_now()),
+            connects: AtomicU64::new(0),
+            connect_failures: AtomicU64::new(0),
+            connect_latency_total_ms: AtomicU64::new(0),
+            connect_latency_max_ms: AtomicU64::new(0),
+            connect_latency_last_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn touch(&self) {
+        self.last_activity_ms.store(unix_ms_now(), Ordering::Relaxed);
+    }
+
+    pub fn connection_opened(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub fn connection_closed(&self) {
+        let _ = self.active_connections.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        self.touch();
+    }
+
+    pub fn record_sent(&self, bytes: u64) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub fn record_received(&self, bytes: u64) {
+        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+        self.touch();
+    }
+
+    /// An IAP session was established after `latency`
+    pub fn record_connect(&self, latency: Duration) {
+        let ms = latency.as_millis() as u64;
+        self.connects.fetch_add(1, Ordering::Relaxed);
+        self.connect_latency_total_ms.fetch_add(ms, Ordering::Relaxed);
+        self.connect_latency_max_ms.fetch_max(ms, Ordering::Relaxed);
+        self.connect_latency_last_ms.store(ms, Ordering::Relaxed);
+    }
+
+    pub fn record_connect_failure(&self) {
+        self.connect_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// For tunnels whose connections are counted from the outside (gcloud)
+    pub fn set_active_connections(&self, count: u64) {
+        let previous = self.active_connections.swap(count, Ordering::Relaxed);
+        if count > previous {
+            self.total_connections.fetch_add(count - previous, Ordering::Relaxed);
+        }
+        if count > 0 || previous > 0 {
+            self.touch();
+        }
+    }
+
+    pub fn snapshot(&self) -> ActivitySnapshot {
+        ActivitySnapshot {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            total_connections: self.total_connections.load(Ordering::Relaxed),
+            last_activity_unix_ms: self.last_activity_ms.load(Ordering::Relaxed),
+            started_unix_ms: self.started_ms.load(Ordering::Relaxed),
+            connects: self.connects.load(Ordering::Relaxed),
+            connect_failures: self.connect_failures.load(Ordering::Relaxed),
+            connect_latency_total_ms: self.connect_latency_total_ms.load(Ordering::Relaxed),
+            connect_latency_max_ms: self.connect_latency_max_ms.load(Ordering::Relaxed),
+            connect_latency_last_ms: self.connect_latency_last_ms.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl IapConnection {
+    /// Count the traffic of this session in `activity`
+    pub fn with_activity(mut self, activity: Arc<TunnelActivity>) -> Self {
+        self.activity = Some(activity);
+        self
+    }
+
+    /// Keep the session open after local EOF until the instance closes it
+    ///
+    /// For stdio proxies (`ProxyCommand`), where stdin may end before the
+    /// last response arrives. IAP has no half-close frame, so without this a
+    /// local EOF ends the session, as it does in gcloud.
+    pub fn with_half_close(mut self) -> Self {
+        self.half_close = true;
+        self
+    }
+
+    /// Relay a bidirectional stream (e.g. an accepted TCP client) over the tunnel
+    pub async fn relay<S>(self, stream: S) -> Result<RelayStats>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        self.relay_split(reader, writer).await
+    }
+
+    /// Relay separate read and write halves over the tunnel
+    ///
+    /// Local EOF closes the session, unless [`IapConnection::with_half_close`]
+    /// was used: then it stops the upload side only and data from the
+    /// instance keeps flowing until the relay closes. `writer` is shut down
+    /// either way.
+    pub async fn relay_split<R, W>(self, mut reader: R, mut writer: W) -> Result<RelayStats>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let activity = self.activity;
+        let half_close = self.half_close;
+        let (mut sink, mut stream) = self.ws.split();
+        let mut stats = RelayStats::default();
+        let mut unacked: u64 = 0;
+        let mut local_open = true;
+        let mut buf = vec![0u8; MAX_DATA_FRAME_SIZE];
+
+        loop {
+            tokio::select! {
+                read = reader.read(&mut buf), if local_open => {
+                    let n = read.map_err(|e| anyhow!("Local read failed: {}", e))?;
+                    if n == 0 {
+                        if !half_close {
+                            tracing::debug!("Local side reached EOF, closing the session");
+                            break;
+                        }
+                        tracing::debug!("Local side reached EOF, draining remote data");
+                        local_open = false;
+                        continue;
+                    }
+                    let frame = IapFrame::Data(buf[..n].to_vec()).encode();
+                    sink.send(Message::Binary(frame))
+                        .await
+                        .map_err(|e| anyhow!("Failed to send data to IAP relay: {}", e))?;
+                    stats.bytes_sent += n as u64;
+                    if let Some(activity) = &activity {
+                        activity.record_sent(n as u64);
+                    }
+                }
+                msg = stream.next() => {
+                    let msg = match msg {
+                        Some(msg) => msg.map_err(|e| anyhow!("IAP relay error: {}", e))?,
+                        None => break,
+                    };
+                    match msg {
+                        Message::Binary(data) => match IapFrame::decode(&data)? {
+                            IapFrame::Data(payload) => {
+                                if let Some(activity) = &activity {
+                                    activity.record_received(payload.len() as u64);
+                                }
+                                writer.write_all(&payload)
+                                    .await
+                                    .map_err(|e| anyhow!("Local write failed: {}", e))?;
+                                stats.bytes_received += payload.len() as u64;
+                                unacked += payload.len() as u64;
+
+                                // Acknowledge in batches, like gcloud does
+                                if unacked >= 2 * MAX_DATA_FRAME_SIZE as u64 {
+                                    sink.send(Message::Binary(IapFrame::Ack(stats.bytes_received).encode()))
+                                        .await
+                                        .map_err(|e| anyhow!("Failed to send ack to IAP relay: {}", e))?;
+                                    unacked = 0;
+                                }
+                            }
+                            IapFrame::Ack(_) | IapFrame::ReconnectSuccessAck(_) => {}
+                            IapFrame::ConnectSuccessSid(_) => {
+                                tracing::debug!("Unexpected CONNECT_SUCCESS_SID mid-session");
+                            }
+                        },
+                        Message::Close(frame) => {
+                            if let Some(frame) = frame {
+                                if frame.code != CloseCode::Normal {
+                                    let code = u16::from(frame.code);
+                                    tracing::warn!(
+                                        code = code,
+                                        reason = %describe_close_code(code),
+                                        "IAP relay closed the session"
+                                    );
+                                }
+                            }
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let _ = writer.flush().await;
+        let _ = writer.shutdown().await;
+        let _ = sink.close().await;
+
+        Ok(stats)
+    }
+}
+
+/// Handle to a running native tunnel listener thread
+pub struct NativeTunnelHandle {
+    pub local_port: u16,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    running: Arc<AtomicBool>,
+}
+
+impl NativeTunnelHandle {
+    /// Whether the accept loop is still running
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Stop accepting connections and wait for the listener thread to exit
+    pub fn stop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for NativeTunnelHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Serve `listener` by relaying every accepted connection to `target`
+///
+/// The listener is already bound by the caller, so there is no window in
+/// which another process can grab the port. Connections and bytes are
+/// counted in `activity`.
+pub fn spawn_native_listener(
+    connector: IapConnector,
+    target: IapTarget,
+    listener: StdTcpListener,
+    activity: Arc<TunnelActivity>,
+) -> Result<NativeTunnelHandle> {
+    let local_port = listener.local_addr()?.port();
+    listener.set_nonblocking(true)?;
+
+    let (shutdown_tx, mut shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let running = Arc::new(AtomicBool::new(true));
+    let running_flag = running.clone();
+
+    let thread = std::thread::Builder::new()
+        .name(format!("iap-{}-{}", target.instance, target.port))
+        .spawn(move || {
+            // The connector's token cache refreshes over the shared HTTP client
+            let rt = match runtime::runtime() {
+                Ok(rt) => rt,
+                Err(e) => {
+                    tracing::error!(error = %e, "Tunnel listener has no runtime");
+                    running_flag.store(false, Ordering::SeqCst);
+                    return;
+                }
+            };
+
+            rt.block_on(async move {
+                let listener = match TcpListener::from_std(listener) {
+                    Ok(l) => l,
+                    Err(e) => {
+                        tracing::error!(error = %e, "Failed to register tunnel listener");
+                        return;
+                    }
+                };
+
+                // Relays end with the listener instead of outliving it on the shared runtime
+                let mut sessions = JoinSet::new();
+                loop {
+                    tokio::select! {
+                        _ = &mut shutdown_rx => break,
+                        Some(_) = sessions.join_next(), if !sessions.is_empty() => {}
+                        accepted = listener.accept() => {
+                            let (socket, peer) = match accepted {
+                                Ok(a) => a,
+                                Err(e) => {
+                                    tracing::warn!(error = %e, "Tunnel accept failed");
+                                    continue;
+                                }
+                            };
+                            let connector = connector.clone();
+                            let target = target.clone();
+                            let activity = activity.clone();
+                            sessions.spawn(async move {
+                                tracing::debug!(peer = %peer, instance = %target.instance, "Tunnel client connected");
+                                activity.connection_opened();
+                                let connect_start = Instant::now();
+                                let result = match connector.connect(&target).await {
+                                    Ok(conn) => {
+                                        activity.record_connect(connect_start.elapsed());
+                                        conn.with_activity(activity.clone()).relay(socket).await
+                                    }
+                                    Err(e) => {
+                                        activity.record_connect_failure();
+                                        Err(e)
+                                    }
+                                };
+                                activity.connection_closed();
+                                match result {
+                                    Ok(stats) => tracing::debug!(
+                                        peer = %peer,
+                                        bytes_sent = stats.bytes_sent,
+                                        bytes_received = stats.bytes_received,
+                                        "Tunnel client disconnected"
+                                    ),
+                                    Err(e) => tracing::warn!(
+                                        peer = %peer,
+                                        instance = %target.instance,
+                                        error = %e,
+                                        "Tunnel relay failed"
+                                    ),
+                                }
+                            });
+                        }
+                    }
+                }
+            });
+
+            running_flag.store(false, Ordering::SeqCst);
+        })
+        .map_err(|e| anyhow!("Failed to spawn tunnel thread: {}", e))?;
+
+    Ok(NativeTunnelHandle {
+        local_port,
+        shutdown: Some(shutdown_tx),
+        thread: Some(thread),
+        running,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let frames = vec![
+            IapFrame::ConnectSuccessSid(b"session-1".to_vec()),
+            IapFrame::ReconnectSuccessAck(42),
+            IapFrame::Data(b"hello".to_vec()),
+            IapFrame::Ack(1 << 40),
+        ];
+        for frame in frames {
+            assert_eq!(IapFrame::decode(&frame.encode()).unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn test_frame_wire_format() {
+        assert_eq!(
+            IapFrame::Data(vec![0xaa]).encode(),
+            vec![0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0xaa]
+        );
+        assert_eq!(
+            IapFrame::Ack(1).encode(),
+            vec![0x00, 0x07, 0, 0, 0, 0, 0, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_frames() {
+        assert!(IapFrame::decode(&[]).is_err());
+        assert!(IapFrame::decode(&[0x00, 0x04, 0x00, 0x00, 0x00, 0x05, 0x01]).is_err());
+        assert!(IapFrame::decode(&[0x00, 0x07, 0x01]).is_err());
+        assert!(IapFrame::decode(&[0x12, 0x34]).is_err());
+    }
+
+    #[test]
+    fn test_target_validation() {
+        assert!(IapTarget::new("my-project", "us-central1-a", "vm-1", 22).is_ok());
+        assert!(IapTarget::new("my-project", "us-central1-a", "vm;rm", 22).is_err());
+    }
+
+    /// Local stand-in for the IAP relay: checks the handshake, confirms the
+    /// connection and echoes every DATA frame back, closing the session after
+    /// echoing a `close` payload. Reports every Close received from a client.
+    #[allow(clippy::result_large_err)]
+    async fn spawn_echo_relay() -> (String, tokio::sync::mpsc::UnboundedReceiver<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (closed_tx, closed_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let closed_tx = closed_tx.clone();
+                tokio::spawn(async move {
+                    let callback = |req: &Request, mut resp: Response| {
+                        assert_eq!(req.headers()["Authorization"], "Bearer test-token");
+                        assert!(req.uri().query().unwrap().contains("instance=vm-1"));
+                        resp.headers_mut()
+                            .insert("Sec-WebSocket-Protocol", HeaderValue::from_static(IAP_SUBPROTOCOL));
+                        Ok(resp)
+                    };
+                    let mut ws = tokio_tungstenite::accept_hdr_async(socket, callback).await.unwrap();
+                    ws.send(Message::Binary(IapFrame::ConnectSuccessSid(b"sid".to_vec()).encode()))
+                        .await
+                        .unwrap();
+                    while let Some(Ok(msg)) = ws.next().await {
+                        if let Message::Close(_) = msg {
+                            let _ = closed_tx.send(());
+                        }
+                        if let Message::Binary(buf) = msg {
+                            if let IapFrame::Data(data) = IapFrame::decode(&buf).unwrap() {
+                                let last = data == b"close";
+                                ws.send(Message::Binary(IapFrame::Data(data).encode())).await.unwrap();
+                                if last {
+                                    let _ = ws.close(None).await;
+                                }
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        (format!("ws://{}/v4/connect", addr), closed_rx)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_native_listener_relays_through_stand_in() {
+        let (endpoint, _closed) = spawn_echo_relay().await;
+        let connector = IapConnector::with_endpoint(&endpoint, TokenSource::Static("test-token".to_string()));
+        let target = IapTarget::new("my-project", "us-central1-a", "vm-1", 22).unwrap();
+
+        let listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+        let activity = Arc::new(TunnelActivity::new());
+        let mut handle = spawn_native_listener(connector, target, listener, activity.clone()).unwrap();
+        assert!(handle.is_running());
+
+        let mut client = TcpStream::connect(("127.0.0.1", handle.local_port)).await.unwrap();
+        client.write_all(b"ping through iap").await.unwrap();
+
+        let mut buf = [0u8; 16];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping through iap");
+
+        let snapshot = activity.snapshot();
+        assert_eq!(snapshot.active_connections, 1);
+        assert_eq!(snapshot.bytes_sent, 16);
+        assert_eq!(snapshot.bytes_received, 16);
+        assert_eq!((snapshot.connects, snapshot.connect_failures), (1, 0));
+        assert!(snapshot.connect_latency_avg_ms().is_some());
+
+        drop(client);
+        tokio::task::spawn_blocking(move || {
+            handle.stop();
+            assert!(!handle.is_running());
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_native_listener_closes_session_when_client_leaves() {
+        let (endpoint, mut closed) = spawn_echo_relay().await;
+        let connector = IapConnector::with_endpoint(&endpoint, TokenSource::Static("test-token".to_string()));
+        let target = IapTarget::new("my-project", "us-central1-a", "vm-1", 22).unwrap();
+
+        let listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+        let activity = Arc::new(TunnelActivity::new());
+        let mut handle = spawn_native_listener(connector, target, listener, activity.clone()).unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", handle.local_port)).await.unwrap();
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(activity.snapshot().active_connections, 1);
+
+        // The instance never closes; the client leaving must end the session
+        drop(client);
+        tokio::time::timeout(Duration::from_secs(5), closed.recv())
+            .await
+            .expect("stand-in relay never received a Close")
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while activity.snapshot().active_connections != 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(activity.snapshot().active_connections, 0);
+
+        tokio::task::spawn_blocking(move || handle.stop()).await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_relay_half_close_drains_remote_data() {
+        let (endpoint, _closed) = spawn_echo_relay().await;
+        let connector = IapConnector::with_endpoint(&endpoint, TokenSource::Static("test-token".to_string()));
+        let target = IapTarget::new("my-project", "us-central1-a", "vm-1", 22).unwrap();
+        let conn = connector.connect(&target).await.unwrap().with_half_close();
+
+        // Local input: one payload, then EOF (like ssh closing stdin)
+        let (mut input, relay_in) = tokio::io::duplex(64);
+        let (relay_out, mut output) = tokio::io::duplex(64);
+        input.write_all(b"close").await.unwrap();
+        drop(input);
+
+        let stats = conn.relay_split(relay_in, relay_out).await.unwrap();
+        assert_eq!(stats.bytes_sent, 5);
+        assert_eq!(stats.bytes_received, 5);
+
+        // Remote data arrives after local EOF, followed by EOF on our side
+        let mut received = Vec::new();
+        output.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"close");
+    }
+}
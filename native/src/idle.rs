@@ -0,0 +1,438 @@
+//! Idle auto-stop
+//!
+//! Dev VMs reached through the connector are often left running overnight.
+//! The idle monitor watches the tunnels of `tunnel.rs`: once an instance has
+//! had no connection and no traffic for the configured period it emits a
+//! warning, and if nothing happens during the warning window it stops the
+//! VM and closes its tunnels.
+//!
+//! The idle clock starts at the later of the last tunnel activity and the
+//! instance's last start, so a VM that was just booted gets a full period.
+//! Instances and labels can get their own period or be exempted, and a
+//! pending stop can be snoozed.
+
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::accounts::GcloudContext;
+use crate::backend::{backend_for, ApiMethod, CloudBackend};
+use crate::bulk::InstanceRef;
+use crate::runtime;
+use crate::tunnel;
+use crate::worker::{EventBus, StopSignal, Worker};
+
+/// Which instances an override applies to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IdleSelector {
+    Instance(InstanceRef),
+    Label { key: String, value: String },
+}
+
+impl IdleSelector {
+    fn matches(&self, target: &InstanceRef, labels: &BTreeMap<String, String>) -> bool {
+        match self {
+            IdleSelector::Instance(instance) => instance == target,
+            IdleSelector::Label { key, value } => labels.get(key) == Some(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdleOverride {
+    pub selector: IdleSelector,
+    /// Idle period for matching instances; `None` exempts them
+    pub idle_after: Option<Duration>,
+}
+
+/// When idle instances are warned about and stopped
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdlePolicy {
+    /// No connection and no traffic for this long counts as idle
+    pub idle_after: Duration,
+    /// Warning lead time before the stop
+    pub warn_before: Duration,
+    /// How often tunnels and instances are checked
+    pub check_interval: Duration,
+    /// Instance overrides win over label overrides; among labels the first match wins
+    #[serde(default)]
+    pub overrides: Vec<IdleOverride>,
+}
+
+impl Default for IdlePolicy {
+    fn default() -> Self {
+        Self {
+            idle_after: Duration::from_secs(60 * 60),
+            warn_before: Duration::from_secs(10 * 60),
+            check_interval: Duration::from_secs(60),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl IdlePolicy {
+    /// Idle period that applies to an instance, `None` if it is exempt
+    pub fn idle_after_for(&self, target: &InstanceRef, labels: &BTreeMap<String, String>) -> Option<Duration> {
+        let instance_rule = self
+            .overrides
+            .iter()
+            .find(|o| matches!(o.selector, IdleSelector::Instance(_)) && o.selector.matches(target, labels));
+        let label_rule = || {
+            self.overrides
+                .iter()
+                .find(|o| matches!(o.selector, IdleSelector::Label { .. }) && o.selector.matches(target, labels))
+        };
+        match instance_rule.or_else(label_rule) {
+            Some(rule) => rule.idle_after,
+            None => Some(self.idle_after),
+        }
+    }
+}
+
+/// What the monitor knows about one instance at a check
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceObservation {
+    pub target: InstanceRef,
+    pub status: String,
+    pub labels: BTreeMap<String, String>,
+    /// `lastStartTimestamp` of the instance
+    pub started_at: Option<SystemTime>,
+    /// Open connections over all tunnels to the instance
+    pub active_connections: u64,
+    /// Last byte or connection over any tunnel to the instance
+    pub last_activity: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdleDecision {
+    Warn { target: InstanceRef, idle_for: Duration, stop_in: Duration },
+    Stop { target: InstanceRef, idle_for: Duration },
+}
+
+/// Warning and snooze state between checks
+#[derive(Debug, Default)]
+pub struct IdleTracker {
+    warned_at: HashMap<InstanceRef, SystemTime>,
+    snoozed_until: HashMap<InstanceRef, SystemTime>,
+}
+
+impl IdleTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Postpone the stop of `target` until at least `until`
+    ///
+    /// A warning already given is withdrawn; a new one comes before the stop.
+    pub fn snooze(&mut self, target: &InstanceRef, until: SystemTime) {
+        self.warned_at.remove(target);
+        self.snoozed_until.insert(target.clone(), until);
+    }
+
+    /// Decide what to do with every observed instance at time `now`
+    ///
+    /// A stop is always preceded by a warning at least `warn_before` earlier,
+    /// even if the instance was already idle for longer when first seen.
+    pub fn evaluate(&mut self, policy: &IdlePolicy, now: SystemTime, observations: &[InstanceObservation]) -> Vec<IdleDecision> {
+        self.snoozed_until.retain(|_, until| *until > now);
+
+        let mut decisions = Vec::new();
+        for obs in observations {
+            let target = &obs.target;
+            let idle_after = match policy.idle_after_for(target, &obs.labels) {
+                Some(idle_after) if obs.status == "RUNNING" && obs.active_connections == 0 => idle_after,
+                _ => {
+                    self.warned_at.remove(target);
+                    continue;
+                }
+            };
+
+            let idle_since = obs.last_activity.max(obs.started_at).unwrap_or(now);
+            let idle_for = now.duration_since(idle_since).unwrap_or_default();
+            let mut deadline = idle_since + idle_after;
+            if let Some(until) = self.snoozed_until.get(target) {
+                deadline = deadline.max(*until);
+            }
+
+            match self.warned_at.get(target) {
+                Some(warned_at) if now >= deadline.max(*warned_at + policy.warn_before) => {
+                    self.warned_at.remove(target);
+                    decisions.push(IdleDecision::Stop { target: target.clone(), idle_for });
+                }
+                Some(_) => {}
+                None if now + policy.warn_before >= deadline => {
+                    let stop_in = deadline.duration_since(now).unwrap_or_default().max(policy.warn_before);
+                    self.warned_at.insert(target.clone(), now);
+                    decisions.push(IdleDecision::Warn { target: target.clone(), idle_for, stop_in });
+                }
+                None => {}
+            }
+        }
+
+        // Forget instances that are no longer observed
+        self.warned_at.retain(|target, _| observations.iter().any(|o| &o.target == target));
+        decisions
+    }
+}
+
+/// Events emitted by the idle monitor
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IdleEvent {
+    Warning { target: InstanceRef, idle_for_secs: u64, stop_in_secs: u64 },
+    Snoozed { target: InstanceRef, snooze_secs: u64 },
+    Stopped { target: InstanceRef, idle_for_secs: u64, tunnels_closed: usize },
+    StopFailed { target: InstanceRef, error: String },
+}
+
+lazy_static! {
+    /// State: the tracker, shared with [`snooze_idle_stop`]
+    static ref MONITOR: Worker<Arc<Mutex<IdleTracker>>> = Worker::new("idle-monitor");
+    static ref EVENTS: EventBus<IdleEvent> = EventBus::new("Idle monitor event");
+}
+
+/// Receive idle monitor events; the channel closes when the receiver is dropped
+pub fn subscribe_idle_events() -> Receiver<IdleEvent> {
+    EVENTS.subscribe()
+}
+
+fn emit(event: IdleEvent) {
+    EVENTS.emit(event);
+}
+
+/// Start the background idle monitor (no-op if it is already running)
+///
+/// Instances are described and stopped with `method` as `context`.
+pub fn start_idle_monitor(method: ApiMethod, context: GcloudContext, policy: IdlePolicy) -> Result<()> {
+    context.validate()?;
+    let tracker = Arc::new(Mutex::new(IdleTracker::new()));
+    let thread_tracker = tracker.clone();
+    MONITOR.start(tracker, move |stop| run(method, context, policy, thread_tracker, stop))?;
+    Ok(())
+}
+
+/// Stop the idle monitor and wait for it to exit
+pub fn stop_idle_monitor() -> Result<()> {
+    MONITOR.stop()
+}
+
+/// Whether the idle monitor thread is running
+pub fn is_idle_monitor_running() -> bool {
+    MONITOR.is_running()
+}
+
+/// Keep an instance running for at least `duration` more
+pub fn snooze_idle_stop(target: InstanceRef, duration: Duration) -> Result<()> {
+    let tracker = MONITOR.with_state(Arc::clone).ok_or_else(|| anyhow!("Idle monitor is not running"))?;
+    tracker
+        .lock()
+        .map_err(|_| anyhow!("Idle tracker lock poisoned"))?
+        .snooze(&target, SystemTime::now() + duration);
+    emit(IdleEvent::Snoozed { target, snooze_secs: duration.as_secs() });
+    Ok(())
+}
+
+fn parse_timestamp(timestamp: Option<&str>) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc3339(timestamp?).ok().map(SystemTime::from)
+}
+
+/// Instances with (or recently with) a tunnel, and their tunnel activity
+///
+/// `last_seen` keeps the activity of instances whose tunnels were closed, so
+/// closing the tunnel does not reset the idle clock.
+fn observe_tunnels(last_seen: &mut HashMap<InstanceRef, SystemTime>) -> HashMap<InstanceRef, u64> {
+    let reports = match tunnel::tunnel_activity() {
+        Ok(reports) => reports,
+        Err(e) => {
+            tracing::error!(error = %e, "Idle monitor could not inspect tunnels");
+            return HashMap::new();
+        }
+    };
+
+    let mut connections = HashMap::new();
+    for report in reports {
+        let target = InstanceRef { project: report.key.project, zone: report.key.zone, instance: report.key.instance };
+        *connections.entry(target.clone()).or_insert(0) += report.activity.active_connections;
+        let last = last_seen.entry(target).or_insert(report.activity.last_activity());
+        *last = (*last).max(report.activity.last_activity());
+    }
+    connections
+}
+
+async fn check(
+    backend: &dyn CloudBackend,
+    policy: &IdlePolicy,
+    tracker: &Mutex<IdleTracker>,
+    last_seen: &mut HashMap<InstanceRef, SystemTime>,
+) {
+    let connections = observe_tunnels(last_seen);
+
+    let mut observations = Vec::new();
+    for (target, last_activity) in last_seen.iter() {
+        match backend.describe_instance_details(&target.project, &target.zone, &target.instance).await {
+            Ok(details) => observations.push(InstanceObservation {
+                target: target.clone(),
+                status: details.status,
+                labels: details.labels,
+                started_at: parse_timestamp(details.last_start_timestamp.as_deref()),
+                active_connections: connections.get(target).copied().unwrap_or(0),
+                last_activity: Some(*last_activity),
+            }),
+            Err(e) => tracing::warn!(instance = %target.instance, error = %e, "Idle monitor could not describe instance"),
+        }
+    }
+    // Stopped elsewhere and no tunnel left: nothing more to watch
+    last_seen.retain(|target, _| {
+        connections.contains_key(target)
+            || observations.iter().any(|o| &o.target == target && o.status == "RUNNING")
+            || !observations.iter().any(|o| &o.target == target)
+    });
+
+    let decisions = match tracker.lock() {
+        Ok(mut tracker) => tracker.evaluate(policy, SystemTime::now(), &observations),
+        Err(_) => {
+            tracing::error!("Idle tracker lock poisoned");
+            return;
+        }
+    };
+
+    for decision in decisions {
+        match decision {
+            IdleDecision::Warn { target, idle_for, stop_in } => emit(IdleEvent::Warning {
+                target,
+                idle_for_secs: idle_for.as_secs(),
+                stop_in_secs: stop_in.as_secs(),
+            }),
+            IdleDecision::Stop { target, idle_for } => {
+                tracing::info!(instance = %target.instance, idle_secs = idle_for.as_secs(), "Stopping idle instance");
+                match backend.stop_instance(&target.project, &target.zone, &target.instance).await {
+                    Ok(()) => {
+                        let (tunnels_closed, closed) =
+                            tunnel::stop_instance_tunnels(&target.project, &target.zone, &target.instance);
+                        if let Err(e) = closed {
+                            tracing::warn!(instance = %target.instance, error = %e, "Failed to close tunnels of stopped instance");
+                        }
+                        last_seen.remove(&target);
+                        emit(IdleEvent::Stopped { target, idle_for_secs: idle_for.as_secs(), tunnels_closed });
+                    }
+                    Err(e) => emit(IdleEvent::StopFailed { target, error: e.to_string() }),
+                }
+            }
+        }
+    }
+}
+
+fn run(method: ApiMethod, context: GcloudContext, policy: IdlePolicy, tracker: Arc<Mutex<IdleTracker>>, stop: StopSignal) {
+    let rt = match runtime::runtime() {
+        Ok(rt) => rt,
+        Err(e) => {
+            tracing::error!(error = %e, "Idle monitor has no runtime");
+            return;
+        }
+    };
+
+    let mut backend: Option<Box<dyn CloudBackend>> = None;
+    let mut last_seen: HashMap<InstanceRef, SystemTime> = HashMap::new();
+
+    while !stop.is_stopped() {
+        rt.block_on(async {
+            if backend.is_none() {
+                match backend_for(method, &context).await {
+                    Ok(b) => backend = Some(b),
+                    Err(e) => tracing::warn!(error = %e, "Idle monitor could not create backend"),
+                }
+            }
+            if let Some(backend) = &backend {
+                check(backend.as_ref(), &policy, &tracker, &mut last_seen).await;
+            }
+        });
+
+        stop.sleep(policy.check_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_target;
+
+    const MIN: Duration = Duration::from_secs(60);
+
+    fn idle_vm(instance: &str, last_activity: SystemTime) -> InstanceObservation {
+        InstanceObservation {
+            target: test_target(instance),
+            status: "RUNNING".to_string(),
+            labels: BTreeMap::new(),
+            started_at: Some(last_activity - 120 * MIN),
+            active_connections: 0,
+            last_activity: Some(last_activity),
+        }
+    }
+
+    #[test]
+    fn test_warns_then_stops_and_honours_snooze() {
+        let policy = IdlePolicy::default();
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut tracker = IdleTracker::new();
+        let vm = [idle_vm("dev-1", t0)];
+
+        assert!(tracker.evaluate(&policy, t0 + 45 * MIN, &vm).is_empty());
+        assert_eq!(
+            tracker.evaluate(&policy, t0 + 50 * MIN, &vm),
+            vec![IdleDecision::Warn { target: test_target("dev-1"), idle_for: 50 * MIN, stop_in: 10 * MIN }]
+        );
+        assert!(tracker.evaluate(&policy, t0 + 55 * MIN, &vm).is_empty());
+
+        // Snoozed for 30 minutes: warned again before the new deadline
+        tracker.snooze(&test_target("dev-1"), t0 + 85 * MIN);
+        assert!(tracker.evaluate(&policy, t0 + 60 * MIN, &vm).is_empty());
+        assert!(matches!(tracker.evaluate(&policy, t0 + 75 * MIN, &vm)[..], [IdleDecision::Warn { .. }]));
+        assert_eq!(
+            tracker.evaluate(&policy, t0 + 85 * MIN, &vm),
+            vec![IdleDecision::Stop { target: test_target("dev-1"), idle_for: 85 * MIN }]
+        );
+
+        // An open connection is never idle, and a late first sighting still gets a warning
+        let mut busy = idle_vm("dev-2", t0);
+        busy.active_connections = 1;
+        let mut tracker = IdleTracker::new();
+        assert!(tracker.evaluate(&policy, t0 + 600 * MIN, &[busy]).is_empty());
+        assert!(matches!(
+            tracker.evaluate(&policy, t0 + 600 * MIN, &vm)[..],
+            [IdleDecision::Warn { stop_in, .. }] if stop_in == 10 * MIN
+        ));
+    }
+
+    #[test]
+    fn test_overrides_by_instance_and_label() {
+        let policy = IdlePolicy {
+            overrides: vec![
+                IdleOverride {
+                    selector: IdleSelector::Label { key: "env".to_string(), value: "dev".to_string() },
+                    idle_after: Some(20 * MIN),
+                },
+                IdleOverride {
+                    selector: IdleSelector::Label { key: "idle-stop".to_string(), value: "never".to_string() },
+                    idle_after: None,
+                },
+                IdleOverride { selector: IdleSelector::Instance(test_target("build-1")), idle_after: Some(5 * MIN) },
+            ],
+            ..Default::default()
+        };
+
+        let dev: BTreeMap<String, String> = [("env".to_string(), "dev".to_string())].into();
+        assert_eq!(policy.idle_after_for(&test_target("web-1"), &BTreeMap::new()), Some(60 * MIN));
+        assert_eq!(policy.idle_after_for(&test_target("web-1"), &dev), Some(20 * MIN));
+        assert_eq!(policy.idle_after_for(&test_target("build-1"), &dev), Some(5 * MIN));
+
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut exempt = idle_vm("db-1", t0);
+        exempt.labels.insert("idle-stop".to_string(), "never".to_string());
+        assert!(IdleTracker::new().evaluate(&policy, t0 + 600 * MIN, &[exempt]).is_empty());
+    }
+}
@@ -0,0 +1,473 @@
+//! Full instance description
+//!
+//! `GcpInstance` is the compact row shown in lists. `InstanceDetails` is what
+//! the detail view needs to pick the right VM and troubleshoot it: addresses,
+//! labels, tags, identity, scheduling, shielded VM, GPUs and every disk.
+//!
+//! Parsed from the Instance JSON shared by `gcloud compute instances describe
+//! --format=json` and the REST `instances.get` call.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+use crate::gcloud::GcpInstance;
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct InstanceDetails {
+    pub id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    pub status: String,
+    pub zone: String,
+    pub machine_type: String,
+    pub cpu_platform: Option<String>,
+    pub deletion_protection: bool,
+    pub creation_timestamp: Option<String>,
+    pub last_start_timestamp: Option<String>,
+    pub last_stop_timestamp: Option<String>,
+    pub network_interfaces: Vec<NetworkInterfaceDetails>,
+    pub labels: BTreeMap<String, String>,
+    pub tags: Vec<String>,
+    pub service_accounts: Vec<ServiceAccountDetails>,
+    /// Metadata keys only: values hold startup scripts and ssh keys
+    pub metadata_keys: Vec<String>,
+    pub scheduling: SchedulingDetails,
+    pub shielded_vm: Option<ShieldedVmDetails>,
+    pub guest_accelerators: Vec<AcceleratorDetails>,
+    pub disks: Vec<AttachedDiskDetails>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct NetworkInterfaceDetails {
+    pub name: String,
+    pub network: Option<String>,
+    pub subnetwork: Option<String>,
+    pub internal_ip: Option<String>,
+    pub external_ips: Vec<String>,
+    pub ipv6_address: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ServiceAccountDetails {
+    pub email: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct SchedulingDetails {
+    /// STANDARD or SPOT
+    pub provisioning_model: Option<String>,
+    pub preemptible: bool,
+    pub automatic_restart: Option<bool>,
+    /// MIGRATE or TERMINATE
+    pub on_host_maintenance: Option<String>,
+    /// STOP or DELETE (spot VMs)
+    pub instance_termination_action: Option<String>,
+}
+
+impl SchedulingDetails {
+    /// Spot or legacy preemptible
+    pub fn is_spot(&self) -> bool {
+        self.preemptible || self.provisioning_model.as_deref() == Some("SPOT")
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ShieldedVmDetails {
+    pub secure_boot: bool,
+    pub vtpm: bool,
+    pub integrity_monitoring: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct AcceleratorDetails {
+    pub accelerator_type: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct AttachedDiskDetails {
+    pub device_name: Option<String>,
+    /// Disk resource name (None for local SSD)
+    pub source: Option<String>,
+    pub boot: bool,
+    pub auto_delete: bool,
+    /// READ_WRITE or READ_ONLY
+    pub mode: Option<String>,
+    /// SCSI or NVME
+    pub interface: Option<String>,
+    /// PERSISTENT or SCRATCH
+    pub kind: Option<String>,
+    pub disk_size_gb: Option<u64>,
+}
+
+impl InstanceDetails {
+    /// Parse an Instance resource (CLI or REST JSON)
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        let raw: RawInstanceDetails = serde_json::from_slice(json)
+            .map_err(|e| anyhow!("Failed to parse instance JSON: {}", e))?;
+        Ok(raw.into())
+    }
+
+    /// Parse an already-decoded Instance resource
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let raw: RawInstanceDetails = serde_json::from_value(value)
+            .map_err(|e| anyhow!("Failed to parse instance JSON: {}", e))?;
+        Ok(raw.into())
+    }
+
+    /// Boot disk, falling back to the first disk
+    pub fn boot_disk(&self) -> Option<&AttachedDiskDetails> {
+        self.disks.iter().find(|d| d.boot).or_else(|| self.disks.first())
+    }
+
+    /// First external IP of the first interface that has one
+    pub fn external_ip(&self) -> Option<&str> {
+        self.network_interfaces
+            .iter()
+            .flat_map(|n| n.external_ips.iter())
+            .map(String::as_str)
+            .next()
+    }
+
+    /// Internal IP of the primary interface
+    pub fn internal_ip(&self) -> Option<&str> {
+        self.network_interfaces.first().and_then(|n| n.internal_ip.as_deref())
+    }
+}
+
+/// Minimal details for backends that only know the list row (e.g. tests)
+impl From<&GcpInstance> for InstanceDetails {
+    fn from(instance: &GcpInstance) -> Self {
+        Self {
+            name: instance.name.clone(),
+            status: instance.status.clone(),
+            zone: instance.zone.clone(),
+            machine_type: instance.machine_type.clone(),
+            disks: instance
+                .disk_gb
+                .map(|gb| {
+                    vec![AttachedDiskDetails {
+                        boot: true,
+                        disk_size_gb: Some(gb as u64),
+                        ..Default::default()
+                    }]
+                })
+                .unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Last segment of a resource URL (`.../zones/us-central1-a` -> `us-central1-a`)
+fn short_name(url: &str) -> String {
+    url.rsplit('/').next().unwrap_or(url).to_string()
+}
+
+/// int64 fields come as JSON strings in the API ("10"); accept numbers too
+fn u64_from_string_or_number<'de, D>(deserializer: D) -> std::result::Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Str(String),
+        Num(u64),
+    }
+    Ok(match Option::<Raw>::deserialize(deserializer)? {
+        Some(Raw::Num(n)) => Some(n),
+        Some(Raw::Str(s)) => s.parse().ok(),
+        None => None,
+    })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawInstanceDetails {
+    #[serde(default)]
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    zone: String,
+    #[serde(default)]
+    machine_type: Option<String>,
+    #[serde(default)]
+    cpu_platform: Option<String>,
+    #[serde(default)]
+    deletion_protection: bool,
+    #[serde(default)]
+    creation_timestamp: Option<String>,
+    #[serde(default)]
+    last_start_timestamp: Option<String>,
+    #[serde(default)]
+    last_stop_timestamp: Option<String>,
+    #[serde(default)]
+    network_interfaces: Vec<RawNetworkInterface>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    tags: RawTags,
+    #[serde(default)]
+    service_accounts: Vec<RawServiceAccount>,
+    #[serde(default)]
+    metadata: RawMetadata,
+    #[serde(default)]
+    scheduling: RawScheduling,
+    #[serde(default)]
+    shielded_instance_config: Option<RawShieldedConfig>,
+    #[serde(default)]
+    guest_accelerators: Vec<RawAccelerator>,
+    #[serde(default)]
+    disks: Vec<RawAttachedDisk>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawNetworkInterface {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    network: Option<String>,
+    #[serde(default)]
+    subnetwork: Option<String>,
+    #[serde(default, rename = "networkIP")]
+    network_ip: Option<String>,
+    #[serde(default)]
+    ipv6_address: Option<String>,
+    #[serde(default)]
+    access_configs: Vec<RawAccessConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAccessConfig {
+    #[serde(default, rename = "natIP")]
+    nat_ip: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct RawTags {
+    #[serde(default)]
+    items: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RawServiceAccount {
+    email: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct RawMetadata {
+    #[serde(default)]
+    items: Vec<RawMetadataItem>,
+}
+
+#[derive(Deserialize)]
+struct RawMetadataItem {
+    key: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RawScheduling {
+    #[serde(default)]
+    provisioning_model: Option<String>,
+    #[serde(default)]
+    preemptible: bool,
+    #[serde(default)]
+    automatic_restart: Option<bool>,
+    #[serde(default)]
+    on_host_maintenance: Option<String>,
+    #[serde(default)]
+    instance_termination_action: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawShieldedConfig {
+    #[serde(default)]
+    enable_secure_boot: bool,
+    #[serde(default)]
+    enable_vtpm: bool,
+    #[serde(default)]
+    enable_integrity_monitoring: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAccelerator {
+    accelerator_type: String,
+    #[serde(default)]
+    accelerator_count: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAttachedDisk {
+    #[serde(default)]
+    device_name: Option<String>,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    boot: bool,
+    #[serde(default)]
+    auto_delete: bool,
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    interface: Option<String>,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    #[serde(default, deserialize_with = "u64_from_string_or_number")]
+    disk_size_gb: Option<u64>,
+}
+
+impl From<RawInstanceDetails> for InstanceDetails {
+    fn from(raw: RawInstanceDetails) -> Self {
+        Self {
+            id: raw.id,
+            name: raw.name,
+            description: raw.description.filter(|d| !d.is_empty()),
+            status: raw.status,
+            zone: short_name(&raw.zone),
+            machine_type: raw.machine_type.as_deref().map(short_name).unwrap_or_else(|| "Unknown".to_string()),
+            cpu_platform: raw.cpu_platform,
+            deletion_protection: raw.deletion_protection,
+            creation_timestamp: raw.creation_timestamp,
+            last_start_timestamp: raw.last_start_timestamp,
+            last_stop_timestamp: raw.last_stop_timestamp,
+            network_interfaces: raw
+                .network_interfaces
+                .into_iter()
+                .map(|n| NetworkInterfaceDetails {
+                    name: n.name,
+                    network: n.network.as_deref().map(short_name),
+                    subnetwork: n.subnetwork.as_deref().map(short_name),
+                    internal_ip: n.network_ip,
+                    external_ips: n.access_configs.into_iter().filter_map(|a| a.nat_ip).collect(),
+                    ipv6_address: n.ipv6_address,
+                })
+                .collect(),
+            labels: raw.labels,
+            tags: raw.tags.items,
+            service_accounts: raw
+                .service_accounts
+                .into_iter()
+                .map(|sa| ServiceAccountDetails { email: sa.email, scopes: sa.scopes })
+                .collect(),
+            metadata_keys: raw.metadata.items.into_iter().map(|i| i.key).collect(),
+            scheduling: SchedulingDetails {
+                provisioning_model: raw.scheduling.provisioning_model,
+                preemptible: raw.scheduling.preemptible,
+                automatic_restart: raw.scheduling.automatic_restart,
+                on_host_maintenance: raw.scheduling.on_host_maintenance,
+                instance_termination_action: raw.scheduling.instance_termination_action,
+            },
+            shielded_vm: raw.shielded_instance_config.map(|s| ShieldedVmDetails {
+                secure_boot: s.enable_secure_boot,
+                vtpm: s.enable_vtpm,
+                integrity_monitoring: s.enable_integrity_monitoring,
+            }),
+            guest_accelerators: raw
+                .guest_accelerators
+                .into_iter()
+                .map(|a| AcceleratorDetails {
+                    accelerator_type: short_name(&a.accelerator_type),
+                    count: a.accelerator_count,
+                })
+                .collect(),
+            disks: raw
+                .disks
+                .into_iter()
+                .map(|d| AttachedDiskDetails {
+                    device_name: d.device_name,
+                    source: d.source.as_deref().map(short_name),
+                    boot: d.boot,
+                    auto_delete: d.auto_delete,
+                    mode: d.mode,
+                    interface: d.interface,
+                    kind: d.kind,
+                    disk_size_gb: d.disk_size_gb,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIBE_JSON: &str = r#"{
+        "id": "1234567890123456789",
+        "name": "gpu-worker-1",
+        "status": "RUNNING",
+        "zone": "https://www.googleapis.com/compute/v1/projects/p/zones/us-central1-a",
+        "machineType": "https://www.googleapis.com/compute/v1/projects/p/zones/us-central1-a/machineTypes/n1-standard-8",
+        "cpuPlatform": "Intel Skylake",
+        "deletionProtection": true,
+        "creationTimestamp": "2024-03-01T10:00:00.000-08:00",
+        "lastStartTimestamp": "2024-06-01T08:00:00.000-07:00",
+        "labels": {"env": "prod", "team": "ml"},
+        "tags": {"items": ["allow-iap", "gpu"], "fingerprint": "abc"},
+        "networkInterfaces": [{
+            "name": "nic0",
+            "network": "https://www.googleapis.com/compute/v1/projects/p/global/networks/default",
+            "subnetwork": "https://www.googleapis.com/compute/v1/projects/p/regions/us-central1/subnetworks/default",
+            "networkIP": "10.128.0.5",
+            "accessConfigs": [{"name": "External NAT", "natIP": "34.1.2.3", "type": "ONE_TO_ONE_NAT"}]
+        }],
+        "serviceAccounts": [{"email": "123-compute@developer.gserviceaccount.com",
+                             "scopes": ["https://www.googleapis.com/auth/cloud-platform"]}],
+        "metadata": {"items": [{"key": "startup-script", "value": "echo secret"}, {"key": "enable-oslogin", "value": "TRUE"}]},
+        "scheduling": {"provisioningModel": "SPOT", "preemptible": false, "automaticRestart": false,
+                       "onHostMaintenance": "TERMINATE", "instanceTerminationAction": "STOP"},
+        "shieldedInstanceConfig": {"enableSecureBoot": false, "enableVtpm": true, "enableIntegrityMonitoring": true},
+        "guestAccelerators": [{"acceleratorType": "projects/p/zones/us-central1-a/acceleratorTypes/nvidia-tesla-t4", "acceleratorCount": 1}],
+        "disks": [
+            {"deviceName": "data", "source": "https://www.googleapis.com/compute/v1/projects/p/zones/us-central1-a/disks/data", "boot": false, "diskSizeGb": "500", "type": "PERSISTENT", "mode": "READ_WRITE"},
+            {"deviceName": "boot", "source": "https://www.googleapis.com/compute/v1/projects/p/zones/us-central1-a/disks/gpu-worker-1", "boot": true, "autoDelete": true, "diskSizeGb": "50", "interface": "SCSI"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_full_instance() {
+        let details = InstanceDetails::from_json(DESCRIBE_JSON.as_bytes()).unwrap();
+
+        assert_eq!(details.zone, "us-central1-a");
+        assert_eq!(details.machine_type, "n1-standard-8");
+        assert!(details.deletion_protection);
+        assert_eq!(details.internal_ip(), Some("10.128.0.5"));
+        assert_eq!(details.external_ip(), Some("34.1.2.3"));
+        assert_eq!(details.network_interfaces[0].subnetwork.as_deref(), Some("default"));
+        assert_eq!(details.labels.get("team").map(String::as_str), Some("ml"));
+        assert_eq!(details.tags, vec!["allow-iap", "gpu"]);
+        assert_eq!(details.metadata_keys, vec!["startup-script", "enable-oslogin"]);
+        assert!(details.scheduling.is_spot());
+        assert_eq!(details.shielded_vm.as_ref().map(|s| s.vtpm), Some(true));
+        assert_eq!(details.guest_accelerators[0].accelerator_type, "nvidia-tesla-t4");
+
+        let boot = details.boot_disk().unwrap();
+        assert_eq!(boot.source.as_deref(), Some("gpu-worker-1"));
+        assert_eq!(boot.disk_size_gb, Some(50));
+        assert_eq!(details.disks.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_minimal_instance() {
+        let details = InstanceDetails::from_json(br#"{"name": "vm", "status": "TERMINATED", "zone": "zones/europe-west1-b"}"#).unwrap();
+        assert_eq!(details.zone, "europe-west1-b");
+        assert_eq!(details.machine_type, "Unknown");
+        assert!(details.network_interfaces.is_empty());
+        assert!(!details.scheduling.is_spot());
+        assert_eq!(details.external_ip(), None);
+    }
+}
@@ -0,0 +1,300 @@
+//! Cross-project instance inventory
+//!
+//! Fans `list_instances` out over many projects (or every accessible one)
+//! with a bounded number of concurrent listings and merges the results.
+//! A project that fails (API disabled, no permission...) is reported next to
+//! the inventory instead of failing the whole search, so "where is
+//! `db-replica-3`?" still gets an answer from the projects that did respond.
+
+use anyhow::{Result, anyhow};
+use futures_util::{StreamExt, stream};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::accounts::GcloudContext;
+use crate::backend::{backend_for, ApiMethod, CloudBackend};
+use crate::gcloud::GcpInstance;
+use crate::pagination::ListOptions;
+use crate::runtime;
+use crate::validation::{validate_label, validate_project_id, validate_zone};
+
+const INSTANCE_STATUSES: &[&str] = &[
+    "PROVISIONING", "STAGING", "RUNNING", "STOPPING", "STOPPED",
+    "SUSPENDING", "SUSPENDED", "REPAIRING", "TERMINATED",
+];
+
+/// Which instances to return
+///
+/// Every set field must match. Status and labels are sent to the API as a
+/// server-side filter; name and zone are applied locally as well because
+/// not every backend can express them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InstanceFilter {
+    /// RUNNING, TERMINATED, ... (case-insensitive)
+    pub status: Option<String>,
+    pub zone: Option<String>,
+    /// Regular expression searched in the instance name
+    pub name_regex: Option<String>,
+    /// `labels.KEY = VALUE`
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+impl InstanceFilter {
+    pub fn is_empty(&self) -> bool {
+        self.status.is_none() && self.zone.is_none() && self.name_regex.is_none() && self.labels.is_empty()
+    }
+
+    /// SECURITY: Must be called before the expression reaches gcloud or the API
+    pub fn validate(&self) -> Result<()> {
+        if let Some(status) = &self.status {
+            if !INSTANCE_STATUSES.contains(&status.to_uppercase().as_str()) {
+                return Err(anyhow!("Invalid status '{}'. Expected one of {}", status, INSTANCE_STATUSES.join(", ")));
+            }
+        }
+        if let Some(zone) = &self.zone {
+            validate_zone(zone)?;
+        }
+        if let Some(pattern) = &self.name_regex {
+            // Quotes would end the quoted filter value
+            if pattern.contains(['\'', '"', '\n']) {
+                return Err(anyhow!("Name pattern cannot contain quotes or newlines"));
+            }
+            Regex::new(pattern).map_err(|e| anyhow!("Invalid name pattern '{}': {}", pattern, e))?;
+        }
+        for (key, value) in &self.labels {
+            validate_label(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn status_upper(&self) -> Option<String> {
+        self.status.as_ref().map(|s| s.to_uppercase())
+    }
+
+    /// `--filter` expression for `gcloud compute instances list`
+    ///
+    /// The zone goes in `--zones` instead.
+    pub fn gcloud_expression(&self) -> Option<String> {
+        let mut terms = Vec::new();
+        if let Some(status) = self.status_upper() {
+            terms.push(format!("status={}", status));
+        }
+        if let Some(pattern) = &self.name_regex {
+            terms.push(format!("name~'{}'", pattern));
+        }
+        for (key, value) in &self.labels {
+            terms.push(format!("labels.{}={}", key, value));
+        }
+        (!terms.is_empty()).then(|| terms.join(" AND "))
+    }
+
+    /// `filter` parameter for `instances.aggregatedList`
+    ///
+    /// Regex (`eq`) terms can't be mixed with `=` terms, so the name is left
+    /// to [`InstanceFilter::matches`].
+    pub fn api_expression(&self) -> Option<String> {
+        let mut terms = Vec::new();
+        if let Some(status) = self.status_upper() {
+            terms.push(format!("(status = \"{}\")", status));
+        }
+        for (key, value) in &self.labels {
+            terms.push(format!("(labels.{} = \"{}\")", key, value));
+        }
+        (!terms.is_empty()).then(|| terms.join(" "))
+    }
+
+    /// Local check of the fields present on [`GcpInstance`] (labels aren't)
+    pub fn matches(&self, instance: &GcpInstance) -> bool {
+        if let Some(status) = self.status_upper() {
+            if instance.status != status {
+                return false;
+            }
+        }
+        if let Some(zone) = &self.zone {
+            if &instance.zone != zone {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.name_regex {
+            match Regex::new(pattern) {
+                Ok(re) if re.is_match(&instance.name) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Fan-out tuning
+#[derive(Debug, Clone)]
+pub struct InventoryOptions {
+    /// Projects listed at the same time
+    pub concurrency: usize,
+    /// Per-project listing options
+    pub list: ListOptions,
+}
+
+impl Default for InventoryOptions {
+    fn default() -> Self {
+        Self { concurrency: 8, list: ListOptions::default() }
+    }
+}
+
+/// An instance and the project it lives in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    pub project_id: String,
+    #[serde(flatten)]
+    pub instance: GcpInstance,
+}
+
+/// A project that could not be listed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectFailure {
+    pub project_id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    /// Sorted by project, then instance name
+    pub entries: Vec<InventoryEntry>,
+    pub failures: Vec<ProjectFailure>,
+    pub projects_scanned: usize,
+}
+
+/// List matching instances across `projects` (`None` = every active project)
+///
+/// Only an invalid filter or failing to enumerate projects is an error;
+/// per-project problems end up in [`Inventory::failures`].
+pub async fn collect_inventory(
+    backend: &dyn CloudBackend,
+    projects: Option<Vec<String>>,
+    filter: &InstanceFilter,
+    options: &InventoryOptions,
+) -> Result<Inventory> {
+    filter.validate()?;
+
+    let projects = match projects {
+        Some(projects) => projects,
+        None => backend
+            .list_projects()
+            .await?
+            .into_iter()
+            .filter(|p| p.state.as_deref().is_none_or(|s| s == "ACTIVE"))
+            .map(|p| p.project_id)
+            .collect(),
+    };
+
+    tracing::info!(
+        projects = projects.len(),
+        concurrency = options.concurrency,
+        backend = backend.name(),
+        "Collecting instance inventory"
+    );
+
+    let results: Vec<(String, Result<Vec<GcpInstance>>)> = stream::iter(projects)
+        .map(|project| async move {
+            let listed = match validate_project_id(&project) {
+                Ok(()) => backend.list_instances_filtered(&project, filter, &options.list).await,
+                Err(e) => Err(e),
+            };
+            (project, listed)
+        })
+        .buffer_unordered(options.concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut inventory = Inventory { projects_scanned: results.len(), ..Default::default() };
+    for (project_id, listed) in results {
+        match listed {
+            Ok(instances) => inventory.entries.extend(
+                instances
+                    .into_iter()
+                    .filter(|i| filter.matches(i))
+                    .map(|instance| InventoryEntry { project_id: project_id.clone(), instance }),
+            ),
+            Err(e) => {
+                tracing::warn!(project = %project_id, error = %e, "Skipping project in inventory");
+                inventory.failures.push(ProjectFailure { project_id, error: e.to_string() });
+            }
+        }
+    }
+
+    inventory.entries.sort_by(|a, b| {
+        (a.project_id.as_str(), a.instance.name.as_str()).cmp(&(b.project_id.as_str(), b.instance.name.as_str()))
+    });
+    inventory.failures.sort_by(|a, b| a.project_id.cmp(&b.project_id));
+    Ok(inventory)
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn search_instances_via(
+    method: ApiMethod,
+    context: &GcloudContext,
+    projects: Option<Vec<String>>,
+    filter: &InstanceFilter,
+    options: &InventoryOptions,
+) -> Result<Inventory> {
+    runtime::block_on(async {
+        let backend = backend_for(method, context).await?;
+        collect_inventory(backend.as_ref(), projects, filter, options).await
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{test_instance, FakeBackend};
+
+    #[test]
+    fn test_filter_expressions() {
+        let mut filter = InstanceFilter {
+            status: Some("running".to_string()),
+            name_regex: Some("^db-".to_string()),
+            ..Default::default()
+        };
+        filter.labels.insert("env".to_string(), "prod".to_string());
+        assert!(filter.validate().is_ok());
+        assert_eq!(
+            filter.gcloud_expression().as_deref(),
+            Some("status=RUNNING AND name~'^db-' AND labels.env=prod")
+        );
+        assert_eq!(filter.api_expression().as_deref(), Some("(status = \"RUNNING\") (labels.env = \"prod\")"));
+
+        assert!(filter.matches(&test_instance("db-replica-3", "us-central1-a", "RUNNING")));
+        assert!(!filter.matches(&test_instance("web-1", "us-central1-a", "RUNNING")));
+
+        let injected = InstanceFilter { name_regex: Some("x' OR name~'.".to_string()), ..Default::default() };
+        assert!(injected.validate().is_err());
+        assert!(InstanceFilter { status: Some("BOGUS".to_string()), ..Default::default() }.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inventory_merges_and_keeps_failures() {
+        let fake = FakeBackend::new();
+        fake.add_project("alpha-project");
+        fake.add_project("beta-project");
+        fake.add_instance("alpha-project", test_instance("db-replica-3", "europe-west1-b", "RUNNING"));
+        fake.add_instance("alpha-project", test_instance("web-1", "europe-west1-b", "RUNNING"));
+        fake.add_instance("beta-project", test_instance("db-replica-1", "us-east1-c", "TERMINATED"));
+
+        let projects = vec!["alpha-project".to_string(), "beta-project".to_string(), "gamma-project".to_string()];
+        let filter = InstanceFilter { name_regex: Some("^db-replica".to_string()), ..Default::default() };
+        let inventory = collect_inventory(&fake, Some(projects), &filter, &InventoryOptions { concurrency: 2, ..Default::default() })
+            .await
+            .unwrap();
+
+        assert_eq!(inventory.projects_scanned, 3);
+        let found: Vec<(&str, &str)> = inventory
+            .entries
+            .iter()
+            .map(|e| (e.project_id.as_str(), e.instance.name.as_str()))
+            .collect();
+        assert_eq!(found, vec![("alpha-project", "db-replica-3"), ("beta-project", "db-replica-1")]);
+        assert_eq!(inventory.failures.len(), 1);
+        assert_eq!(inventory.failures[0].project_id, "gamma-project");
+    }
+}
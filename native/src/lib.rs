@@ -1,9 +1,30 @@
 mod api;
-mod gcloud;
-mod gcloud_client_poc;  // PoC: Google Cloud Client Libraries
-mod tunnel;
-mod remmina;
-mod validation;
-mod logging;
-mod sftp;
-mod frb_generated;
\ No newline at end of file
+pub mod accounts;
+pub mod backend;
+pub mod bulk;
+pub mod cost;
+pub mod gcloud;
+pub mod credentials;
+pub mod gcloud_client_poc;  // PoC: Google Cloud Client Libraries
+pub mod tunnel;
+pub mod tunnel_state;
+pub mod tunnel_errors;
+pub mod iap;
+pub mod hierarchy;
+pub mod idle;
+pub mod instance_details;
+pub mod inventory;
+pub mod machine_types;
+pub mod metrics;
+pub mod operations;
+pub mod pagination;
+pub mod schedules;
+pub mod supervisor;
+pub mod remmina;
+pub mod runtime;
+pub mod validation;
+pub mod worker;
+pub mod logging;
+pub mod sftp;
+pub mod ssh_config;
+mod frb_generated;
@@ -0,0 +1,497 @@
+//! Machine type specs
+//!
+//! Resolution order for a machine type name:
+//! 1. Custom shapes are parsed from the name (`n2-custom-8-32768-ext`)
+//! 2. The on-disk cache, per project and zone, if younger than the TTL
+//! 3. `machineTypes.aggregatedList` (REST) or `gcloud compute machine-types list`
+//! 4. A heuristic ratio table, only when offline
+//!
+//! The cache lives in `~/.cache/linux_cloud_connector/machine_types/<project>.json`.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::process::Command as TokioCommand;
+use tokio::time::timeout;
+
+use crate::accounts::GcloudContext;
+use crate::gcloud::GcpInstance;
+use crate::validation::{validate_project_id, validate_zone};
+
+/// How long API answers are trusted; machine types almost never change
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Where a spec came from, so the UI can flag guesses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpecSource {
+    Api,
+    CustomName,
+    Heuristic,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MachineAccelerator {
+    pub accelerator_type: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MachineSpec {
+    pub name: String,
+    /// vCPUs visible to the guest
+    pub guest_cpus: u32,
+    pub memory_mb: u32,
+    /// Shared-core types get a fraction of a physical core
+    pub shared_cpu: bool,
+    /// Sustained CPU share for shared-core types (e2-micro = 0.25)
+    pub cpu_fraction: Option<f32>,
+    #[serde(default)]
+    pub accelerators: Vec<MachineAccelerator>,
+    pub source: SpecSource,
+}
+
+impl MachineSpec {
+    fn new(name: &str, guest_cpus: u32, memory_mb: u32, source: SpecSource) -> Self {
+        Self {
+            name: name.to_string(),
+            guest_cpus,
+            memory_mb,
+            shared_cpu: false,
+            cpu_fraction: None,
+            accelerators: Vec::new(),
+            source,
+        }
+    }
+
+    fn shared(mut self, fraction: f32) -> Self {
+        self.shared_cpu = true;
+        self.cpu_fraction = Some(fraction);
+        self
+    }
+
+    fn with_gpus(mut self, accelerator_type: &str, count: u32) -> Self {
+        self.accelerators.push(MachineAccelerator { accelerator_type: accelerator_type.to_string(), count });
+        self
+    }
+}
+
+/// Sustained CPU share of the shared-core types
+fn shared_core_fraction(name: &str) -> Option<f32> {
+    match name {
+        "e2-micro" => Some(0.25),
+        "e2-small" => Some(0.5),
+        "e2-medium" => Some(1.0),
+        "f1-micro" => Some(0.2),
+        "g1-small" => Some(0.5),
+        _ => None,
+    }
+}
+
+/// Parse custom machine type names
+///
+/// `custom-4-8192` (N1), `e2-custom-4-8192`, `n2-custom-8-32768-ext`,
+/// `e2-custom-medium-4096` (shared core).
+pub fn parse_custom(name: &str) -> Option<MachineSpec> {
+    let parts: Vec<&str> = name.split('-').collect();
+    let custom_at = parts.iter().position(|p| *p == "custom")?;
+    if custom_at > 1 {
+        return None;
+    }
+    let series = if custom_at == 1 { parts[0] } else { "n1" };
+    let mut rest = &parts[custom_at + 1..];
+    if rest.last() == Some(&"ext") {
+        rest = &rest[..rest.len() - 1];
+    }
+
+    match rest {
+        [shape @ ("micro" | "small" | "medium"), memory] if series == "e2" => {
+            let memory_mb = memory.parse().ok()?;
+            let fraction = shared_core_fraction(&format!("e2-{}", shape))?;
+            Some(MachineSpec::new(name, 2, memory_mb, SpecSource::CustomName).shared(fraction))
+        }
+        [cpus, memory] => Some(MachineSpec::new(name, cpus.parse().ok()?, memory.parse().ok()?, SpecSource::CustomName)),
+        _ => None,
+    }
+}
+
+/// Offline guess from the name (custom parse, then ratio tables)
+pub fn offline_spec(name: &str) -> Option<MachineSpec> {
+    parse_custom(name).or_else(|| heuristic_spec(name))
+}
+
+/// Ratio-table guess, used only when the API cannot be reached
+pub fn heuristic_spec(name: &str) -> Option<MachineSpec> {
+    let spec = |cpus: u32, memory_gb_x10: u32| Some(MachineSpec::new(name, cpus, memory_gb_x10 * 1024 / 10, SpecSource::Heuristic));
+
+    // Shared core and accelerator-optimized shapes don't follow {series}-{family}-{cpus}
+    let known = match name {
+        "e2-micro" => spec(2, 10),
+        "e2-small" => spec(2, 20),
+        "e2-medium" => spec(2, 40),
+        "f1-micro" => spec(1, 6),
+        "g1-small" => spec(1, 17),
+        "a2-highgpu-1g" => spec(12, 850).map(|s| s.with_gpus("nvidia-tesla-a100", 1)),
+        "a2-highgpu-2g" => spec(24, 1700).map(|s| s.with_gpus("nvidia-tesla-a100", 2)),
+        "a2-highgpu-4g" => spec(48, 3400).map(|s| s.with_gpus("nvidia-tesla-a100", 4)),
+        "a2-highgpu-8g" => spec(96, 6800).map(|s| s.with_gpus("nvidia-tesla-a100", 8)),
+        "a2-megagpu-16g" => spec(96, 13600).map(|s| s.with_gpus("nvidia-tesla-a100", 16)),
+        "a2-ultragpu-1g" => spec(12, 1700).map(|s| s.with_gpus("nvidia-a100-80gb", 1)),
+        "a2-ultragpu-2g" => spec(24, 3400).map(|s| s.with_gpus("nvidia-a100-80gb", 2)),
+        "a2-ultragpu-4g" => spec(48, 6800).map(|s| s.with_gpus("nvidia-a100-80gb", 4)),
+        "a2-ultragpu-8g" => spec(96, 13600).map(|s| s.with_gpus("nvidia-a100-80gb", 8)),
+        "a3-highgpu-1g" => spec(26, 2340).map(|s| s.with_gpus("nvidia-h100-80gb", 1)),
+        "a3-highgpu-2g" => spec(52, 4680).map(|s| s.with_gpus("nvidia-h100-80gb", 2)),
+        "a3-highgpu-4g" => spec(104, 9360).map(|s| s.with_gpus("nvidia-h100-80gb", 4)),
+        "a3-highgpu-8g" => spec(208, 18720).map(|s| s.with_gpus("nvidia-h100-80gb", 8)),
+        "a3-megagpu-8g" => spec(208, 18720).map(|s| s.with_gpus("nvidia-h100-mega-80gb", 8)),
+        _ => None,
+    };
+    if let Some(mut spec) = known {
+        if let Some(fraction) = shared_core_fraction(name) {
+            spec = spec.shared(fraction);
+        }
+        return Some(spec);
+    }
+
+    // {series}-{family}-{cpus}[-lssd|-metal]
+    let parts: Vec<&str> = name.split('-').collect();
+    if parts.len() < 3 {
+        return None;
+    }
+    let (series, family) = (parts[0], parts[1]);
+    let cpus: u32 = parts[2].parse().ok()?;
+
+    // MB of memory per vCPU
+    let per_cpu = match (series, family) {
+        ("n1", "standard") => 3840,
+        ("n1", "highmem") => 6656,
+        ("n1", "highcpu") => 922,
+        ("c4" | "c4a" | "c4d", "standard") => 3840,
+        ("c4" | "c4a" | "c4d", "highmem") => 7936,
+        ("m1" | "m2" | "m3", "megamem") => 14336,
+        ("m1" | "m2" | "m3", "ultramem") => 24576,
+        (_, "standard") => 4096,
+        (_, "highmem") => 8192,
+        ("e2" | "n2" | "n2d", "highcpu") => 1024,
+        (_, "highcpu") => 2048,
+        _ => return None,
+    };
+    let mut spec = MachineSpec::new(name, cpus, cpus * per_cpu, SpecSource::Heuristic);
+
+    // G2 carries L4 GPUs: 1 per 4..32 vCPUs, then 2/4/8
+    if series == "g2" {
+        let gpus = match cpus {
+            24 => 2,
+            48 => 4,
+            96 => 8,
+            _ => 1,
+        };
+        spec = spec.with_gpus("nvidia-l4", gpus);
+    }
+    Some(spec)
+}
+
+// ==========================================
+// On-disk cache
+// ==========================================
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ZoneEntry {
+    /// Unix seconds
+    fetched_at: u64,
+    types: BTreeMap<String, MachineSpec>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CacheFile {
+    zones: BTreeMap<String, ZoneEntry>,
+}
+
+/// Machine type specs for one project, keyed by zone
+pub struct MachineTypeCache {
+    path: PathBuf,
+    ttl: Duration,
+    data: CacheFile,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+impl MachineTypeCache {
+    /// Load (or start empty) the cache for `project` in the default location
+    pub fn for_project(project: &str, ttl: Duration) -> Result<Self> {
+        validate_project_id(project)?;
+        let dir = dirs::cache_dir()
+            .ok_or_else(|| anyhow!("Could not determine cache directory"))?
+            .join("linux_cloud_connector")
+            .join("machine_types");
+        Ok(Self::load(dir.join(format!("{}.json", project)), ttl))
+    }
+
+    /// Load from an explicit path; a missing or corrupt file is an empty cache
+    pub fn load(path: PathBuf, ttl: Duration) -> Self {
+        let data = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self { path, ttl, data }
+    }
+
+    /// Fresh cached spec for `name` in `zone`
+    pub fn get(&self, zone: &str, name: &str) -> Option<&MachineSpec> {
+        let entry = self.data.zones.get(zone)?;
+        if now_secs().saturating_sub(entry.fetched_at) >= self.ttl.as_secs() {
+            return None;
+        }
+        entry.types.get(name)
+    }
+
+    /// Record API results for a zone
+    pub fn insert(&mut self, zone: &str, spec: MachineSpec) {
+        let entry = self.data.zones.entry(zone.to_string()).or_default();
+        if now_secs().saturating_sub(entry.fetched_at) >= self.ttl.as_secs() {
+            entry.types.clear();
+        }
+        entry.fetched_at = now_secs();
+        entry.types.insert(spec.name.clone(), spec);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.data)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+// ==========================================
+// API lookups
+// ==========================================
+
+/// MachineType resource fields we read (same JSON from gcloud and REST)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RawMachineType {
+    name: String,
+    #[serde(default)]
+    zone: String,
+    #[serde(default)]
+    guest_cpus: u32,
+    #[serde(default)]
+    memory_mb: u32,
+    #[serde(default)]
+    is_shared_cpu: bool,
+    #[serde(default)]
+    accelerators: Vec<RawMachineAccelerator>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawMachineAccelerator {
+    guest_accelerator_type: String,
+    #[serde(default)]
+    guest_accelerator_count: u32,
+}
+
+impl RawMachineType {
+    /// (zone, spec)
+    pub(crate) fn into_zone_spec(self) -> (String, MachineSpec) {
+        let zone = self.zone.rsplit('/').next().unwrap_or(&self.zone).to_string();
+        let mut spec = MachineSpec::new(&self.name, self.guest_cpus, self.memory_mb, SpecSource::Api);
+        if self.is_shared_cpu {
+            spec = spec.shared(shared_core_fraction(&self.name).unwrap_or(0.5));
+        }
+        spec.accelerators = self
+            .accelerators
+            .into_iter()
+            .map(|a| MachineAccelerator { accelerator_type: a.guest_accelerator_type, count: a.guest_accelerator_count })
+            .collect();
+        (zone, spec)
+    }
+}
+
+/// Filter expression matching any of `names` (API and gcloud accept the same syntax)
+pub(crate) fn name_filter(names: &BTreeSet<String>) -> String {
+    let alternatives: Vec<&str> = names.iter().map(String::as_str).collect();
+    format!("name eq \"({})\"", alternatives.join("|"))
+}
+
+/// `gcloud compute machine-types list` for the given names (all zones)
+pub async fn fetch_machine_types_cli(
+    ctx: &GcloudContext,
+    project: &str,
+    names: &BTreeSet<String>,
+) -> Result<Vec<(String, MachineSpec)>> {
+    validate_project_id(project)?;
+    ctx.validate()?;
+    // SECURITY: names come from API responses; only allow machine type characters
+    if names.iter().any(|n| !n.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')) {
+        return Err(anyhow!("Invalid machine type name in lookup"));
+    }
+    let filter = format!("name:({})", names.iter().cloned().collect::<Vec<_>>().join(","));
+
+    let output = timeout(
+        Duration::from_secs(30),
+        TokioCommand::new("gcloud")
+            .args(["compute", "machine-types", "list", "--project", project, "--format=json"])
+            .arg(format!("--filter={}", filter))
+            .args(ctx.args())
+            .output(),
+    )
+    .await
+    .map_err(|_| anyhow!("Timeout: gcloud machine-types list took longer than 30 seconds"))?
+    .map_err(|e| anyhow!("Failed to execute gcloud: {}", e))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("gcloud error: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    let raw: Vec<RawMachineType> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| anyhow!("Failed to parse machine types JSON: {}", e))?;
+    Ok(raw.into_iter().map(RawMachineType::into_zone_spec).collect())
+}
+
+/// Fill `cpu_count`/`memory_mb` of listed instances from the cache or the API
+///
+/// `fetch` is called at most once, with every name the cache could not
+/// answer. If it fails the offline guesses already on the instances stay.
+pub async fn refine_instances<F, Fut>(
+    instances: &mut [GcpInstance],
+    cache: &mut MachineTypeCache,
+    fetch: F,
+) -> Result<()>
+where
+    F: FnOnce(BTreeSet<String>) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<(String, MachineSpec)>>>,
+{
+    let missing: BTreeSet<String> = instances
+        .iter()
+        .filter(|i| parse_custom(&i.machine_type).is_none() && i.machine_type != "Unknown")
+        .filter(|i| cache.get(&i.zone, &i.machine_type).is_none())
+        .map(|i| i.machine_type.clone())
+        .collect();
+
+    if !missing.is_empty() {
+        match fetch(missing).await {
+            Ok(specs) => {
+                for (zone, spec) in specs {
+                    if validate_zone(&zone).is_ok() {
+                        cache.insert(&zone, spec);
+                    }
+                }
+                if let Err(e) = cache.save() {
+                    tracing::warn!(error = %e, "Could not write machine type cache");
+                }
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Machine type lookup failed, keeping offline estimates");
+            }
+        }
+    }
+
+    for instance in instances.iter_mut() {
+        if let Some(spec) = cache.get(&instance.zone, &instance.machine_type) {
+            instance.cpu_count = Some(spec.guest_cpus);
+            instance.memory_mb = Some(spec.memory_mb);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_custom_shapes() {
+        let n1 = parse_custom("custom-4-8192").unwrap();
+        assert_eq!((n1.guest_cpus, n1.memory_mb), (4, 8192));
+
+        let ext = parse_custom("n2-custom-8-32768-ext").unwrap();
+        assert_eq!((ext.guest_cpus, ext.memory_mb), (8, 32768));
+        assert_eq!(ext.source, SpecSource::CustomName);
+
+        let shared = parse_custom("e2-custom-medium-6144").unwrap();
+        assert_eq!((shared.guest_cpus, shared.memory_mb, shared.cpu_fraction), (2, 6144, Some(1.0)));
+
+        assert!(parse_custom("e2-standard-4").is_none());
+        assert!(parse_custom("n2-custom-x-1024").is_none());
+    }
+
+    #[test]
+    fn test_heuristic_table() {
+        let micro = heuristic_spec("e2-micro").unwrap();
+        assert_eq!((micro.guest_cpus, micro.cpu_fraction), (2, Some(0.25)));
+
+        assert_eq!(heuristic_spec("c3d-highmem-8").unwrap().memory_mb, 8 * 8192);
+        assert_eq!(heuristic_spec("n4-highcpu-16").unwrap().memory_mb, 16 * 2048);
+        assert_eq!(heuristic_spec("c2d-highmem-4").unwrap().memory_mb, 4 * 8192);
+
+        let a3 = heuristic_spec("a3-highgpu-8g").unwrap();
+        assert_eq!(a3.guest_cpus, 208);
+        assert_eq!(a3.accelerators[0].count, 8);
+
+        assert!(heuristic_spec("mystery").is_none());
+    }
+
+    fn instance(machine_type: &str) -> GcpInstance {
+        let spec = offline_spec(machine_type);
+        GcpInstance {
+            name: "vm".to_string(),
+            status: "RUNNING".to_string(),
+            zone: "us-central1-a".to_string(),
+            machine_type: machine_type.to_string(),
+            cpu_count: spec.as_ref().map(|s| s.guest_cpus),
+            memory_mb: spec.as_ref().map(|s| s.memory_mb),
+            disk_gb: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refine_uses_api_then_cache() {
+        let path = std::env::temp_dir().join(format!("lcc-machine-types-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut cache = MachineTypeCache::load(path.clone(), DEFAULT_CACHE_TTL);
+
+        let mut instances = vec![instance("c2-standard-8"), instance("n2-custom-2-4096")];
+        refine_instances(&mut instances, &mut cache, |names| async move {
+            assert_eq!(names.into_iter().collect::<Vec<_>>(), vec!["c2-standard-8".to_string()]);
+            Ok(vec![("us-central1-a".to_string(), MachineSpec::new("c2-standard-8", 8, 32768, SpecSource::Api))])
+        })
+        .await
+        .unwrap();
+        assert_eq!(instances[0].memory_mb, Some(32768));
+        assert_eq!(instances[1].memory_mb, Some(4096));
+
+        // Second pass is served from disk without calling the API
+        let mut reloaded = MachineTypeCache::load(path.clone(), DEFAULT_CACHE_TTL);
+        let mut again = vec![instance("c2-standard-8")];
+        refine_instances(&mut again, &mut reloaded, |_| async { Err(anyhow!("should not be called")) })
+            .await
+            .unwrap();
+        assert_eq!(again[0].cpu_count, Some(8));
+
+        // Expired entries are ignored
+        let expired = MachineTypeCache::load(path.clone(), Duration::ZERO);
+        assert!(expired.get("us-central1-a", "c2-standard-8").is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_parse_api_machine_type() {
+        let json = r#"{"name": "g2-standard-4", "zone": "us-central1-a", "guestCpus": 4, "memoryMb": 16384,
+                       "accelerators": [{"guestAcceleratorType": "nvidia-l4", "guestAcceleratorCount": 1}]}"#;
+        let raw: RawMachineType = serde_json::from_str(json).unwrap();
+        let (zone, spec) = raw.into_zone_spec();
+        assert_eq!(zone, "us-central1-a");
+        assert_eq!(spec.accelerators[0].accelerator_type, "nvidia-l4");
+        assert_eq!(spec.source, SpecSource::Api);
+    }
+}
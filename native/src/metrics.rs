@@ -0,0 +1,271 @@
+//! Tunnel metrics endpoint
+//!
+//! Renders the per-tunnel counters of [`tunnel::tunnel_activity`] in the
+//! Prometheus text format and can serve them on a local HTTP endpoint
+//! (`GET /metrics`), to see which tunnels are actually in use.
+//!
+//! Byte, session and connect-latency series only exist for native tunnels;
+//! gcloud tunnels report their established connections and liveness.
+
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+use crate::tunnel::{self, TunnelActivityReport};
+use crate::worker::{StopSignal, Worker};
+
+lazy_static! {
+    /// State: the address the endpoint listens on
+    static ref METRICS_SERVER: Worker<SocketAddr> = Worker::new("tunnel-metrics");
+}
+
+type Sample = fn(&TunnelActivityReport) -> Option<f64>;
+
+/// (name, type, help, value); `None` skips the tunnel for that series
+const FAMILIES: &[(&str, &str, &str, Sample)] = &[
+    ("lcc_tunnel_up", "gauge", "Whether the tunnel process or listener is running", |r| {
+        Some(if r.alive { 1.0 } else { 0.0 })
+    }),
+    ("lcc_tunnel_active_connections", "gauge", "Client connections currently open", |r| {
+        Some(r.activity.active_connections as f64)
+    }),
+    ("lcc_tunnel_connections_total", "counter", "Client connections accepted", |r| {
+        Some(r.activity.total_connections as f64)
+    }),
+    ("lcc_tunnel_sent_bytes_total", "counter", "Bytes sent to the instance", |r| {
+        r.native.then_some(r.activity.bytes_sent as f64)
+    }),
+    ("lcc_tunnel_received_bytes_total", "counter", "Bytes received from the instance", |r| {
+        r.native.then_some(r.activity.bytes_received as f64)
+    }),
+    ("lcc_tunnel_connect_failures_total", "counter", "IAP sessions that could not be opened", |r| {
+        r.native.then_some(r.activity.connect_failures as f64)
+    }),
+    ("lcc_tunnel_connect_latency_max_seconds", "gauge", "Slowest IAP session open", |r| {
+        r.native.then_some(r.activity.connect_latency_max_ms as f64 / 1000.0)
+    }),
+    ("lcc_tunnel_last_activity_timestamp_seconds", "gauge", "Unix time of the last byte or connection", |r| {
+        Some(r.activity.last_activity_unix_ms as f64 / 1000.0)
+    }),
+    ("lcc_tunnel_start_timestamp_seconds", "gauge", "Unix time the tunnel was created", |r| {
+        Some(r.activity.started_unix_ms as f64 / 1000.0)
+    }),
+];
+
+/// (name, help, sum, count) of summaries without quantiles
+const SUMMARIES: &[(&str, &str, Sample, Sample)] = &[(
+    "lcc_tunnel_connect_latency_seconds",
+    "Time spent opening IAP sessions",
+    |r| r.native.then_some(r.activity.connect_latency_total_ms as f64 / 1000.0),
+    |r| r.native.then_some(r.activity.connects as f64),
+)];
+
+/// Escape a label value (backslash, double quote, newline)
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn labels(report: &TunnelActivityReport) -> String {
+    let key = &report.key;
+    format!(
+        "account=\"{}\",configuration=\"{}\",project=\"{}\",zone=\"{}\",instance=\"{}\",remote_port=\"{}\",local_port=\"{}\",bind_address=\"{}\",backend=\"{}\"",
+        escape_label(key.account.as_deref().unwrap_or_default()),
+        escape_label(key.configuration.as_deref().unwrap_or_default()),
+        escape_label(&key.project),
+        escape_label(&key.zone),
+        escape_label(&key.instance),
+        key.remote_port,
+        report.local_port,
+        key.bind_address,
+        if report.native { "native" } else { "gcloud" },
+    )
+}
+
+/// Prometheus text exposition (format 0.0.4) of `reports`
+pub fn render_prometheus(reports: &[TunnelActivityReport]) -> String {
+    let mut out = String::new();
+    for (name, kind, help, sample) in FAMILIES {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for report in reports {
+            if let Some(value) = sample(report) {
+                let _ = writeln!(out, "{}{{{}}} {}", name, labels(report), value);
+            }
+        }
+    }
+    for (name, help, sum, count) in SUMMARIES {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} summary", name);
+        for report in reports {
+            if let (Some(sum), Some(count)) = (sum(report), count(report)) {
+                let labels = labels(report);
+                let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, sum);
+                let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, count);
+            }
+        }
+    }
+    out
+}
+
+/// Metrics of the tunnels registered in this process, ready to serve
+pub fn tunnel_metrics_text() -> Result<String> {
+    Ok(render_prometheus(&tunnel::tunnel_activity()?))
+}
+
+/// Serve `GET /metrics` on `address`, returning the bound address
+///
+/// Use a loopback address unless the metrics should be reachable from
+/// other machines. No-op returning the current address if already running.
+pub fn start_metrics_server(address: SocketAddr) -> Result<SocketAddr> {
+    if let Some(running) = metrics_server_address() {
+        return Ok(running);
+    }
+
+    let listener = TcpListener::bind(address)
+        .map_err(|e| anyhow!("Failed to listen for metrics on {}: {}", address, e))?;
+    let address = listener.local_addr()?;
+    listener.set_nonblocking(true)?;
+
+    if !METRICS_SERVER.start(address, move |stop| serve(listener, stop))? {
+        // Started concurrently by another caller; our listener is dropped
+        return metrics_server_address().ok_or_else(|| anyhow!("Metrics endpoint stopped while starting"));
+    }
+    tracing::info!(address = %address, "Tunnel metrics endpoint started");
+    Ok(address)
+}
+
+/// Stop the metrics endpoint and wait for it to exit
+pub fn stop_metrics_server() -> Result<()> {
+    METRICS_SERVER.stop()
+}
+
+/// Address of the running metrics endpoint
+pub fn metrics_server_address() -> Option<SocketAddr> {
+    METRICS_SERVER.with_state(|address| *address)
+}
+
+fn serve(listener: TcpListener, stop: StopSignal) {
+    while !stop.is_stopped() {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                if let Err(e) = handle_request(stream) {
+                    tracing::debug!(peer = %peer, error = %e, "Metrics request failed");
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                stop.sleep(Duration::from_millis(100));
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Metrics accept failed");
+                stop.sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+fn handle_request(mut stream: TcpStream) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+
+    // Only the request line matters; read until the end of the headers
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.lines().next().unwrap_or_default().split_whitespace();
+
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => match tunnel_metrics_text() {
+            Ok(text) => ("200 OK", "text/plain; version=0.0.4", text),
+            Err(e) => ("500 Internal Server Error", "text/plain", format!("{}\n", e)),
+        },
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Method not allowed\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::GcloudContext;
+    use crate::iap::ActivitySnapshot;
+    use crate::tunnel::TunnelKey;
+
+    fn report(instance: &str, native: bool) -> TunnelActivityReport {
+        TunnelActivityReport {
+            key: TunnelKey::new("my-project", "us-central1-a", instance, 5432),
+            local_port: 15432,
+            native,
+            alive: true,
+            activity: ActivitySnapshot {
+                bytes_sent: 2048,
+                active_connections: 1,
+                total_connections: 3,
+                connects: 3,
+                connect_latency_total_ms: 900,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_prometheus_rendering() {
+        let text = render_prometheus(&[report("db-1", true), report("db-\"2\"", false)]);
+        assert!(text.contains("# TYPE lcc_tunnel_connections_total counter\n"));
+        assert!(text.contains(
+            "lcc_tunnel_sent_bytes_total{account=\"\",configuration=\"\",project=\"my-project\",zone=\"us-central1-a\",\
+             instance=\"db-1\",remote_port=\"5432\",local_port=\"15432\",bind_address=\"127.0.0.1\",backend=\"native\"} 2048\n"
+        ));
+        assert!(text.contains("# TYPE lcc_tunnel_connect_latency_seconds summary\n"));
+        assert!(text.contains("lcc_tunnel_connect_latency_seconds_sum{") && text.contains("} 0.9\n"));
+        assert_eq!(text.matches("lcc_tunnel_connect_latency_seconds_count{").count(), 1);
+        // gcloud tunnels have no byte counters, and label values are escaped
+        assert_eq!(text.matches("lcc_tunnel_sent_bytes_total{").count(), 1);
+        assert!(text.contains("instance=\"db-\\\"2\\\"\""));
+        assert_eq!(text.matches("lcc_tunnel_up{").count(), 2);
+
+        // Account and configuration are separate identities
+        let mut as_config = report("db-3", false);
+        as_config.key = as_config.key.with_context(&GcloudContext::for_configuration("work"));
+        assert!(labels(&as_config).starts_with("account=\"\",configuration=\"work\","));
+    }
+
+    #[test]
+    fn test_metrics_endpoint() {
+        let address = start_metrics_server("127.0.0.1:0".parse().unwrap()).unwrap();
+        assert_eq!(metrics_server_address(), Some(address));
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("# TYPE lcc_tunnel_up gauge"));
+        assert!(get("/").starts_with("HTTP/1.1 404"));
+
+        stop_metrics_server().unwrap();
+        assert_eq!(metrics_server_address(), None);
+    }
+}
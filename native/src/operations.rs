@@ -0,0 +1,315 @@
+//! Compute Engine zone operations
+//!
+//! Lifecycle calls on the REST API (start, stop, reset...) return an
+//! Operation as soon as the request is accepted. This module polls that
+//! operation until it is DONE, with exponential backoff, reporting progress
+//! on a channel and turning operation errors into readable messages.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Compute Engine Operation resource (fields we use)
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ZoneOperation {
+    pub name: String,
+    #[serde(default)]
+    pub operation_type: Option<String>,
+    #[serde(default)]
+    pub target_link: Option<String>,
+    /// PENDING, RUNNING or DONE
+    #[serde(default)]
+    pub status: String,
+    /// 0-100, only a rough indication for most operation types
+    #[serde(default)]
+    pub progress: Option<u8>,
+    #[serde(default)]
+    pub error: Option<OperationErrors>,
+    #[serde(default)]
+    pub http_error_status_code: Option<u16>,
+    #[serde(default)]
+    pub http_error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct OperationErrors {
+    #[serde(default)]
+    pub errors: Vec<OperationError>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct OperationError {
+    #[serde(default)]
+    pub code: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+impl ZoneOperation {
+    pub fn is_done(&self) -> bool {
+        self.status == "DONE"
+    }
+
+    /// Error carried by a finished operation, if any
+    pub fn failure(&self) -> Option<String> {
+        let errors: Vec<String> = self
+            .error
+            .as_ref()
+            .map(|e| {
+                e.errors
+                    .iter()
+                    .map(|err| format!("{}: {}", err.code, err.message))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if !errors.is_empty() {
+            return Some(errors.join("; "));
+        }
+        match (self.http_error_status_code, &self.http_error_message) {
+            (Some(code), Some(msg)) if code >= 400 => Some(format!("HTTP {}: {}", code, msg)),
+            _ => None,
+        }
+    }
+}
+
+/// Progress update sent on every poll
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct OperationProgress {
+    pub operation: String,
+    pub status: String,
+    pub progress: u8,
+    pub elapsed_ms: u64,
+}
+
+/// Polling tuning
+#[derive(Debug, Clone)]
+pub struct OperationWaitConfig {
+    /// Give up after this long
+    pub timeout: Duration,
+    /// Delay before the first poll
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between polls
+    pub max_backoff: Duration,
+}
+
+impl Default for OperationWaitConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(300),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Poll `fetch` until the operation is DONE
+///
+/// `fetch` performs one zoneOperations.get; progress is sent to `progress`
+/// (if given) after every poll. Returns the finished operation, or an error
+/// if it failed, timed out or reported an unknown status.
+pub async fn wait_for_operation<F, Fut>(
+    initial: ZoneOperation,
+    config: &OperationWaitConfig,
+    progress: Option<&mpsc::UnboundedSender<OperationProgress>>,
+    mut fetch: F,
+) -> Result<ZoneOperation>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<ZoneOperation>>,
+{
+    let start = Instant::now();
+    let mut backoff = config.initial_backoff;
+    let mut operation = initial;
+
+    loop {
+        if let Some(tx) = progress {
+            let _ = tx.send(OperationProgress {
+                operation: operation.name.clone(),
+                status: operation.status.clone(),
+                progress: operation.progress.unwrap_or(0).min(100),
+                elapsed_ms: start.elapsed().as_millis() as u64,
+            });
+        }
+
+        match operation.status.as_str() {
+            "DONE" => {
+                if let Some(error) = operation.failure() {
+                    return Err(anyhow!("Operation {} failed: {}", operation.name, error));
+                }
+                return Ok(operation);
+            }
+            "PENDING" | "RUNNING" => {
+                tracing::debug!(
+                    operation = %operation.name,
+                    status = %operation.status,
+                    progress = ?operation.progress,
+                    "Operation in progress"
+                );
+            }
+            other => {
+                return Err(anyhow!("Unexpected status '{}' for operation {}", other, operation.name));
+            }
+        }
+
+        if start.elapsed() + backoff > config.timeout {
+            return Err(anyhow!(
+                "Operation {} did not finish within {:?} (last status: {})",
+                operation.name,
+                config.timeout,
+                operation.status
+            ));
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(config.max_backoff);
+
+        operation = fetch(operation.name.clone()).await?;
+    }
+}
+
+/// An operation being polled in the background
+///
+/// Await it for the final result; read `progress` for updates. The progress
+/// channel closes once polling ends.
+pub struct TrackedOperation {
+    pub progress: mpsc::UnboundedReceiver<OperationProgress>,
+    result: JoinHandle<Result<ZoneOperation>>,
+}
+
+impl TrackedOperation {
+    /// Start polling on the current tokio runtime
+    pub fn spawn<F, Fut>(initial: ZoneOperation, config: OperationWaitConfig, fetch: F) -> Self
+    where
+        F: FnMut(String) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<ZoneOperation>> + Send,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let result = tokio::spawn(async move {
+            wait_for_operation(initial, &config, Some(&tx), fetch).await
+        });
+        Self { progress: rx, result }
+    }
+}
+
+impl Future for TrackedOperation {
+    type Output = Result<ZoneOperation>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.result)
+            .poll(cx)
+            .map(|joined| joined.map_err(|e| anyhow!("Operation task failed: {}", e))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn op(status: &str, progress: u8) -> ZoneOperation {
+        ZoneOperation {
+            name: "operation-123".to_string(),
+            status: status.to_string(),
+            progress: Some(progress),
+            ..Default::default()
+        }
+    }
+
+    fn fast_config() -> OperationWaitConfig {
+        OperationWaitConfig {
+            timeout: Duration::from_secs(5),
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+        }
+    }
+
+    /// fetch closure that replays a fixed sequence of states
+    fn replay(states: Vec<ZoneOperation>) -> impl FnMut(String) -> std::future::Ready<Result<ZoneOperation>> {
+        let states = Arc::new(Mutex::new(states.into_iter()));
+        move |_name| {
+            let next = states.lock().unwrap().next().ok_or_else(|| anyhow!("no more states"));
+            std::future::ready(next)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tracked_operation_reports_progress() {
+        let mut tracked = TrackedOperation::spawn(
+            op("PENDING", 0),
+            fast_config(),
+            replay(vec![op("RUNNING", 50), op("DONE", 100)]),
+        );
+
+        let mut seen = Vec::new();
+        while let Some(update) = tracked.progress.recv().await {
+            seen.push((update.status, update.progress));
+        }
+        let done = tracked.await.unwrap();
+
+        assert!(done.is_done());
+        assert_eq!(
+            seen,
+            vec![
+                ("PENDING".to_string(), 0),
+                ("RUNNING".to_string(), 50),
+                ("DONE".to_string(), 100),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_operation_error_is_surfaced() {
+        let mut failed = op("DONE", 100);
+        failed.error = Some(OperationErrors {
+            errors: vec![OperationError {
+                code: "ZONE_RESOURCE_POOL_EXHAUSTED".to_string(),
+                message: "The zone does not have enough resources".to_string(),
+            }],
+        });
+
+        let err = wait_for_operation(op("RUNNING", 10), &fast_config(), None, replay(vec![failed]))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("ZONE_RESOURCE_POOL_EXHAUSTED"));
+    }
+
+    #[tokio::test]
+    async fn test_operation_timeout() {
+        let config = OperationWaitConfig {
+            timeout: Duration::from_millis(20),
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(5),
+        };
+        let pending = std::iter::repeat_n(op("RUNNING", 10), 100).collect();
+        let err = wait_for_operation(op("RUNNING", 10), &config, None, replay(pending))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("did not finish"));
+    }
+
+    #[test]
+    fn test_parse_rest_operation() {
+        let json = r#"{
+            "kind": "compute#operation",
+            "name": "operation-1700000000000-abc",
+            "operationType": "start",
+            "targetLink": "https://www.googleapis.com/compute/v1/projects/p/zones/z/instances/vm",
+            "status": "DONE",
+            "progress": 100,
+            "httpErrorStatusCode": 400,
+            "httpErrorMessage": "BAD REQUEST"
+        }"#;
+        let parsed: ZoneOperation = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.operation_type.as_deref(), Some("start"));
+        assert_eq!(parsed.failure().as_deref(), Some("HTTP 400: BAD REQUEST"));
+    }
+}
@@ -0,0 +1,137 @@
+//! Paginated listing
+//!
+//! Resource Manager and Compute list calls return at most one page per
+//! request plus a `nextPageToken`. [`collect_pages`] follows the tokens,
+//! forwarding every page on a channel as it arrives so the UI can render
+//! partial results while large organisations are still loading.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Receives each page as soon as it is fetched
+pub type PageSender<T> = mpsc::UnboundedSender<Vec<T>>;
+
+/// Listing tuning shared by the CLI and REST paths
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListOptions {
+    /// Items requested per page (`pageSize` / `maxResults` / `--page-size`)
+    pub page_size: u32,
+    /// Budget for the whole listing, all pages included
+    pub timeout: Duration,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            page_size: 500,
+            timeout: Duration::from_secs(120),
+        }
+    }
+}
+
+/// One page of a list response
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_page_token: Option<String>,
+}
+
+/// Fetch pages until there is no `nextPageToken`
+///
+/// `fetch` receives the token of the page to load (`None` for the first).
+/// Every non-empty page is sent to `pages` before the next request is made;
+/// a dropped receiver does not stop the listing.
+pub async fn collect_pages<T, F, Fut>(
+    options: &ListOptions,
+    pages: Option<&PageSender<T>>,
+    mut fetch: F,
+) -> Result<Vec<T>>
+where
+    T: Clone,
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Page<T>>>,
+{
+    let listing = async {
+        let mut all = Vec::new();
+        let mut seen_tokens = HashSet::new();
+        let mut token: Option<String> = None;
+
+        loop {
+            let page = fetch(token.clone()).await?;
+            tracing::debug!(items = page.items.len(), more = page.next_page_token.is_some(), "Fetched page");
+
+            if let Some(tx) = pages {
+                if !page.items.is_empty() {
+                    let _ = tx.send(page.items.clone());
+                }
+            }
+            all.extend(page.items);
+
+            match page.next_page_token.filter(|t| !t.is_empty()) {
+                Some(next) => {
+                    // A repeated token would loop forever
+                    if !seen_tokens.insert(next.clone()) {
+                        return Err(anyhow!("API returned the same page token twice"));
+                    }
+                    token = Some(next);
+                }
+                None => return Ok(all),
+            }
+        }
+    };
+
+    tokio::time::timeout(options.timeout, listing)
+        .await
+        .map_err(|_| anyhow!("Timeout: listing took longer than {:?}", options.timeout))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages_of(pages: Vec<Vec<u32>>) -> impl FnMut(Option<String>) -> std::future::Ready<Result<Page<u32>>> {
+        move |token| {
+            let index: usize = token.map(|t| t.parse().unwrap()).unwrap_or(0);
+            let next = (index + 1 < pages.len()).then(|| (index + 1).to_string());
+            std::future::ready(Ok(Page { items: pages[index].clone(), next_page_token: next }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect_pages_streams_every_page() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let all = collect_pages(
+            &ListOptions::default(),
+            Some(&tx),
+            pages_of(vec![vec![1, 2], vec![], vec![3]]),
+        )
+        .await
+        .unwrap();
+        drop(tx);
+
+        assert_eq!(all, vec![1, 2, 3]);
+        let mut streamed = Vec::new();
+        while let Some(page) = rx.recv().await {
+            streamed.push(page);
+        }
+        assert_eq!(streamed, vec![vec![1, 2], vec![3]]);
+    }
+
+    #[tokio::test]
+    async fn test_collect_pages_rejects_token_loop_and_timeout() {
+        let looping = |_token| std::future::ready(Ok(Page { items: vec![1u32], next_page_token: Some("same".to_string()) }));
+        assert!(collect_pages(&ListOptions::default(), None, looping).await.is_err());
+
+        let options = ListOptions { page_size: 10, timeout: Duration::from_millis(10) };
+        let slow = |_token| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(Page::<u32> { items: vec![], next_page_token: None })
+        };
+        let err = collect_pages(&options, None, slow).await.unwrap_err().to_string();
+        assert!(err.contains("Timeout"));
+    }
+}
@@ -0,0 +1,32 @@
+//! Shared tokio runtime
+//!
+//! The REST backend shares one `reqwest` client so connections are pooled
+//! across calls, but a pooled connection is driven by a task on the runtime
+//! that opened it and dies with that runtime. Sync wrappers, background
+//! workers and tunnel listeners therefore run their async work on this one
+//! process-wide runtime instead of building and dropping their own.
+
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use std::future::Future;
+use tokio::runtime::Runtime;
+
+lazy_static! {
+    static ref RUNTIME: std::result::Result<Runtime, String> = tokio::runtime::Builder::new_multi_thread()
+        .thread_name("lcc-runtime")
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string());
+}
+
+/// The process-wide runtime, created on first use
+pub fn runtime() -> Result<&'static Runtime> {
+    RUNTIME.as_ref().map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))
+}
+
+/// Run one async library call to completion from sync code
+///
+/// Must not be called from inside an async task.
+pub fn block_on<T>(future: impl Future<Output = Result<T>>) -> Result<T> {
+    runtime()?.block_on(future)
+}
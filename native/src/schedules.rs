@@ -0,0 +1,851 @@
+//! Scheduled start/stop windows
+//!
+//! A schedule starts and/or stops a set of instances (explicit list or
+//! label selector, as in `bulk.rs`) at times given by five-field cron
+//! expressions evaluated in an IANA time zone, e.g. "start `0 8 * * 1-5`,
+//! stop `0 20 * * 1-5` in Europe/Madrid".
+//!
+//! Schedules are persisted as JSON in the config directory and evaluated by
+//! a background thread while the app (or `lcc schedule run`) is up. Firings
+//! missed while nothing was running are caught up only within a short grace
+//! window, so waking a laptop at noon doesn't replay the morning. A schedule
+//! on fixed instances can also be pushed to Compute Engine as an instance
+//! schedule resource policy, which keeps working with the connector closed.
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::accounts::GcloudContext;
+use crate::backend::{backend_for, ApiMethod, CloudBackend, InstanceAction};
+use crate::bulk::{run_bulk_action, BulkOptions, BulkTarget, CancelHandle};
+use crate::gcloud;
+use crate::runtime;
+use crate::validation::{validate_instance_name, validate_project_id, validate_schedule_id, validate_zone};
+use crate::worker::{EventBus, StopSignal, Worker};
+
+// ==========================================
+// Cron expressions
+// ==========================================
+
+const MONTH_NAMES: &[&str] = &["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const WEEKDAY_NAMES: &[&str] = &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// Four years and five days: enough to reach the next Feb 29
+const MAX_SEARCH_DAYS: u32 = 4 * 366 + 5;
+
+/// `minute hour day-of-month month day-of-week`, as in crontab(5)
+///
+/// Fields accept `*`, numbers, ranges `a-b`, steps `*/n` / `a-b/n` and
+/// comma lists; months and weekdays also take names (`JAN`, `MON`), and
+/// Sunday is 0 or 7. As in Vixie cron, a day matching either day field
+/// fires when neither starts with `*`; otherwise it must match both.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronExpr {
+    /// Normalised text (single spaces), also what is sent to Compute
+    source: String,
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Bitmask of the values selected by one field
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], name_offset: u32) -> Result<u64> {
+    let value = |text: &str| -> Result<u32> {
+        if let Ok(n) = text.parse::<u32>() {
+            if (min..=max).contains(&n) {
+                return Ok(n);
+            }
+            return Err(anyhow!("Value {} out of range {}-{} in '{}'", n, min, max, field));
+        }
+        names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(text))
+            .map(|i| i as u32 + name_offset)
+            .ok_or_else(|| anyhow!("Invalid value '{}' in cron field '{}'", text, field))
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| anyhow!("Invalid step in '{}'", part))?;
+                if step == 0 {
+                    return Err(anyhow!("Step cannot be 0 in '{}'", part));
+                }
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                // `5/15` means every 15 starting at 5
+                None if step.is_some() => (value(range)?, max),
+                None => {
+                    let v = value(range)?;
+                    (v, v)
+                }
+            },
+        };
+        if first > last {
+            return Err(anyhow!("Range {} is backwards", range));
+        }
+        for v in (first..=last).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(anyhow!(
+                "Cron expression '{}' must have 5 fields: minute hour day-of-month month day-of-week",
+                expr
+            ));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7, WEEKDAY_NAMES, 0)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & 0x7f;
+        }
+        Ok(Self {
+            source: fields.join(" "),
+            minutes: parse_field(minute, 0, 59, &[], 0)?,
+            hours: parse_field(hour, 0, 23, &[], 0)? as u32,
+            days: parse_field(day, 1, 31, &[], 0)? as u32,
+            months: parse_field(month, 1, 12, MONTH_NAMES, 1)? as u16,
+            weekdays: weekdays as u8,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+
+    /// First firing strictly after `after`, in `after`'s time zone
+    ///
+    /// Local times skipped by a DST change don't fire; repeated ones fire once.
+    pub fn next_after<T: TimeZone>(&self, after: &DateTime<T>) -> Option<DateTime<T>> {
+        let tz = after.timezone();
+        let start = after.naive_local();
+        let mut date = start.date();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_date(date) {
+                for hour in (0..24).filter(|h| self.hours & (1 << h) != 0) {
+                    if date == start.date() && hour < start.hour() {
+                        continue;
+                    }
+                    for minute in (0..60).filter(|m| self.minutes & (1 << m) != 0) {
+                        let Some(at) = date.and_hms_opt(hour, minute, 0).and_then(|naive| tz.from_local_datetime(&naive).earliest()) else {
+                            continue;
+                        };
+                        if at > *after {
+                            return Some(at);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    /// Latest firing in `(from, to]`
+    fn last_in<T: TimeZone>(&self, from: &DateTime<T>, to: &DateTime<T>) -> Option<DateTime<T>> {
+        let mut last = None;
+        let mut cursor = from.clone();
+        while let Some(at) = self.next_after(&cursor) {
+            if at > *to {
+                break;
+            }
+            cursor = at.clone();
+            last = Some(at);
+        }
+        last
+    }
+}
+
+impl TryFrom<String> for CronExpr {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+impl From<CronExpr> for String {
+    fn from(value: CronExpr) -> Self {
+        value.source
+    }
+}
+
+// ==========================================
+// Schedules and their store
+// ==========================================
+
+/// Start and/or stop times for a set of instances
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    #[serde(default)]
+    pub description: String,
+    pub target: BulkTarget,
+    /// IANA name, e.g. `Europe/Madrid`
+    pub time_zone: String,
+    pub start: Option<CronExpr>,
+    pub stop: Option<CronExpr>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// End of the last window the scheduler evaluated (managed by the scheduler)
+    #[serde(default)]
+    pub last_evaluated: Option<DateTime<Utc>>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// One upcoming action, for previews
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduledRun {
+    /// RFC 3339 in the schedule's time zone
+    pub at: String,
+    pub action: InstanceAction,
+}
+
+impl Schedule {
+    /// SECURITY: Must be called before the schedule is stored or synced
+    pub fn validate(&self) -> Result<()> {
+        validate_schedule_id(&self.id)?;
+        self.tz()?;
+        if self.start.is_none() && self.stop.is_none() {
+            return Err(anyhow!("Schedule '{}' needs a start or a stop time", self.id));
+        }
+        match &self.target {
+            BulkTarget::Instances(instances) => {
+                if instances.is_empty() {
+                    return Err(anyhow!("Schedule '{}' has no instances", self.id));
+                }
+                for i in instances {
+                    validate_project_id(&i.project)?;
+                    validate_zone(&i.zone)?;
+                    validate_instance_name(&i.instance)?;
+                }
+            }
+            BulkTarget::Selector { projects, filter } => {
+                for project in projects.iter().flatten() {
+                    validate_project_id(project)?;
+                }
+                if filter.is_empty() {
+                    return Err(anyhow!("Schedule '{}' selector matches every instance; add a label or name filter", self.id));
+                }
+                filter.validate()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn tz(&self) -> Result<Tz> {
+        self.time_zone
+            .parse()
+            .map_err(|_| anyhow!("Unknown time zone '{}'. Expected an IANA name like Europe/Madrid", self.time_zone))
+    }
+
+    fn actions(&self) -> impl Iterator<Item = (InstanceAction, &CronExpr)> {
+        [(InstanceAction::Start, &self.start), (InstanceAction::Stop, &self.stop)]
+            .into_iter()
+            .filter_map(|(action, expr)| expr.as_ref().map(|e| (action, e)))
+    }
+
+    /// The action due in `(from, to]`, if any
+    ///
+    /// When both fired (a long catch-up window), the later one wins: it is
+    /// the state the instances should be in now.
+    pub fn due_action(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Option<(InstanceAction, DateTime<Utc>)>> {
+        let tz = self.tz()?;
+        let (from, to) = (from.with_timezone(&tz), to.with_timezone(&tz));
+        Ok(self
+            .actions()
+            .filter_map(|(action, expr)| expr.last_in(&from, &to).map(|at| (action, at.with_timezone(&Utc))))
+            .max_by_key(|(_, at)| *at))
+    }
+
+    /// The next `count` actions after `after`
+    pub fn upcoming(&self, after: DateTime<Utc>, count: usize) -> Result<Vec<ScheduledRun>> {
+        let tz = self.tz()?;
+        let mut runs = Vec::new();
+        for (action, expr) in self.actions() {
+            let mut cursor = after.with_timezone(&tz);
+            for _ in 0..count {
+                match expr.next_after(&cursor) {
+                    Some(at) => {
+                        runs.push((at, action));
+                        cursor = at;
+                    }
+                    None => break,
+                }
+            }
+        }
+        runs.sort_by_key(|(at, _)| *at);
+        Ok(runs
+            .into_iter()
+            .take(count)
+            .map(|(at, action)| ScheduledRun { at: at.to_rfc3339(), action })
+            .collect())
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreFile {
+    #[serde(default)]
+    schedules: Vec<Schedule>,
+}
+
+/// Schedules persisted in one JSON file
+pub struct ScheduleStore {
+    path: PathBuf,
+    data: StoreFile,
+}
+
+lazy_static! {
+    /// Serialises read-modify-write of the store between the UI and the scheduler
+    static ref STORE_LOCK: Mutex<()> = Mutex::new(());
+    static ref SCHEDULER: Worker = Worker::new("instance-scheduler");
+    static ref EVENTS: EventBus<ScheduleEvent> = EventBus::new("Schedule event");
+}
+
+impl ScheduleStore {
+    /// `<config dir>/linux_cloud_connector/schedules.json`
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or_else(|| anyhow!("Could not determine config directory"))?
+            .join("linux_cloud_connector")
+            .join("schedules.json"))
+    }
+
+    /// Load from `path`; a missing file is an empty store
+    ///
+    /// A corrupt file is an error rather than silently dropping schedules.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoreFile::default(),
+            Err(e) => return Err(anyhow!("Failed to read {}: {}", path.display(), e)),
+        };
+        Ok(Self { path: path.to_path_buf(), data })
+    }
+
+    pub fn schedules(&self) -> &[Schedule] {
+        &self.data.schedules
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Schedule> {
+        self.data.schedules.iter().find(|s| s.id == id)
+    }
+
+    /// Add or replace a schedule, keeping the scheduler's bookkeeping
+    pub fn upsert(&mut self, mut schedule: Schedule) -> Result<()> {
+        schedule.validate()?;
+        match self.data.schedules.iter_mut().find(|s| s.id == schedule.id) {
+            Some(existing) => {
+                schedule.last_evaluated = existing.last_evaluated;
+                *existing = schedule;
+            }
+            None => {
+                self.data.schedules.push(schedule);
+                self.data.schedules.sort_by(|a, b| a.id.cmp(&b.id));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.data.schedules.len();
+        self.data.schedules.retain(|s| s.id != id);
+        self.data.schedules.len() != before
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&self.data)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn with_store<T>(path: &Path, f: impl FnOnce(&mut ScheduleStore) -> Result<T>) -> Result<T> {
+    let _guard = STORE_LOCK.lock().map_err(|_| anyhow!("Schedule store lock poisoned"))?;
+    let mut store = ScheduleStore::load(path)?;
+    let result = f(&mut store)?;
+    store.save()?;
+    Ok(result)
+}
+
+/// All saved schedules
+pub fn list_schedules() -> Result<Vec<Schedule>> {
+    let _guard = STORE_LOCK.lock().map_err(|_| anyhow!("Schedule store lock poisoned"))?;
+    Ok(ScheduleStore::load(&ScheduleStore::default_path()?)?.data.schedules)
+}
+
+/// Create or update a schedule
+pub fn save_schedule(schedule: Schedule) -> Result<()> {
+    with_store(&ScheduleStore::default_path()?, |store| store.upsert(schedule))
+}
+
+/// Delete a schedule; false if there was none with that id
+pub fn delete_schedule(id: &str) -> Result<bool> {
+    with_store(&ScheduleStore::default_path()?, |store| Ok(store.remove(id)))
+}
+
+// ==========================================
+// Evaluation
+// ==========================================
+
+/// Scheduler tuning
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// How often schedules are evaluated
+    pub check_interval: Duration,
+    /// Firings older than this when first noticed are skipped
+    pub catch_up: Duration,
+    pub bulk: BulkOptions,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(30),
+            catch_up: Duration::from_secs(15 * 60),
+            bulk: BulkOptions::default(),
+        }
+    }
+}
+
+/// An action to run now
+#[derive(Debug, Clone, PartialEq)]
+pub struct DueRun {
+    pub schedule_id: String,
+    pub action: InstanceAction,
+    pub scheduled_for: DateTime<Utc>,
+    pub target: BulkTarget,
+}
+
+/// Events emitted as schedules fire
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduleEvent {
+    Ran {
+        schedule_id: String,
+        action: InstanceAction,
+        scheduled_for: String,
+        succeeded: usize,
+        failed: usize,
+        skipped_projects: Vec<String>,
+    },
+    Failed {
+        schedule_id: String,
+        action: InstanceAction,
+        error: String,
+    },
+}
+
+/// Actions due at `now`, marking every enabled schedule as evaluated
+///
+/// A schedule never evaluated before starts its window at `now`.
+pub fn collect_due(schedules: &mut [Schedule], now: DateTime<Utc>, catch_up: Duration) -> Vec<DueRun> {
+    let earliest = now - chrono::Duration::from_std(catch_up).unwrap_or_default();
+    let mut due = Vec::new();
+    for schedule in schedules.iter_mut().filter(|s| s.enabled) {
+        let from = schedule.last_evaluated.unwrap_or(now).max(earliest);
+        match schedule.due_action(from, now) {
+            Ok(Some((action, scheduled_for))) => due.push(DueRun {
+                schedule_id: schedule.id.clone(),
+                action,
+                scheduled_for,
+                target: schedule.target.clone(),
+            }),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(schedule = %schedule.id, error = %e, "Skipping invalid schedule"),
+        }
+        schedule.last_evaluated = Some(now);
+    }
+    due
+}
+
+/// Evaluate the store at `path` once and run whatever is due
+pub async fn run_due_schedules(
+    backend: &dyn CloudBackend,
+    path: &Path,
+    now: DateTime<Utc>,
+    config: &SchedulerConfig,
+) -> Result<Vec<ScheduleEvent>> {
+    let due = with_store(path, |store| Ok(collect_due(&mut store.data.schedules, now, config.catch_up)))?;
+
+    let mut events = Vec::new();
+    for run in due {
+        tracing::info!(
+            schedule = %run.schedule_id,
+            action = run.action.verb(),
+            scheduled_for = %run.scheduled_for,
+            "Running scheduled action"
+        );
+        let event = match run_bulk_action(backend, run.action, run.target, &config.bulk, &CancelHandle::new(), None).await {
+            Ok(report) => ScheduleEvent::Ran {
+                schedule_id: run.schedule_id,
+                action: run.action,
+                scheduled_for: run.scheduled_for.to_rfc3339(),
+                succeeded: report.succeeded(),
+                failed: report.failed(),
+                skipped_projects: report.skipped_projects,
+            },
+            Err(e) => ScheduleEvent::Failed { schedule_id: run.schedule_id, action: run.action, error: e.to_string() },
+        };
+        events.push(event);
+    }
+    Ok(events)
+}
+
+/// Receive schedule events; the channel closes when the receiver is dropped
+pub fn subscribe_schedule_events() -> Receiver<ScheduleEvent> {
+    EVENTS.subscribe()
+}
+
+fn emit(event: ScheduleEvent) {
+    EVENTS.emit(event);
+}
+
+/// Start evaluating the saved schedules in the background (no-op if running)
+pub fn start_scheduler(method: ApiMethod, context: GcloudContext, config: SchedulerConfig) -> Result<()> {
+    context.validate()?;
+    let path = ScheduleStore::default_path()?;
+    SCHEDULER.start((), move |stop| run(method, context, path, config, stop))?;
+    Ok(())
+}
+
+/// Stop the scheduler and wait for it to exit
+pub fn stop_scheduler() -> Result<()> {
+    SCHEDULER.stop()
+}
+
+/// Whether the scheduler thread is running
+pub fn is_scheduler_running() -> bool {
+    SCHEDULER.is_running()
+}
+
+fn run(method: ApiMethod, context: GcloudContext, path: PathBuf, config: SchedulerConfig, stop: StopSignal) {
+    let rt = match runtime::runtime() {
+        Ok(rt) => rt,
+        Err(e) => {
+            tracing::error!(error = %e, "Scheduler has no runtime");
+            return;
+        }
+    };
+
+    let mut backend: Option<Box<dyn CloudBackend>> = None;
+    while !stop.is_stopped() {
+        rt.block_on(async {
+            if backend.is_none() {
+                match backend_for(method, &context).await {
+                    Ok(b) => backend = Some(b),
+                    Err(e) => tracing::warn!(error = %e, "Scheduler could not create backend"),
+                }
+            }
+            if let Some(backend) = &backend {
+                match run_due_schedules(backend.as_ref(), &path, Utc::now(), &config).await {
+                    Ok(events) => events.into_iter().for_each(emit),
+                    Err(e) => tracing::error!(error = %e, "Scheduler could not evaluate schedules"),
+                }
+            }
+        });
+
+        stop.sleep(config.check_interval);
+    }
+}
+
+// ==========================================
+// Compute Engine instance schedules
+// ==========================================
+
+/// Result of pushing a schedule to Compute Engine
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicySync {
+    /// `project/region/name` of every policy used
+    pub policies: Vec<String>,
+    /// Policies that already matched the schedule
+    pub reused: Vec<String>,
+    /// Policies recreated because the schedule had changed
+    pub updated: Vec<String>,
+    pub instances_attached: usize,
+}
+
+/// The policy settings that mirror `schedule`
+fn policy_for(schedule: &Schedule) -> gcloud::InstanceSchedulePolicy {
+    gcloud::InstanceSchedulePolicy {
+        vm_start_schedule: schedule.start.as_ref().map(|c| c.as_str().to_string()),
+        vm_stop_schedule: schedule.stop.as_ref().map(|c| c.as_str().to_string()),
+        time_zone: schedule.time_zone.clone(),
+    }
+}
+
+/// Whether an existing policy already runs `wanted` (Compute may re-space the cron text)
+fn policy_matches(existing: &gcloud::InstanceSchedulePolicy, wanted: &gcloud::InstanceSchedulePolicy) -> bool {
+    let cron = |c: &Option<String>| c.as_deref().map(|c| c.split_whitespace().collect::<Vec<_>>().join(" "));
+    cron(&existing.vm_start_schedule) == cron(&wanted.vm_start_schedule)
+        && cron(&existing.vm_stop_schedule) == cron(&wanted.vm_stop_schedule)
+        && existing.time_zone == wanted.time_zone
+}
+
+/// Mirror a schedule as `lcc-<id>` instance schedule policies
+///
+/// Resource policies are regional and attach to fixed instances, so only
+/// schedules with an explicit instance list can be synced. Policies can't
+/// be edited in place: one that no longer matches the schedule is detached
+/// from its instances, deleted and created again.
+pub async fn sync_to_resource_policy(context: &GcloudContext, schedule: &Schedule) -> Result<PolicySync> {
+    schedule.validate()?;
+    let BulkTarget::Instances(instances) = &schedule.target else {
+        return Err(anyhow!(
+            "Only schedules on explicit instances can be synced: Compute Engine policies don't follow label selectors"
+        ));
+    };
+    let name = format!("lcc-{}", schedule.id);
+
+    let regions: BTreeSet<(String, String)> = instances
+        .iter()
+        .map(|i| (i.project.clone(), i.zone.rsplit_once('-').map(|(r, _)| r.to_string()).unwrap_or_default()))
+        .collect();
+    let wanted = policy_for(schedule);
+    let mut sync = PolicySync { policies: Vec::new(), reused: Vec::new(), updated: Vec::new(), instances_attached: 0 };
+    // (project, zone, instance) that already carry the policy
+    let mut attached: BTreeSet<(String, String, String)> = BTreeSet::new();
+    for (project, region) in regions {
+        let path = format!("{}/{}/{}", project, region, name);
+        let existing = gcloud::describe_instance_schedule_policy_async(context, &project, &region, &name).await?;
+        match existing {
+            Some(existing) if policy_matches(&existing, &wanted) => {
+                for i in gcloud::instances_with_resource_policy_async(context, &project, &region, &name).await? {
+                    attached.insert((project.clone(), i.zone, i.name));
+                }
+                sync.reused.push(path.clone());
+            }
+            Some(_) => {
+                tracing::info!(policy = %path, "Schedule changed, recreating its resource policy");
+                for i in gcloud::instances_with_resource_policy_async(context, &project, &region, &name).await? {
+                    gcloud::remove_resource_policy_async(context, &project, &i.zone, &i.name, &name).await?;
+                }
+                gcloud::delete_resource_policy_async(context, &project, &region, &name).await?;
+                create_policy(context, &project, &region, &name, &wanted).await?;
+                sync.updated.push(path.clone());
+            }
+            None => create_policy(context, &project, &region, &name, &wanted).await?,
+        }
+        sync.policies.push(path);
+    }
+
+    for i in instances {
+        if !attached.contains(&(i.project.clone(), i.zone.clone(), i.instance.clone())) {
+            gcloud::add_resource_policy_async(context, &i.project, &i.zone, &i.instance, &name).await?;
+        }
+    }
+    sync.instances_attached = instances.len();
+    Ok(sync)
+}
+
+async fn create_policy(
+    context: &GcloudContext,
+    project: &str,
+    region: &str,
+    name: &str,
+    policy: &gcloud::InstanceSchedulePolicy,
+) -> Result<()> {
+    let created = gcloud::create_instance_schedule_policy_async(
+        context,
+        project,
+        region,
+        name,
+        policy.vm_start_schedule.as_deref(),
+        policy.vm_stop_schedule.as_deref(),
+        &policy.time_zone,
+    )
+    .await?;
+    if !created {
+        // Someone else created it between describe and create
+        return Err(anyhow!("Resource policy {} appeared in {}/{} during the sync; run it again", name, project, region));
+    }
+    Ok(())
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn sync_schedule_to_compute(context: &GcloudContext, id: &str) -> Result<PolicySync> {
+    let schedule = list_schedules()?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| anyhow!("No schedule '{}'", id))?;
+    runtime::block_on(sync_to_resource_policy(context, &schedule))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{test_instance, test_target, FakeBackend};
+
+    fn madrid(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Tz> {
+        chrono_tz::Europe::Madrid.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn office_hours() -> Schedule {
+        Schedule {
+            id: "office-hours".to_string(),
+            description: String::new(),
+            target: BulkTarget::Instances(vec![test_target("dev-1")]),
+            time_zone: "Europe/Madrid".to_string(),
+            start: Some(CronExpr::parse("0 8 * * MON-FRI").unwrap()),
+            stop: Some(CronExpr::parse("0 20 * * 1-5").unwrap()),
+            enabled: true,
+            last_evaluated: None,
+        }
+    }
+
+    #[test]
+    fn test_cron_parsing_and_next_firing() {
+        let weekdays = CronExpr::parse("0  8 * * mon-fri").unwrap();
+        assert_eq!(weekdays.as_str(), "0 8 * * mon-fri");
+
+        // Friday evening -> Monday morning
+        assert_eq!(weekdays.next_after(&madrid(2025, 3, 7, 21, 0)), Some(madrid(2025, 3, 10, 8, 0)));
+        // Strictly after
+        assert_eq!(weekdays.next_after(&madrid(2025, 3, 10, 8, 0)), Some(madrid(2025, 3, 11, 8, 0)));
+
+        let every_15 = CronExpr::parse("*/15 9-10 1,15 * 0,7").unwrap();
+        assert_eq!(every_15.next_after(&madrid(2025, 3, 1, 10, 50)), Some(madrid(2025, 3, 2, 9, 0)));
+
+        // 02:30 doesn't exist on 2025-03-30 in Madrid (clocks jump to 03:00)
+        let at_0230 = CronExpr::parse("30 2 * * *").unwrap();
+        assert_eq!(at_0230.next_after(&madrid(2025, 3, 29, 12, 0)), Some(madrid(2025, 3, 31, 2, 30)));
+
+        // A `*/2` day field is still a restriction: odd days only (2025-03-01 is a Saturday)
+        let odd_days = CronExpr::parse("0 8 */2 * *").unwrap();
+        assert_eq!(odd_days.next_after(&madrid(2025, 3, 1, 10, 0)), Some(madrid(2025, 3, 3, 8, 0)));
+        assert_eq!(odd_days.next_after(&madrid(2025, 3, 3, 8, 0)), Some(madrid(2025, 3, 5, 8, 0)));
+        // ... and with a weekday list both must match, since the day field starts with `*`
+        let odd_weekdays = CronExpr::parse("0 8 */2 * 1-5").unwrap();
+        assert_eq!(odd_weekdays.next_after(&madrid(2025, 3, 3, 8, 0)), Some(madrid(2025, 3, 5, 8, 0)));
+        assert_eq!(odd_weekdays.next_after(&madrid(2025, 3, 7, 8, 0)), Some(madrid(2025, 3, 11, 8, 0)));
+
+        for bad in ["0 8 * *", "60 8 * * *", "0 8 * * FOO", "*/0 * * * *", "0 20-8 * * *", "0 8 * * 1; rm"] {
+            assert!(CronExpr::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_policy_change_detection() {
+        // `instanceSchedulePolicy` of `gcloud compute resource-policies describe`
+        let existing: gcloud::InstanceSchedulePolicy = serde_json::from_str(
+            r#"{"timeZone": "Europe/Madrid", "vmStartSchedule": {"schedule": "0 8 * * MON-FRI"},
+                "vmStopSchedule": {"schedule": "0  20 * * 1-5"}}"#,
+        )
+        .unwrap();
+        let mut schedule = office_hours();
+        assert!(policy_matches(&existing, &policy_for(&schedule)));
+
+        schedule.stop = Some(CronExpr::parse("0 21 * * 1-5").unwrap());
+        assert!(!policy_matches(&existing, &policy_for(&schedule)));
+        schedule = office_hours();
+        schedule.time_zone = "Europe/Lisbon".to_string();
+        assert!(!policy_matches(&existing, &policy_for(&schedule)));
+        schedule = office_hours();
+        schedule.stop = None;
+        assert!(!policy_matches(&existing, &policy_for(&schedule)));
+    }
+
+    #[test]
+    fn test_due_actions_and_catch_up() {
+        let mut schedules = vec![office_hours()];
+        let monday_0759 = madrid(2025, 3, 10, 7, 59).with_timezone(&Utc);
+        let monday_0801 = madrid(2025, 3, 10, 8, 1).with_timezone(&Utc);
+        let catch_up = Duration::from_secs(15 * 60);
+
+        // First evaluation only records the time
+        assert!(collect_due(&mut schedules, monday_0759, catch_up).is_empty());
+        let due = collect_due(&mut schedules, monday_0801, catch_up);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].action, InstanceAction::Start);
+        assert!(collect_due(&mut schedules, monday_0801, catch_up).is_empty());
+
+        // Laptop asleep from Monday 08:01 to Tuesday 20:05: only the last
+        // firing (the stop) is caught up
+        let tuesday_2005 = madrid(2025, 3, 11, 20, 5).with_timezone(&Utc);
+        let due = collect_due(&mut schedules, tuesday_2005, catch_up);
+        assert_eq!(due[0].action, InstanceAction::Stop);
+        // ...and not at all once outside the grace window
+        schedules[0].last_evaluated = Some(monday_0801);
+        let tuesday_2100 = madrid(2025, 3, 11, 21, 0).with_timezone(&Utc);
+        assert!(collect_due(&mut schedules, tuesday_2100, catch_up).is_empty());
+
+        let upcoming = schedules[0].upcoming(tuesday_2100, 3).unwrap();
+        assert_eq!(upcoming[0].action, InstanceAction::Start);
+        assert_eq!(upcoming[0].at, "2025-03-12T08:00:00+01:00");
+        assert_eq!(upcoming[1].action, InstanceAction::Stop);
+    }
+
+    #[tokio::test]
+    async fn test_store_roundtrip_and_scheduled_start() {
+        let path = std::env::temp_dir().join(format!("lcc-schedules-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = ScheduleStore::load(&path).unwrap();
+        store.upsert(office_hours()).unwrap();
+        let mut invalid = office_hours();
+        invalid.time_zone = "Mars/Olympus".to_string();
+        assert!(store.upsert(invalid).is_err());
+        store.data.schedules[0].last_evaluated = Some(madrid(2025, 3, 10, 7, 59).with_timezone(&Utc));
+        store.save().unwrap();
+
+        let fake = FakeBackend::new();
+        fake.add_instance("dev-project", test_instance("dev-1", "europe-west1-b", "TERMINATED"));
+        let now = madrid(2025, 3, 10, 8, 0).with_timezone(&Utc);
+        let events = run_due_schedules(&fake, &path, now, &SchedulerConfig::default()).await.unwrap();
+        assert!(matches!(&events[..], [ScheduleEvent::Ran { succeeded: 1, failed: 0, .. }]));
+        assert_eq!(fake.describe_instance("dev-project", "europe-west1-b", "dev-1").await.unwrap().status, "RUNNING");
+
+        let reloaded = ScheduleStore::load(&path).unwrap();
+        assert_eq!(reloaded.get("office-hours").unwrap().last_evaluated, Some(now));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,441 @@
+//! Managed `~/.ssh/config` entries for discovered instances
+//!
+//! Writes one `Host project.instance` entry per instance, routed through
+//! `lcc proxy-command`, so `ssh project.web-1` works from any terminal.
+//! Entries live inside clearly delimited per-project blocks; anything outside
+//! those markers (i.e. user-written configuration) is never modified.
+
+use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+use crate::accounts::GcloudContext;
+use crate::gcloud::{self, GcpInstance};
+use crate::validation::{validate_instance_name, validate_project_id, validate_username, validate_zone};
+
+/// Name of the include file created next to `~/.ssh/config`
+pub const MANAGED_INCLUDE_FILE: &str = "lcc_config";
+
+const BLOCK_BEGIN: &str = "# >>> lcc managed block: project";
+const BLOCK_END: &str = "# <<< lcc managed block: project";
+
+/// Where managed blocks are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshConfigTarget {
+    /// `~/.ssh/lcc_config`, pulled in with an `Include` line in `~/.ssh/config`
+    IncludeFile,
+    /// Directly inside `~/.ssh/config`
+    MainConfig,
+}
+
+/// Options for the generated `Host` entries
+#[derive(Debug, Clone)]
+pub struct SshConfigOptions {
+    /// Remote login user (`User`)
+    pub user: Option<String>,
+    /// Private key (`IdentityFile`), e.g. `~/.ssh/google_compute_engine`
+    pub identity_file: Option<String>,
+    /// Alias prefix instead of the project id (`prod` -> `prod.web-1`)
+    pub alias_prefix: Option<String>,
+    /// Command used in `ProxyCommand` (path to the `lcc` binary)
+    pub proxy_command_bin: String,
+    /// gcloud account/configuration used to list instances and in `ProxyCommand`
+    pub context: GcloudContext,
+    pub target: SshConfigTarget,
+}
+
+impl Default for SshConfigOptions {
+    fn default() -> Self {
+        Self {
+            user: None,
+            identity_file: None,
+            alias_prefix: None,
+            proxy_command_bin: "lcc".to_string(),
+            context: GcloudContext::default(),
+            target: SshConfigTarget::IncludeFile,
+        }
+    }
+}
+
+/// Result of a sync, for display
+#[derive(Debug, Clone)]
+pub struct SshConfigUpdate {
+    pub path: PathBuf,
+    pub hosts: Vec<String>,
+    pub changed: bool,
+}
+
+/// Reject values that could break out of a single ssh_config line
+fn validate_config_value(field: &str, value: &str) -> Result<()> {
+    if value.is_empty() || value.chars().any(|c| c.is_control() || c == '"') {
+        return Err(anyhow!("Invalid {} for ssh config: {:?}", field, value));
+    }
+    Ok(())
+}
+
+fn quote_if_needed(value: &str) -> String {
+    if value.contains(' ') {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render the managed block for one project
+///
+/// Output is deterministic (instances sorted by name) so re-running the
+/// sync on an unchanged project produces byte-identical files.
+pub fn render_project_block(project: &str, instances: &[GcpInstance], opts: &SshConfigOptions) -> Result<String> {
+    validate_project_id(project)?;
+    let prefix = opts.alias_prefix.as_deref().unwrap_or(project);
+    validate_config_value("alias prefix", prefix)?;
+    if prefix.contains(char::is_whitespace) {
+        return Err(anyhow!("Alias prefix cannot contain whitespace: {:?}", prefix));
+    }
+    if let Some(user) = &opts.user {
+        validate_username(user)?;
+    }
+    if let Some(key) = &opts.identity_file {
+        validate_config_value("identity file", key)?;
+    }
+    validate_config_value("proxy command", &opts.proxy_command_bin)?;
+    opts.context.validate()?;
+    let identity_args: String = opts.context.args().iter().map(|a| format!(" {}", a)).collect();
+
+    let mut sorted: Vec<&GcpInstance> = instances.iter().collect();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut block = format!("{} {} >>>\n", BLOCK_BEGIN, project);
+    block.push_str("# Generated by Linux Cloud Connector. Edits inside this block are overwritten.\n");
+
+    for instance in sorted {
+        validate_instance_name(&instance.name)?;
+        validate_zone(&instance.zone)?;
+
+        block.push_str(&format!("Host {}.{}\n", prefix, instance.name));
+        block.push_str(&format!("    HostName {}\n", instance.name));
+        if let Some(user) = &opts.user {
+            block.push_str(&format!("    User {}\n", user));
+        }
+        if let Some(key) = &opts.identity_file {
+            block.push_str(&format!("    IdentityFile {}\n", quote_if_needed(key)));
+            block.push_str("    IdentitiesOnly yes\n");
+        }
+        block.push_str(&format!(
+            "    ProxyCommand {} proxy-command{} --project {} --zone {} {} %p\n",
+            quote_if_needed(&opts.proxy_command_bin),
+            identity_args,
+            project,
+            instance.zone,
+            instance.name
+        ));
+        // The IAP hop hides the real host; key by alias so entries don't clash
+        block.push_str(&format!("    HostKeyAlias {}.{}\n", project, instance.name));
+    }
+
+    block.push_str(&format!("{} {} <<<\n", BLOCK_END, project));
+    Ok(block)
+}
+
+/// Line range `[start, end]` of the managed block for `project`, if present
+fn find_block(lines: &[&str], project: &str) -> Result<Option<(usize, usize)>> {
+    let begin = format!("{} {} >>>", BLOCK_BEGIN, project);
+    let end = format!("{} {} <<<", BLOCK_END, project);
+
+    let Some(start) = lines.iter().position(|l| l.trim_end() == begin) else {
+        return Ok(None);
+    };
+    let stop = lines[start..]
+        .iter()
+        .position(|l| l.trim_end() == end)
+        .map(|offset| start + offset)
+        .ok_or_else(|| anyhow!("Managed block for '{}' has no end marker; refusing to edit", project))?;
+    Ok(Some((start, stop)))
+}
+
+/// Insert or replace the block for `project`, leaving every other line intact
+pub fn upsert_block(existing: &str, project: &str, block: &str) -> Result<String> {
+    let lines: Vec<&str> = existing.lines().collect();
+
+    let mut out = match find_block(&lines, project)? {
+        Some((start, stop)) => {
+            let mut out = lines[..start].join("\n");
+            if start > 0 {
+                out.push('\n');
+            }
+            out.push_str(block);
+            let rest = lines[stop + 1..].join("\n");
+            if !rest.is_empty() {
+                out.push_str(&rest);
+                out.push('\n');
+            }
+            out
+        }
+        None => {
+            let mut out = existing.to_string();
+            if !out.is_empty() {
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push('\n');
+            }
+            out.push_str(block);
+            out
+        }
+    };
+
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Remove the block for `project`; returns the input unchanged if absent
+pub fn remove_block(existing: &str, project: &str) -> Result<String> {
+    let lines: Vec<&str> = existing.lines().collect();
+    match find_block(&lines, project)? {
+        Some((start, stop)) => {
+            let mut kept: Vec<&str> = lines[..start].to_vec();
+            // Drop the blank separator we added in front of the block
+            if kept.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+                kept.pop();
+            }
+            kept.extend_from_slice(&lines[stop + 1..]);
+            let mut out = kept.join("\n");
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        None => Ok(existing.to_string()),
+    }
+}
+
+/// Add an `Include` for the managed file at the top of `~/.ssh/config`
+///
+/// `Include` only applies globally when it precedes the first `Host`, so it
+/// is inserted at the very beginning. Returns `None` if already present.
+pub fn ensure_include(existing: &str, include: &str) -> Option<String> {
+    let already = existing.lines().any(|l| {
+        let mut words = l.split_whitespace();
+        matches!(words.next(), Some(k) if k.eq_ignore_ascii_case("include"))
+            && words.any(|w| w == include || w.ends_with(&format!("/{}", include)))
+    });
+    if already {
+        return None;
+    }
+    Some(format!(
+        "# Added by Linux Cloud Connector\nInclude {}\n\n{}",
+        include, existing
+    ))
+}
+
+fn ssh_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not determine home directory"))?;
+    Ok(home.join(".ssh"))
+}
+
+/// Write `content` with 0600 permissions via a temp file + rename
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    let tmp = path.with_extension("lcc-tmp");
+    fs::write(&tmp, content).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    #[cfg(unix)]
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to set permissions on {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+fn read_or_empty(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(s),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(anyhow!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Write the managed block for `project` built from `instances`
+pub fn write_project_block(project: &str, instances: &[GcpInstance], opts: &SshConfigOptions) -> Result<SshConfigUpdate> {
+    let dir = ssh_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    #[cfg(unix)]
+    let _ = fs::set_permissions(&dir, fs::Permissions::from_mode(0o700));
+
+    let main_config = dir.join("config");
+    let path = match opts.target {
+        SshConfigTarget::IncludeFile => dir.join(MANAGED_INCLUDE_FILE),
+        SshConfigTarget::MainConfig => main_config.clone(),
+    };
+
+    let block = render_project_block(project, instances, opts)?;
+    let existing = read_or_empty(&path)?;
+    let updated = upsert_block(&existing, project, &block)?;
+    let mut changed = updated != existing;
+    if changed {
+        write_private(&path, &updated)?;
+    }
+
+    if opts.target == SshConfigTarget::IncludeFile {
+        let main = read_or_empty(&main_config)?;
+        if let Some(with_include) = ensure_include(&main, MANAGED_INCLUDE_FILE) {
+            write_private(&main_config, &with_include)?;
+            changed = true;
+        }
+    }
+
+    let prefix = opts.alias_prefix.as_deref().unwrap_or(project);
+    let mut hosts: Vec<String> = instances.iter().map(|i| format!("{}.{}", prefix, i.name)).collect();
+    hosts.sort();
+
+    tracing::info!(
+        project = project,
+        path = %path.display(),
+        hosts = hosts.len(),
+        changed = changed,
+        "SSH config block synced"
+    );
+
+    Ok(SshConfigUpdate { path, hosts, changed })
+}
+
+/// Discover the instances of `project` and sync its managed block
+pub async fn sync_ssh_config_async(project: &str, opts: &SshConfigOptions) -> Result<SshConfigUpdate> {
+    let instances = gcloud::get_instances_async(&opts.context, project).await?;
+    write_project_block(project, &instances, opts)
+}
+
+/// Synchronous wrapper for FFI bridge
+pub fn sync_ssh_config(project: &str, opts: &SshConfigOptions) -> Result<SshConfigUpdate> {
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
+    rt.block_on(sync_ssh_config_async(project, opts))
+}
+
+/// Remove the managed block for `project` from the configured target
+pub fn remove_project_block(project: &str, target: SshConfigTarget) -> Result<bool> {
+    validate_project_id(project)?;
+    let dir = ssh_dir()?;
+    let path = match target {
+        SshConfigTarget::IncludeFile => dir.join(MANAGED_INCLUDE_FILE),
+        SshConfigTarget::MainConfig => dir.join("config"),
+    };
+    let existing = read_or_empty(&path)?;
+    let updated = remove_block(&existing, project)?;
+    if updated == existing {
+        return Ok(false);
+    }
+    write_private(&path, &updated)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(name: &str, zone: &str) -> GcpInstance {
+        GcpInstance {
+            name: name.to_string(),
+            status: "RUNNING".to_string(),
+            zone: zone.to_string(),
+            machine_type: "e2-medium".to_string(),
+            cpu_count: None,
+            memory_mb: None,
+            disk_gb: None,
+        }
+    }
+
+    fn opts() -> SshConfigOptions {
+        SshConfigOptions {
+            user: Some("jlopezre".to_string()),
+            identity_file: Some("~/.ssh/google_compute_engine".to_string()),
+            alias_prefix: Some("prod".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_block() {
+        let block = render_project_block(
+            "my-prod-project",
+            &[instance("web-2", "us-central1-b"), instance("web-1", "us-central1-a")],
+            &opts(),
+        )
+        .unwrap();
+
+        assert!(block.starts_with("# >>> lcc managed block: project my-prod-project >>>\n"));
+        assert!(block.ends_with("# <<< lcc managed block: project my-prod-project <<<\n"));
+        assert!(block.find("Host prod.web-1").unwrap() < block.find("Host prod.web-2").unwrap());
+        assert!(block.contains(
+            "    ProxyCommand lcc proxy-command --project my-prod-project --zone us-central1-a web-1 %p\n"
+        ));
+        assert!(block.contains("    User jlopezre\n"));
+    }
+
+    #[test]
+    fn test_render_block_pins_account() {
+        let mut pinned = opts();
+        pinned.context = GcloudContext::for_account("ops@example.com");
+        let block = render_project_block("my-prod-project", &[instance("web-1", "us-central1-a")], &pinned).unwrap();
+        assert!(block.contains("ProxyCommand lcc proxy-command --account=ops@example.com --project my-prod-project"));
+
+        pinned.context = GcloudContext::for_account("ops@example.com %h");
+        assert!(render_project_block("my-prod-project", &[instance("web-1", "us-central1-a")], &pinned).is_err());
+    }
+
+    #[test]
+    fn test_render_rejects_injection() {
+        let mut bad = opts();
+        bad.identity_file = Some("key\nProxyCommand sh".to_string());
+        assert!(render_project_block("my-prod-project", &[instance("web-1", "us-central1-a")], &bad).is_err());
+
+        let mut bad = opts();
+        bad.alias_prefix = Some("a b".to_string());
+        assert!(render_project_block("my-prod-project", &[], &bad).is_err());
+    }
+
+    #[test]
+    fn test_upsert_is_idempotent_and_preserves_user_entries() {
+        let user_config = "Host bastion\n    HostName 10.0.0.1\n";
+        let block = render_project_block("my-prod-project", &[instance("web-1", "us-central1-a")], &opts()).unwrap();
+
+        let once = upsert_block(user_config, "my-prod-project", &block).unwrap();
+        let twice = upsert_block(&once, "my-prod-project", &block).unwrap();
+        assert_eq!(once, twice);
+        assert!(once.starts_with(user_config));
+
+        // Updating in place keeps trailing user entries after the block
+        let with_trailer = format!("{}Host after\n    HostName 10.0.0.2\n", once);
+        let new_block = render_project_block("my-prod-project", &[instance("web-9", "us-central1-a")], &opts()).unwrap();
+        let updated = upsert_block(&with_trailer, "my-prod-project", &new_block).unwrap();
+        assert!(updated.contains("Host prod.web-9"));
+        assert!(!updated.contains("Host prod.web-1"));
+        assert!(updated.starts_with(user_config));
+        assert!(updated.ends_with("Host after\n    HostName 10.0.0.2\n"));
+    }
+
+    #[test]
+    fn test_remove_block_restores_original() {
+        let user_config = "Host bastion\n    HostName 10.0.0.1\n";
+        let block = render_project_block("my-prod-project", &[instance("web-1", "us-central1-a")], &opts()).unwrap();
+        let with_block = upsert_block(user_config, "my-prod-project", &block).unwrap();
+        assert_eq!(remove_block(&with_block, "my-prod-project").unwrap(), user_config);
+    }
+
+    #[test]
+    fn test_unterminated_block_is_not_edited() {
+        let broken = "# >>> lcc managed block: project my-prod-project >>>\nHost x\n";
+        assert!(upsert_block(broken, "my-prod-project", "").is_err());
+    }
+
+    #[test]
+    fn test_ensure_include() {
+        let added = ensure_include("Host a\n", MANAGED_INCLUDE_FILE).unwrap();
+        assert!(added.starts_with("# Added by Linux Cloud Connector\nInclude lcc_config\n"));
+        assert!(added.ends_with("Host a\n"));
+        assert!(ensure_include(&added, MANAGED_INCLUDE_FILE).is_none());
+        assert!(ensure_include("include ~/.ssh/lcc_config\n", MANAGED_INCLUDE_FILE).is_none());
+    }
+}
@@ -0,0 +1,230 @@
+//! Tunnel supervisor
+//!
+//! Watches the tunnels registered in `tunnel.rs` and brings dead ones back
+//! on the same local port, so clients pointed at a fixed port keep working.
+//! Restarts use exponential backoff and a retry budget per tunnel.
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use crate::tunnel::{self, TunnelKey};
+use crate::worker::{EventBus, StopSignal, Worker};
+
+/// Supervisor tuning
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    /// How often tunnel health is checked
+    pub check_interval: Duration,
+    /// Delay before the first restart attempt
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between attempts
+    pub max_backoff: Duration,
+    /// Attempts before giving up on a tunnel
+    pub max_retries: u32,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(5),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_retries: 8,
+        }
+    }
+}
+
+impl SupervisorConfig {
+    /// Delay before restart attempt number `attempt` (1-based)
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// Lifecycle events emitted while the supervisor repairs tunnels
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TunnelEvent {
+    Reconnecting {
+        #[serde(flatten)]
+        key: TunnelKey,
+        local_port: u16,
+        attempt: u32,
+        delay_ms: u64,
+    },
+    Reconnected {
+        #[serde(flatten)]
+        key: TunnelKey,
+        local_port: u16,
+        attempts: u32,
+    },
+    GaveUp {
+        #[serde(flatten)]
+        key: TunnelKey,
+        local_port: u16,
+        attempts: u32,
+        error: String,
+    },
+}
+
+/// Per-tunnel retry bookkeeping
+struct RetryState {
+    attempts: u32,
+    next_attempt: Instant,
+    last_error: Option<String>,
+}
+
+lazy_static! {
+    static ref SUPERVISOR: Worker = Worker::new("tunnel-supervisor");
+    static ref EVENTS: EventBus<TunnelEvent> = EventBus::new("Tunnel supervisor event");
+}
+
+/// Receive supervisor events; the channel closes when the receiver is dropped
+pub fn subscribe_tunnel_events() -> Receiver<TunnelEvent> {
+    EVENTS.subscribe()
+}
+
+fn emit(event: TunnelEvent) {
+    EVENTS.emit(event);
+}
+
+/// Start the background supervisor (no-op if it is already running)
+pub fn start_supervisor(config: SupervisorConfig) -> Result<()> {
+    SUPERVISOR.start((), move |stop| run(config, stop))?;
+    Ok(())
+}
+
+/// Stop the background supervisor and wait for it to exit
+pub fn stop_supervisor() -> Result<()> {
+    SUPERVISOR.stop()
+}
+
+/// Whether the supervisor thread is running
+pub fn is_supervisor_running() -> bool {
+    SUPERVISOR.is_running()
+}
+
+fn run(config: SupervisorConfig, stop: StopSignal) {
+    let mut retries: HashMap<TunnelKey, RetryState> = HashMap::new();
+
+    while !stop.is_stopped() {
+        let unhealthy = match tunnel::unhealthy_tunnels() {
+            Ok(list) => list,
+            Err(e) => {
+                tracing::error!(error = %e, "Supervisor could not inspect tunnels");
+                Vec::new()
+            }
+        };
+
+        // Forget state for tunnels that recovered or were stopped by the user
+        retries.retain(|key, _| unhealthy.iter().any(|(spec, _)| &spec.key == key));
+
+        for (spec, local_port) in unhealthy {
+            let key = spec.key;
+            if stop.is_stopped() {
+                break;
+            }
+
+            let state = retries.entry(key.clone()).or_insert_with(|| {
+                let delay = config.backoff_for(1);
+                emit(TunnelEvent::Reconnecting {
+                    key: key.clone(),
+                    local_port,
+                    attempt: 1,
+                    delay_ms: delay.as_millis() as u64,
+                });
+                RetryState { attempts: 0, next_attempt: Instant::now() + delay, last_error: None }
+            });
+
+            if Instant::now() < state.next_attempt {
+                continue;
+            }
+
+            state.attempts += 1;
+            match tunnel::restart_tunnel(&key) {
+                Ok(port) => {
+                    emit(TunnelEvent::Reconnected {
+                        key: key.clone(),
+                        local_port: port,
+                        attempts: state.attempts,
+                    });
+                    retries.remove(&key);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        tunnel = %key,
+                        attempt = state.attempts,
+                        error = %e,
+                        "Tunnel restart failed"
+                    );
+                    state.last_error = Some(e.to_string());
+
+                    if state.attempts >= config.max_retries {
+                        let attempts = state.attempts;
+                        let error = state.last_error.take().unwrap_or_default();
+                        let _ = tunnel::remove_tunnel(&key);
+                        retries.remove(&key);
+                        emit(TunnelEvent::GaveUp {
+                            key: key.clone(),
+                            local_port,
+                            attempts,
+                            error,
+                        });
+                    } else {
+                        let delay = config.backoff_for(state.attempts + 1);
+                        state.next_attempt = Instant::now() + delay;
+                        emit(TunnelEvent::Reconnecting {
+                            key: key.clone(),
+                            local_port,
+                            attempt: state.attempts + 1,
+                            delay_ms: delay.as_millis() as u64,
+                        });
+                    }
+                }
+            }
+        }
+
+        stop.sleep(config.check_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let config = SupervisorConfig {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+            ..Default::default()
+        };
+        assert_eq!(config.backoff_for(1), Duration::from_secs(1));
+        assert_eq!(config.backoff_for(2), Duration::from_secs(2));
+        assert_eq!(config.backoff_for(4), Duration::from_secs(8));
+        assert_eq!(config.backoff_for(5), Duration::from_secs(10));
+        assert_eq!(config.backoff_for(64), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = TunnelEvent::Reconnected {
+            key: TunnelKey::new("my-project", "us-central1-a", "vm-1", 5432),
+            local_port: 5432,
+            attempts: 2,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "reconnected");
+        assert_eq!(json["local_port"], 5432);
+        assert_eq!(json["instance"], "vm-1");
+        assert_eq!(json["project"], "my-project");
+    }
+}
@@ -1,177 +1,592 @@
-use std::process::{Command, Child, Stdio};
-use std::net::{TcpListener, TcpStream};
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::process::{Command, Child, ChildStderr, Stdio};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
 use anyhow::{Result, anyhow};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use std::time::Duration;
 use lazy_static::lazy_static;
 use tracing;
+use crate::accounts::GcloudContext;
+use serde::{Deserialize, Serialize};
+use crate::tunnel_state::{self, PersistedTunnel};
+use crate::tunnel_errors::{StderrLog, TunnelError};
+use crate::iap::{ActivitySnapshot, IapConnector, IapTarget, NativeTunnelHandle, TunnelActivity, spawn_native_listener};
+use crate::runtime;
 use crate::validation::{validate_project_id, validate_zone, validate_instance_name};
 
+/// Which implementation carries the tunnel traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TunnelBackendKind {
+    /// Native relay first, falling back to gcloud if it cannot be set up
+    Auto,
+    /// Pure-Rust IAP WebSocket relay
+    Native,
+    /// `gcloud compute start-iap-tunnel` subprocess
+    Gcloud,
+}
+
+enum TunnelBackend {
+    /// Child process and its recent stderr
+    Gcloud(Child, Arc<StderrLog>),
+    Native(NativeTunnelHandle),
+    /// gcloud process left by a previous run of the app (not our child)
+    Adopted(u32),
+}
+
+/// Local address tunnels listen on unless told otherwise
+pub const DEFAULT_BIND_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+/// Identity of a tunnel
+///
+/// Two `start_tunnel` calls with the same key share one tunnel; anything
+/// that differs (another project with a same-named instance, another
+/// account, another bind address) gets its own.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TunnelKey {
+    /// gcloud account the tunnel authenticates as (`None` = active account)
+    pub account: Option<String>,
+    /// gcloud configuration supplying the account, if any
+    pub configuration: Option<String>,
+    pub project: String,
+    pub zone: String,
+    pub instance: String,
+    pub remote_port: u16,
+    pub bind_address: IpAddr,
+}
+
+impl TunnelKey {
+    /// Key for a tunnel as the active account on the default bind address
+    pub fn new(project: &str, zone: &str, instance: &str, remote_port: u16) -> Self {
+        Self {
+            account: None,
+            configuration: None,
+            project: project.to_string(),
+            zone: zone.to_string(),
+            instance: instance.to_string(),
+            remote_port,
+            bind_address: DEFAULT_BIND_ADDRESS,
+        }
+    }
+
+    pub fn with_context(mut self, context: &GcloudContext) -> Self {
+        self.account = context.account.clone();
+        self.configuration = context.configuration.clone();
+        self
+    }
+
+    pub fn with_bind_address(mut self, bind_address: IpAddr) -> Self {
+        self.bind_address = bind_address;
+        self
+    }
+
+    /// gcloud identity of the tunnel
+    pub fn context(&self) -> GcloudContext {
+        GcloudContext { account: self.account.clone(), configuration: self.configuration.clone() }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        // SECURITY: Validate all inputs before passing to gcloud command
+        validate_project_id(&self.project)?;
+        validate_zone(&self.zone)?;
+        validate_instance_name(&self.instance)?;
+        self.context().validate()
+    }
+}
+
+/// `account/project/zone/instance:port@bind`, for logs and errors
+impl fmt::Display for TunnelKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let identity = self.account.as_deref().or(self.configuration.as_deref()).unwrap_or("default");
+        write!(
+            f,
+            "{}/{}/{}/{}:{}@{}",
+            identity, self.project, self.zone, self.instance, self.remote_port, self.bind_address
+        )
+    }
+}
+
+/// Which local port a tunnel listens on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LocalPortSpec {
+    /// Any free port
+    #[default]
+    Any,
+    /// Exactly this port; fails if it is taken
+    Fixed(u16),
+    /// This port if it is free, any free port otherwise
+    Preferred(u16),
+    /// The first free port in `start..=end`
+    Range { start: u16, end: u16 },
+}
+
+impl LocalPortSpec {
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            LocalPortSpec::Fixed(0) | LocalPortSpec::Preferred(0) => Err(anyhow!("Local port must not be 0")),
+            LocalPortSpec::Range { start, end } if start == 0 || start > end => {
+                Err(anyhow!("Invalid local port range {}-{}", start, end))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a tunnel already listening on `port` satisfies this request
+    fn accepts(&self, port: u16) -> bool {
+        match *self {
+            LocalPortSpec::Fixed(wanted) => port == wanted,
+            LocalPortSpec::Range { start, end } => (start..=end).contains(&port),
+            LocalPortSpec::Any | LocalPortSpec::Preferred(_) => true,
+        }
+    }
+}
+
+/// Everything needed to (re)create a tunnel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelSpec {
+    pub key: TunnelKey,
+    pub backend: TunnelBackendKind,
+    pub local_port: LocalPortSpec,
+}
+
 pub struct IapTunnel {
-    process: Child,
+    backend: TunnelBackend,
     pub local_port: u16,
+    pub spec: TunnelSpec,
+    /// Traffic through the tunnel; survives supervisor restarts
+    pub activity: Arc<TunnelActivity>,
 }
 
 impl IapTunnel {
     pub fn stop(&mut self) -> Result<()> {
-        // Enviar SIGTERM o SIGKILL. kill() es SIGKILL.
-        let _ = self.process.kill();
-        let _ = self.process.wait();
+        match &mut self.backend {
+            TunnelBackend::Gcloud(process, _) => {
+                // Enviar SIGTERM o SIGKILL. kill() es SIGKILL.
+                let _ = process.kill();
+                let _ = process.wait();
+            }
+            TunnelBackend::Native(handle) => handle.stop(),
+            TunnelBackend::Adopted(pid) => {
+                // The pid may have been reused since the gcloud process died
+                if tunnel_state::is_gcloud_tunnel_process(*pid, &self.spec.key) {
+                    tunnel_state::terminate_process(*pid)?;
+                } else {
+                    tracing::info!(tunnel = %self.spec.key, pid = *pid, "Adopted tunnel process already gone");
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Check if the tunnel process is still running
+    /// Check if the tunnel process (or native listener thread) is still running
     pub fn is_process_alive(&mut self) -> bool {
-        match self.process.try_wait() {
-            Ok(Some(_)) => false, // Process has exited
-            Ok(None) => true,     // Process is still running
-            Err(_) => false,      // Error checking status, assume dead
+        match &mut self.backend {
+            TunnelBackend::Gcloud(process, _) => match process.try_wait() {
+                Ok(Some(_)) => false, // Process has exited
+                Ok(None) => true,     // Process is still running
+                Err(_) => false,      // Error checking status, assume dead
+            },
+            TunnelBackend::Native(handle) => handle.is_running(),
+            TunnelBackend::Adopted(pid) => tunnel_state::is_gcloud_tunnel_process(*pid, &self.spec.key),
         }
     }
 
+    /// Whether this tunnel uses the native relay
+    pub fn is_native(&self) -> bool {
+        matches!(self.backend, TunnelBackend::Native(_))
+    }
+
     /// Check if the local port is actually listening
     pub fn is_port_listening(&self) -> bool {
-        let addr = format!("127.0.0.1:{}", self.local_port);
-        // Try to connect to the port
-        TcpStream::connect_timeout(
-            &addr.parse().unwrap(),
-            Duration::from_millis(500)
-        ).is_ok()
+        is_port_listening(self.spec.key.bind_address, self.local_port)
+    }
+
+    /// gcloud process id, for tunnels served by gcloud
+    fn pid(&self) -> Option<u32> {
+        match &self.backend {
+            TunnelBackend::Gcloud(process, _) => Some(process.id()),
+            TunnelBackend::Native(_) => None,
+            TunnelBackend::Adopted(pid) => Some(*pid),
+        }
     }
 
     /// Comprehensive health check
+    ///
+    /// The native listener owns its socket, so a running accept loop is
+    /// enough; probing the port would open a real relay session.
     pub fn is_healthy(&mut self) -> bool {
+        if self.is_native() {
+            return self.is_process_alive();
+        }
         self.is_process_alive() && self.is_port_listening()
     }
 }
 
 lazy_static! {
-    static ref TUNNELS: Mutex<HashMap<String, IapTunnel>> = Mutex::new(HashMap::new());
+    static ref TUNNELS: Mutex<HashMap<TunnelKey, IapTunnel>> = Mutex::new(HashMap::new());
+}
+
+/// A registered tunnel, as returned by [`list_tunnels`] and [`open_tunnel`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TunnelDescriptor {
+    #[serde(flatten)]
+    pub key: TunnelKey,
+    pub local_port: u16,
+    /// Served by the native relay rather than a gcloud subprocess
+    pub native: bool,
+    /// Process/listener still running (the port is not probed)
+    pub alive: bool,
+}
+
+/// Whether something accepts connections on `port` of `bind_address`
+pub(crate) fn is_port_listening(bind_address: IpAddr, port: u16) -> bool {
+    // A wildcard bind is reachable on loopback
+    let host = match bind_address {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    // Try to connect to the port
+    TcpStream::connect_timeout(
+        &SocketAddr::new(host, port),
+        Duration::from_millis(500)
+    ).is_ok()
 }
 
-/// Creates a unique key for tunnel identification: "instance:port"
-fn make_tunnel_key(instance: &str, remote_port: u16) -> String {
-    format!("{}:{}", instance, remote_port)
+impl IapTunnel {
+    fn record(&self) -> PersistedTunnel {
+        PersistedTunnel {
+            key: self.spec.key.clone(),
+            backend: self.spec.backend,
+            local_port: self.spec.local_port,
+            last_local_port: self.local_port,
+            pid: self.pid(),
+        }
+    }
+
+    fn descriptor(&mut self) -> TunnelDescriptor {
+        TunnelDescriptor {
+            key: self.spec.key.clone(),
+            local_port: self.local_port,
+            native: self.is_native(),
+            alive: self.is_process_alive(),
+        }
+    }
 }
 
 pub fn start_tunnel(project: &str, zone: &str, instance: &str, remote_port: u16) -> Result<u16> {
-    // SECURITY: Validate all inputs before passing to gcloud command
-    validate_project_id(project)?;
-    validate_zone(zone)?;
-    validate_instance_name(instance)?;
+    start_tunnel_with_backend(project, zone, instance, remote_port, TunnelBackendKind::Auto, &GcloudContext::default())
+}
+
+/// Start a tunnel with an explicit backend choice and gcloud identity
+pub fn start_tunnel_with_backend(
+    project: &str,
+    zone: &str,
+    instance: &str,
+    remote_port: u16,
+    backend: TunnelBackendKind,
+    context: &GcloudContext,
+) -> Result<u16> {
+    let key = TunnelKey::new(project, zone, instance, remote_port).with_context(context);
+    Ok(open_tunnel(TunnelSpec { key, backend, local_port: LocalPortSpec::Any })?.local_port)
+}
+
+/// Start the tunnel described by `spec`, or return the one already registered under its key
+///
+/// An existing tunnel on a port outside `spec.local_port` is an error rather
+/// than a silent second port.
+pub fn open_tunnel(spec: TunnelSpec) -> Result<TunnelDescriptor> {
+    spec.key.validate()?;
+    spec.local_port.validate()?;
 
     // Scope para el lock
     {
-        let tunnel_key = make_tunnel_key(instance, remote_port);
-        let tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
-        if let Some(tunnel) = tunnels.get(&tunnel_key) {
+        let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+        if let Some(tunnel) = tunnels.get_mut(&spec.key) {
+            if !spec.local_port.accepts(tunnel.local_port) {
+                return Err(anyhow!(
+                    "Tunnel {} is already open on local port {}",
+                    spec.key,
+                    tunnel.local_port
+                ));
+            }
             tracing::info!(
-                instance = instance,
-                remote_port = remote_port,
+                tunnel = %spec.key,
                 local_port = tunnel.local_port,
                 "Tunnel already exists, returning existing local port"
             );
-            return Ok(tunnel.local_port);
+            return Ok(tunnel.descriptor());
+        }
+    }
+
+    let mut tunnel = launch_tunnel(&spec, spec.local_port, Arc::new(TunnelActivity::new()))?;
+
+    let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+    if let Some(existing) = tunnels.get_mut(&spec.key) {
+        // Another caller opened the same tunnel while this one was starting
+        let _ = tunnel.stop();
+        return Ok(existing.descriptor());
+    }
+    let descriptor = tunnel.descriptor();
+    tunnels.insert(spec.key, tunnel);
+    persist(&tunnels);
+    Ok(descriptor)
+}
+
+/// Register a gcloud tunnel process left by a previous run
+pub(crate) fn adopt_tunnel(spec: TunnelSpec, pid: u32, local_port: u16) -> Result<TunnelDescriptor> {
+    let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+    if tunnels.contains_key(&spec.key) {
+        return Err(anyhow!("Tunnel {} is already registered", spec.key));
+    }
+    tracing::info!(tunnel = %spec.key, pid = pid, local_port = local_port, "Adopting orphaned gcloud tunnel");
+    let mut tunnel = IapTunnel {
+        backend: TunnelBackend::Adopted(pid),
+        local_port,
+        spec,
+        activity: Arc::new(TunnelActivity::new()),
+    };
+    let descriptor = tunnel.descriptor();
+    tunnels.insert(descriptor.key.clone(), tunnel);
+    persist(&tunnels);
+    Ok(descriptor)
+}
+
+/// Mirror the registry to the state file when persistence is enabled
+fn persist(tunnels: &HashMap<TunnelKey, IapTunnel>) {
+    if let Err(e) = tunnel_state::save_if_enabled(tunnels.values().map(IapTunnel::record).collect()) {
+        tracing::warn!(error = %e, "Failed to save tunnel state");
+    }
+}
+
+/// Every registered tunnel, sorted by key
+pub fn list_tunnels() -> Result<Vec<TunnelDescriptor>> {
+    let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+    let mut list: Vec<TunnelDescriptor> = tunnels.values_mut().map(IapTunnel::descriptor).collect();
+    list.sort_by_key(|d| d.key.to_string());
+    Ok(list)
+}
+
+/// Create a tunnel for `spec` on a port chosen by `local_port`
+fn launch_tunnel(spec: &TunnelSpec, local_port: LocalPortSpec, activity: Arc<TunnelActivity>) -> Result<IapTunnel> {
+    match spec.backend {
+        TunnelBackendKind::Native => start_native_tunnel(spec, local_port, activity),
+        TunnelBackendKind::Gcloud => start_gcloud_tunnel(spec, local_port, activity),
+        TunnelBackendKind::Auto => match start_native_tunnel(spec, local_port, activity.clone()) {
+            Ok(tunnel) => Ok(tunnel),
+            Err(e) => {
+                tracing::warn!(
+                    tunnel = %spec.key,
+                    error = %e,
+                    "Native IAP tunnel unavailable, falling back to gcloud"
+                );
+                start_gcloud_tunnel(spec, local_port, activity)
+            }
+        },
+    }
+}
+
+/// Start a tunnel served by the in-process IAP relay
+///
+/// Opens one probe connection first (like gcloud's "Testing if tunnel
+/// connection works") so auth and firewall problems surface immediately.
+fn start_native_tunnel(spec: &TunnelSpec, local_port: LocalPortSpec, activity: Arc<TunnelActivity>) -> Result<IapTunnel> {
+    let key = &spec.key;
+    let target = IapTarget::new(&key.project, &key.zone, &key.instance, key.remote_port)?;
+
+    // Same runtime as the listener, which keeps refreshing tokens with this connector
+    let connector = runtime::block_on(async {
+        let connector = IapConnector::for_context(&key.context()).await?;
+        let probe = connector.connect(&target).await?;
+        drop(probe);
+        Ok::<_, anyhow::Error>(connector)
+    })?;
+
+    // Keep the listener bound from here on: no free-port race
+    let listener = bind_local_listener(key.bind_address, local_port)?;
+    let handle = spawn_native_listener(connector, target, listener, activity.clone())?;
+    let port = handle.local_port;
+
+    tracing::info!(
+        tunnel = %key,
+        local_port = port,
+        "Native IAP tunnel listening"
+    );
+
+    Ok(IapTunnel { backend: TunnelBackend::Native(handle), local_port: port, spec: spec.clone(), activity })
+}
+
+/// Start a tunnel backed by a `gcloud compute start-iap-tunnel` child process
+///
+/// gcloud binds the local port itself, so nothing is bound and released
+/// beforehand: for any free port it binds port 0 and reports the port it
+/// got, and a requested port that gcloud finds taken is handled by
+/// [`select_gcloud_port`].
+fn start_gcloud_tunnel(spec: &TunnelSpec, local_port: LocalPortSpec, activity: Arc<TunnelActivity>) -> Result<IapTunnel> {
+    select_gcloud_port(spec.key.bind_address, local_port, |port| {
+        spawn_gcloud_tunnel(spec, port, activity.clone())
+    })
+}
+
+/// Run `attempt` on the ports allowed by `ports` until gcloud gets one
+///
+/// `attempt(port)` returns `Ok(None)` when the port was taken by the time
+/// gcloud tried to bind it. That is a conflict for `Fixed`, a fallback to
+/// any free port for `Preferred`, and the next candidate for `Range`. Ports
+/// that are visibly taken are skipped without starting gcloud; that probe
+/// only ever rules ports out.
+fn select_gcloud_port<T>(
+    bind_address: IpAddr,
+    ports: LocalPortSpec,
+    mut attempt: impl FnMut(u16) -> Result<Option<T>>,
+) -> Result<T> {
+    let probe = |port: u16| TcpListener::bind((bind_address, port)).map(drop);
+    let in_use = |port: u16| bind_error(bind_address, port, ErrorKind::AddrInUse.into());
+    let any_port = |attempt: &mut dyn FnMut(u16) -> Result<Option<T>>| {
+        attempt(0)?.ok_or_else(|| anyhow!("gcloud could not listen on a free port on {}", bind_address))
+    };
+
+    match ports {
+        LocalPortSpec::Any => any_port(&mut attempt),
+        LocalPortSpec::Fixed(port) => {
+            probe(port).map_err(|e| bind_error(bind_address, port, e))?;
+            attempt(port)?.ok_or_else(|| in_use(port))
+        }
+        LocalPortSpec::Preferred(port) => {
+            if probe(port).is_ok() {
+                if let Some(started) = attempt(port)? {
+                    return Ok(started);
+                }
+            }
+            tracing::info!(port = port, "Preferred local port unavailable, using a free one");
+            any_port(&mut attempt)
+        }
+        LocalPortSpec::Range { start, end } => {
+            for port in start..=end {
+                if probe(port).is_err() {
+                    continue;
+                }
+                match attempt(port)? {
+                    Some(started) => return Ok(started),
+                    None => tracing::debug!(port = port, "Local port taken before gcloud bound it, trying the next one"),
+                }
+            }
+            Err(anyhow!("No free local port in {}-{} on {}", start, end, bind_address))
         }
     }
+}
 
-    let port = get_free_port()?;
+/// Start gcloud on `port` (0 = any free port)
+///
+/// `Ok(None)` if gcloud reported the port as already in use.
+fn spawn_gcloud_tunnel(spec: &TunnelSpec, port: u16, activity: Arc<TunnelActivity>) -> Result<Option<IapTunnel>> {
+    let key = &spec.key;
+    let (project, zone, instance, remote_port) =
+        (key.project.as_str(), key.zone.as_str(), key.instance.as_str(), key.remote_port);
+    let bind_host = match key.bind_address {
+        IpAddr::V6(ip) => format!("[{}]", ip),
+        ip => ip.to_string(),
+    };
     
-    let child = Command::new("gcloud")
+    let mut child = Command::new("gcloud")
         .args([
             "compute", 
             "start-iap-tunnel", 
             instance, 
             &remote_port.to_string(),
-            &format!("--local-host-port=localhost:{}", port),
+            &format!("--local-host-port={}:{}", bind_host, port),
             "--zone", zone,
             "--project", project
         ])
+        .args(key.context().args())
         .stdout(Stdio::null()) // Ignorar stdout por ahora
-        .stderr(Stdio::piped()) // Capturar stderr para logs si fuera necesario (no implementado lectura async aun)
+        .stderr(Stdio::piped())
         .spawn()
         .map_err(|e| anyhow!("Failed to spawn gcloud tunnel: {}", e))?;
+    let (startup_tx, startup_rx) = std::sync::mpsc::channel();
+    let stderr_log = Arc::new(StderrLog::default());
+    if let Some(stderr) = child.stderr.take() {
+        spawn_gcloud_stderr_reader(stderr, key.to_string(), stderr_log.clone(), startup_tx);
+    }
+    let failure = |symptom: &str| anyhow::Error::new(TunnelError::from_stderr(&key.to_string(), symptom, &stderr_log));
 
     // Store the tunnel immediately so we can check its health
-    let mut tunnel = IapTunnel { process: child, local_port: port };
-
-    // Wait briefly for tunnel to initialize
-    std::thread::sleep(std::time::Duration::from_millis(1000));
-
-    // IMPROVEMENT: Verify tunnel health before declaring success
-    if !tunnel.is_process_alive() {
-        return Err(anyhow!("Tunnel process died immediately after startup"));
-    }
+    let mut tunnel = IapTunnel {
+        backend: TunnelBackend::Gcloud(child, stderr_log.clone()),
+        local_port: port,
+        spec: spec.clone(),
+        activity,
+    };
 
-    // Wait up to 10 seconds for port to start listening (increased from 5s)
-    let mut port_ready = false;
     tracing::info!(
         instance = instance,
         port = port,
         "Waiting for tunnel port to start listening (max 10 seconds)..."
     );
 
+    // Wait up to 10 seconds for port to start listening
     for attempt in 0..20 {
-        if tunnel.is_port_listening() {
-            port_ready = true;
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        let alive = tunnel.is_process_alive();
+        // After an exit, give the reader a moment to pass on gcloud's last words
+        let wait = if alive { Duration::ZERO } else { Duration::from_millis(500) };
+        while let Ok(event) = startup_rx.recv_timeout(wait) {
+            match event {
+                GcloudStartup::Listening(listening) if tunnel.local_port == 0 => tunnel.local_port = listening,
+                GcloudStartup::Listening(_) => {}
+                GcloudStartup::PortInUse => {
+                    let _ = tunnel.stop();
+                    tracing::info!(instance = instance, port = port, "gcloud found the local port in use");
+                    return Ok(None);
+                }
+            }
+        }
+        if !alive {
+            return Err(failure("process exited before listening on a local port"));
+        }
+        if tunnel.local_port != 0 && tunnel.is_port_listening() {
             tracing::info!(
                 instance = instance,
-                port = port,
+                port = tunnel.local_port,
                 attempt = attempt + 1,
                 elapsed_ms = (attempt + 1) * 500,
                 "Port is now listening"
             );
-            break;
+            tracing::info!(
+                instance = instance,
+                remote_port = remote_port,
+                local_port = tunnel.local_port,
+                "Tunnel health check passed - process alive and port listening"
+            );
+            return Ok(Some(tunnel));
         }
-        std::thread::sleep(std::time::Duration::from_millis(500));
-    }
-
-    if !port_ready {
-        // Kill the process since it's not working
-        let _ = tunnel.stop();
-        tracing::error!(
-            instance = instance,
-            port = port,
-            "Tunnel port failed to listen after 10 seconds"
-        );
-        return Err(anyhow!(
-            "Tunnel process started but port {} is not listening after 10 seconds. \
-             Possible causes: IAP not enabled, firewall blocking, or slow network. \
-             Check logs and try SSH first to verify IAP works.",
-            port
-        ));
     }
 
-    tracing::info!(
+    // Kill the process since it's not working
+    let _ = tunnel.stop();
+    tracing::error!(
         instance = instance,
-        remote_port = remote_port,
-        local_port = port,
-        "Tunnel health check passed - process alive and port listening"
+        port = tunnel.local_port,
+        "Tunnel port failed to listen after 10 seconds"
     );
-
-    let tunnel_key = make_tunnel_key(instance, remote_port);
-    let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
-    tunnels.insert(tunnel_key, tunnel);
-
-    Ok(port)
+    Err(failure(&format!("port {} not listening after 10 seconds", tunnel.local_port)))
 }
 
-pub fn stop_tunnel(instance: &str, remote_port: u16) -> Result<()> {
-    let tunnel_key = make_tunnel_key(instance, remote_port);
+pub fn stop_tunnel(key: &TunnelKey) -> Result<()> {
     let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
-    if let Some(mut tunnel) = tunnels.remove(&tunnel_key) {
+    if let Some(mut tunnel) = tunnels.remove(key) {
         tracing::info!(
-            instance = instance,
-            remote_port = remote_port,
+            tunnel = %key,
             local_port = tunnel.local_port,
             "Stopping tunnel"
         );
-        tunnel.stop()?;
+        let stopped = tunnel.stop();
+        persist(&tunnels);
+        stopped?;
     } else {
         tracing::warn!(
-            instance = instance,
-            remote_port = remote_port,
+            tunnel = %key,
             "Attempted to stop non-existent tunnel"
         );
     }
@@ -180,32 +595,519 @@ pub fn stop_tunnel(instance: &str, remote_port: u16) -> Result<()> {
 
 /// Check if a tunnel is healthy (process alive + port listening)
 /// Returns true if healthy, false if dead/unhealthy, error if tunnel doesn't exist
-pub fn check_tunnel_health(instance: &str, remote_port: u16) -> Result<bool> {
-    let tunnel_key = make_tunnel_key(instance, remote_port);
+pub fn check_tunnel_health(key: &TunnelKey) -> Result<bool> {
     let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
 
-    if let Some(tunnel) = tunnels.get_mut(&tunnel_key) {
+    if let Some(tunnel) = tunnels.get_mut(key) {
         let is_healthy = tunnel.is_healthy();
 
         // If unhealthy, automatically clean up the dead tunnel
         if !is_healthy {
             tracing::warn!(
-                instance = instance,
-                remote_port = remote_port,
+                tunnel = %key,
                 "Tunnel is unhealthy - process died or port stopped listening"
             );
-            // We can't remove while holding the reference, so we'll mark it for removal
-            // by dropping the tunnel. The caller should call stop_tunnel to clean up.
+            // Left in place: if the supervisor is running it restarts the tunnel on
+            // the same local port, otherwise the caller should call stop_tunnel.
         }
 
         Ok(is_healthy)
     } else {
-        Err(anyhow!("No tunnel exists for instance '{}' on port {}", instance, remote_port))
+        Err(anyhow!("No tunnel exists for {}", key))
+    }
+}
+
+/// Relay a single IAP connection over stdin/stdout (SSH `ProxyCommand` mode)
+///
+/// No local port is opened. EOF on stdin stops the upload direction only;
+/// output from the instance keeps flowing until the relay closes the session.
+/// With `Auto`, falls back to `gcloud ... --listen-on-stdin` when the native
+/// relay cannot be set up (e.g. no Application Default Credentials).
+pub fn proxy_stdio(
+    project: &str,
+    zone: &str,
+    instance: &str,
+    remote_port: u16,
+    backend: TunnelBackendKind,
+    context: &GcloudContext,
+) -> Result<()> {
+    // SECURITY: Validate all inputs before passing to gcloud command
+    let target = IapTarget::new(project, zone, instance, remote_port)?;
+    context.validate()?;
+
+    if backend != TunnelBackendKind::Gcloud {
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|e| anyhow!("Failed to create tokio runtime: {}", e))?;
+        let result = rt.block_on(async {
+            let connector = IapConnector::for_context(context).await?;
+            let conn = connector.connect(&target).await?;
+            // Once connected, relay errors are final: stdin may already be consumed
+            Ok::<_, anyhow::Error>(
+                conn.with_half_close().relay_split(tokio::io::stdin(), tokio::io::stdout()).await
+            )
+        });
+        // stdin is read on a blocking thread that may never return; don't wait for it
+        rt.shutdown_background();
+
+        match result {
+            Ok(relay) => {
+                let stats = relay?;
+                tracing::debug!(
+                    instance = instance,
+                    bytes_sent = stats.bytes_sent,
+                    bytes_received = stats.bytes_received,
+                    "Stdio relay finished"
+                );
+                return Ok(());
+            }
+            Err(e) if backend == TunnelBackendKind::Auto => {
+                tracing::warn!(
+                    instance = instance,
+                    error = %e,
+                    "Native IAP relay unavailable, falling back to gcloud --listen-on-stdin"
+                );
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let status = Command::new("gcloud")
+        .args([
+            "compute",
+            "start-iap-tunnel",
+            instance,
+            &remote_port.to_string(),
+            "--listen-on-stdin",
+            "--zone", zone,
+            "--project", project,
+        ])
+        .args(context.args())
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .map_err(|e| anyhow!("Failed to spawn gcloud tunnel: {}", e))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("gcloud stdin tunnel exited with {}", status))
+    }
+}
+
+/// Tunnels that are registered but no longer healthy: (spec, local_port)
+pub(crate) fn unhealthy_tunnels() -> Result<Vec<(TunnelSpec, u16)>> {
+    let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+    Ok(tunnels
+        .values_mut()
+        .filter_map(|tunnel| {
+            if tunnel.is_healthy() {
+                None
+            } else {
+                Some((tunnel.spec.clone(), tunnel.local_port))
+            }
+        })
+        .collect())
+}
+
+/// Replace a dead tunnel with a fresh one bound to the same local port
+///
+/// The old process is stopped first so the port is free again. The lock is
+/// not held while the new tunnel starts; if the tunnel was stopped by the
+/// user in the meantime, the replacement is discarded.
+pub(crate) fn restart_tunnel(key: &TunnelKey) -> Result<u16> {
+    let (spec, local_port, activity) = {
+        let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+        let tunnel = tunnels
+            .get_mut(key)
+            .ok_or_else(|| anyhow!("Tunnel {} no longer exists", key))?;
+        tunnel.stop()?;
+        (tunnel.spec.clone(), tunnel.local_port, tunnel.activity.clone())
+    };
+
+    let mut replacement = launch_tunnel(&spec, LocalPortSpec::Fixed(local_port), activity)?;
+
+    let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+    match tunnels.get_mut(key) {
+        Some(slot) => {
+            *slot = replacement;
+            persist(&tunnels);
+            Ok(local_port)
+        }
+        None => {
+            let _ = replacement.stop();
+            Err(anyhow!("Tunnel {} was stopped while reconnecting", key))
+        }
+    }
+}
+
+/// Drop a tunnel the supervisor has given up on
+pub(crate) fn remove_tunnel(key: &TunnelKey) -> Result<()> {
+    let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+    if let Some(mut tunnel) = tunnels.remove(key) {
+        let stopped = tunnel.stop();
+        persist(&tunnels);
+        stopped?;
+    }
+    Ok(())
+}
+
+/// Traffic and connection metrics of one registered tunnel
+#[derive(Debug, Clone, Serialize)]
+pub struct TunnelActivityReport {
+    #[serde(flatten)]
+    pub key: TunnelKey,
+    pub local_port: u16,
+    pub native: bool,
+    /// Process/listener still running
+    pub alive: bool,
+    #[serde(flatten)]
+    pub activity: ActivitySnapshot,
+}
+
+/// Metrics snapshot of every registered tunnel, sorted by key
+///
+/// The native relay counts bytes, sessions and connect latency itself. For
+/// gcloud tunnels only the number of established connections on the local
+/// port is known, sampled here from the kernel socket table.
+pub fn tunnel_activity() -> Result<Vec<TunnelActivityReport>> {
+    let mut tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+    let mut reports: Vec<TunnelActivityReport> = tunnels
+        .values_mut()
+        .map(|tunnel| {
+            if !tunnel.is_native() {
+                if let Some(count) = established_connections(tunnel.local_port) {
+                    tunnel.activity.set_active_connections(count);
+                }
+            }
+            TunnelActivityReport {
+                key: tunnel.spec.key.clone(),
+                local_port: tunnel.local_port,
+                native: tunnel.is_native(),
+                alive: tunnel.is_process_alive(),
+                activity: tunnel.activity.snapshot(),
+            }
+        })
+        .collect();
+    reports.sort_by_key(|r| r.key.to_string());
+    Ok(reports)
+}
+
+/// Recent gcloud stderr of a tunnel, oldest first
+///
+/// Empty for native and adopted tunnels, whose output is not ours to read.
+pub fn tunnel_stderr(key: &TunnelKey) -> Result<Vec<String>> {
+    let tunnels = TUNNELS.lock().map_err(|_| anyhow!("Tunnel lock poisoned"))?;
+    let tunnel = tunnels.get(key).ok_or_else(|| anyhow!("No tunnel exists for {}", key))?;
+    Ok(match &tunnel.backend {
+        TunnelBackend::Gcloud(_, log) => log.lines(),
+        TunnelBackend::Native(_) | TunnelBackend::Adopted(_) => Vec::new(),
+    })
+}
+
+/// Stop every tunnel to one instance
+///
+/// All of them are unregistered even if stopping one fails. Returns how
+/// many were stopped cleanly, and the first error if any failed.
+pub(crate) fn stop_instance_tunnels(project: &str, zone: &str, instance: &str) -> (usize, Result<()>) {
+    let mut tunnels = match TUNNELS.lock() {
+        Ok(tunnels) => tunnels,
+        Err(_) => return (0, Err(anyhow!("Tunnel lock poisoned"))),
+    };
+    let keys: Vec<TunnelKey> = tunnels
+        .keys()
+        .filter(|k| k.project == project && k.zone == zone && k.instance == instance)
+        .cloned()
+        .collect();
+    // Stop them all even if one fails, so none is left in the state file
+    let mut stopped = 0;
+    let mut first_error = None;
+    for key in &keys {
+        if let Some(mut tunnel) = tunnels.remove(key) {
+            tracing::info!(instance = instance, local_port = tunnel.local_port, "Stopping tunnel");
+            match tunnel.stop() {
+                Ok(()) => stopped += 1,
+                Err(e) => {
+                    tracing::warn!(tunnel = %key, error = %e, "Failed to stop tunnel");
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
     }
+    persist(&tunnels);
+    (stopped, first_error.map_or(Ok(()), Err))
+}
+
+/// What gcloud reports on stderr while it starts listening
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GcloudStartup {
+    /// "Listening on port [N]."
+    Listening(u16),
+    /// The requested local port was taken
+    PortInUse,
 }
 
-fn get_free_port() -> Result<u16> {
-    let listener = TcpListener::bind("127.0.0.1:0")?;
-    let port = listener.local_addr()?.port();
-    Ok(port)
+/// Log gcloud's stderr into `log` and pass on startup events
+fn spawn_gcloud_stderr_reader(
+    stderr: ChildStderr,
+    tunnel: String,
+    log: Arc<StderrLog>,
+    startup_tx: std::sync::mpsc::Sender<GcloudStartup>,
+) {
+    let _ = std::thread::Builder::new()
+        .name("gcloud-tunnel-stderr".to_string())
+        .spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(|l| l.ok()) {
+                if line.starts_with("ERROR") || line.starts_with("WARNING") {
+                    tracing::warn!(tunnel = %tunnel, line = %line, "gcloud tunnel output");
+                } else {
+                    tracing::debug!(tunnel = %tunnel, line = %line, "gcloud tunnel output");
+                }
+                if let Some(event) = parse_startup_line(&line) {
+                    let _ = startup_tx.send(event);
+                }
+                log.push(line);
+            }
+        });
+}
+
+fn parse_startup_line(line: &str) -> Option<GcloudStartup> {
+    let line = line.trim();
+    if let Some(rest) = line.strip_prefix("Listening on port [") {
+        return rest.split(']').next()?.parse().ok().map(GcloudStartup::Listening);
+    }
+    // "Local port [N] is not available." or a raw EADDRINUSE from the socket layer
+    let lowered = line.to_lowercase();
+    ((lowered.contains("local port") && lowered.contains("is not available")) || lowered.contains("address already in use"))
+        .then_some(GcloudStartup::PortInUse)
+}
+
+/// Bind the local side of a tunnel according to `ports`
+fn bind_local_listener(bind_address: IpAddr, ports: LocalPortSpec) -> Result<TcpListener> {
+    let bind = |port: u16| TcpListener::bind((bind_address, port)).map_err(|e| bind_error(bind_address, port, e));
+    match ports {
+        LocalPortSpec::Any => bind(0),
+        LocalPortSpec::Fixed(port) => bind(port),
+        LocalPortSpec::Preferred(port) => bind(port).or_else(|e| {
+            tracing::info!(port = port, reason = %e, "Preferred local port unavailable, using a free one");
+            bind(0)
+        }),
+        LocalPortSpec::Range { start, end } => (start..=end)
+            .find_map(|port| TcpListener::bind((bind_address, port)).ok())
+            .ok_or_else(|| anyhow!("No free local port in {}-{} on {}", start, end, bind_address)),
+    }
+}
+
+/// Turn a bind failure into an error that says what to do about it
+fn bind_error(bind_address: IpAddr, port: u16, error: std::io::Error) -> anyhow::Error {
+    match error.kind() {
+        ErrorKind::AddrInUse => match port_owner(port) {
+            Some(owner) => anyhow!("Local port {} on {} is already in use by {}", port, bind_address, owner),
+            None => anyhow!("Local port {} on {} is already in use by another process", port, bind_address),
+        },
+        ErrorKind::AddrNotAvailable => anyhow!("{} is not an address of this machine", bind_address),
+        ErrorKind::PermissionDenied => {
+            anyhow!("Not allowed to listen on port {} (ports below 1024 need privileges)", port)
+        }
+        _ => anyhow!("Failed to listen on {}:{}: {}", bind_address, port, error),
+    }
+}
+
+/// "name (pid N)" of the process listening on `port` (Linux only)
+///
+/// Sockets of other users' processes cannot be resolved without privileges.
+fn port_owner(port: u16) -> Option<String> {
+    let mut inodes = Vec::new();
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        if let Ok(contents) = std::fs::read_to_string(table) {
+            inodes.extend(socket_rows(&contents).filter(|r| r.port == port && r.state == "0A").map(|r| r.inode.to_string()));
+        }
+    }
+    if inodes.is_empty() {
+        return None;
+    }
+
+    for process in std::fs::read_dir("/proc").ok()?.filter_map(|e| e.ok()) {
+        let Some(pid) = process.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else { continue };
+        let Ok(fds) = std::fs::read_dir(process.path().join("fd")) else { continue };
+        let owns = fds.filter_map(|fd| fd.ok()).any(|fd| {
+            std::fs::read_link(fd.path()).ok().and_then(|link| {
+                link.to_str()
+                    .and_then(|l| l.strip_prefix("socket:["))
+                    .and_then(|l| l.strip_suffix(']'))
+                    .map(|inode| inodes.iter().any(|i| i == inode))
+            }) == Some(true)
+        });
+        if owns {
+            let name = std::fs::read_to_string(process.path().join("comm")).unwrap_or_default();
+            return Some(format!("{} (pid {})", name.trim(), pid));
+        }
+    }
+    None
+}
+
+/// Accepted connections on `local_port` in ESTABLISHED state (Linux only)
+fn established_connections(local_port: u16) -> Option<u64> {
+    let mut found = false;
+    let mut count = 0;
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        if let Ok(contents) = std::fs::read_to_string(table) {
+            found = true;
+            count += count_established(&contents, local_port);
+        }
+    }
+    found.then_some(count)
+}
+
+/// Rows of a `/proc/net/tcp` table with local port `port` and state 01
+fn count_established(table: &str, port: u16) -> u64 {
+    socket_rows(table).filter(|r| r.port == port && r.state == "01").count() as u64
+}
+
+/// The columns of a `/proc/net/tcp` row this module needs
+struct SocketRow<'a> {
+    port: u16,
+    /// 01 = ESTABLISHED, 0A = LISTEN
+    state: &'a str,
+    inode: &'a str,
+}
+
+fn socket_rows(table: &str) -> impl Iterator<Item = SocketRow<'_>> {
+    table.lines().skip(1).filter_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let port = fields
+            .get(1)
+            .and_then(|addr| addr.rsplit_once(':'))
+            .and_then(|(_, p)| u16::from_str_radix(p, 16).ok())?;
+        Some(SocketRow { port, state: fields.get(3)?, inode: fields.get(9)? })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_established_connections() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:A2F1 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 1 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:A2F1 0100007F:D6A4 01 00000000:00000000 00:00000000 00000000  1000        0 2 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:D6A4 0100007F:A2F1 01 00000000:00000000 00:00000000 00000000  1000        0 3 1 0000000000000000 20 4 30 10 -1
+   3: 0100007F:A2F1 0100007F:D6B0 06 00000000:00000000 03:00000000 00000000     0        0 0 3 0000000000000000
+";
+        // Listening socket, the client end and TIME_WAIT rows don't count
+        assert_eq!(count_established(table, 0xA2F1), 1);
+        assert_eq!(count_established(table, 22), 0);
+        let listening: Vec<&str> = socket_rows(table).filter(|r| r.state == "0A").map(|r| r.inode).collect();
+        assert_eq!(listening, vec!["1"]);
+    }
+
+    #[test]
+    fn test_local_port_specs() {
+        let localhost = DEFAULT_BIND_ADDRESS;
+        let taken = TcpListener::bind((localhost, 0)).unwrap();
+        let taken_port = taken.local_addr().unwrap().port();
+
+        let err = bind_local_listener(localhost, LocalPortSpec::Fixed(taken_port)).unwrap_err().to_string();
+        assert!(err.contains(&format!("Local port {} on 127.0.0.1 is already in use", taken_port)), "{}", err);
+        // This test process holds the port, and can see its own sockets
+        assert!(err.contains(&format!("(pid {})", std::process::id())), "{}", err);
+
+        let fallback = bind_local_listener(localhost, LocalPortSpec::Preferred(taken_port)).unwrap();
+        assert_ne!(fallback.local_addr().unwrap().port(), taken_port);
+
+        let range = LocalPortSpec::Range { start: taken_port, end: taken_port.saturating_add(20) };
+        let in_range = bind_local_listener(localhost, range).unwrap().local_addr().unwrap().port();
+        assert!(in_range > taken_port && range.accepts(in_range));
+        assert!(bind_local_listener(localhost, LocalPortSpec::Range { start: taken_port, end: taken_port }).is_err());
+
+        assert!(LocalPortSpec::Range { start: 6000, end: 5000 }.validate().is_err());
+        assert!(LocalPortSpec::Fixed(0).validate().is_err());
+        assert!(!LocalPortSpec::Fixed(5432).accepts(5433));
+        assert_eq!(parse_startup_line("Listening on port [41235]."), Some(GcloudStartup::Listening(41235)));
+        assert_eq!(parse_startup_line("Testing if tunnel connection works."), None);
+        assert_eq!(
+            parse_startup_line("ERROR: (gcloud.compute.start-iap-tunnel) Local port [5432] is not available."),
+            Some(GcloudStartup::PortInUse)
+        );
+    }
+
+    #[test]
+    fn test_gcloud_port_taken_after_probe() {
+        let localhost = DEFAULT_BIND_ADDRESS;
+        let free_port = || TcpListener::bind((localhost, 0)).unwrap().local_addr().unwrap().port();
+
+        // Stand-in for gcloud: another socket grabs `stolen` between the
+        // probe and gcloud's own bind; every other port binds normally
+        fn gcloud(stolen: u16, thieves: &mut Vec<TcpListener>) -> impl FnMut(u16) -> Result<Option<TcpListener>> + '_ {
+            move |port| {
+                if port == stolen {
+                    thieves.push(TcpListener::bind((DEFAULT_BIND_ADDRESS, port)).unwrap());
+                }
+                match TcpListener::bind((DEFAULT_BIND_ADDRESS, port)) {
+                    Ok(listener) => Ok(Some(listener)),
+                    Err(e) if e.kind() == ErrorKind::AddrInUse => Ok(None),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+
+        let mut thieves = Vec::new();
+        let port = free_port();
+        let err = select_gcloud_port(localhost, LocalPortSpec::Fixed(port), gcloud(port, &mut thieves))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(&format!("Local port {} on 127.0.0.1 is already in use by", port)), "{}", err);
+        assert!(err.contains(&format!("(pid {})", std::process::id())), "{}", err);
+
+        let port = free_port();
+        let started = select_gcloud_port(localhost, LocalPortSpec::Preferred(port), gcloud(port, &mut thieves)).unwrap();
+        assert_ne!(started.local_addr().unwrap().port(), port);
+
+        // Find two consecutive free ports; the first is stolen after its probe
+        let (start, end) = loop {
+            let start = free_port();
+            if start < u16::MAX && TcpListener::bind((localhost, start + 1)).is_ok() {
+                break (start, start + 1);
+            }
+        };
+        let started = select_gcloud_port(localhost, LocalPortSpec::Range { start, end }, gcloud(start, &mut thieves)).unwrap();
+        assert_eq!(started.local_addr().unwrap().port(), end);
+    }
+
+    #[test]
+    fn test_stop_instance_tunnels_spares_vanished_and_reused_pids() {
+        // Adopted tunnels whose gcloud process is gone; one pid now belongs to this test
+        for (remote_port, pid) in [(22, i32::MAX as u32 - 22), (3389, std::process::id())] {
+            let key = TunnelKey::new("stop-all-project", "us-central1-a", "vanished-1", remote_port);
+            let spec = TunnelSpec { key, backend: TunnelBackendKind::Gcloud, local_port: LocalPortSpec::Any };
+            adopt_tunnel(spec, pid, 0).unwrap();
+        }
+
+        let (stopped, result) = stop_instance_tunnels("stop-all-project", "us-central1-a", "vanished-1");
+        result.unwrap();
+        assert_eq!(stopped, 2);
+        let left = list_tunnels().unwrap().into_iter().filter(|t| t.key.project == "stop-all-project").count();
+        assert_eq!(left, 0);
+    }
+
+    #[test]
+    fn test_tunnel_keys_distinguish_projects_and_accounts() {
+        let a = TunnelKey::new("project-a", "us-central1-a", "web-1", 22);
+        let b = TunnelKey::new("project-b", "us-central1-a", "web-1", 22);
+        assert_ne!(a, b);
+        assert_ne!(a, TunnelKey::new("project-a", "europe-west1-b", "web-1", 22));
+
+        let as_alice = a.clone().with_context(&GcloudContext::for_account("alice@example.com"));
+        assert_ne!(a, as_alice);
+        assert_eq!(as_alice.context().account.as_deref(), Some("alice@example.com"));
+        assert_ne!(a, a.clone().with_bind_address("0.0.0.0".parse().unwrap()));
+
+        assert_eq!(as_alice.to_string(), "alice@example.com/project-a/us-central1-a/web-1:22@127.0.0.1");
+        assert!(TunnelKey::new("project-a", "us-central1-a", "web-1;rm", 22).validate().is_err());
+
+        let json = serde_json::to_value(&a).unwrap();
+        assert_eq!(json["bind_address"], "127.0.0.1");
+        assert_eq!(serde_json::from_value::<TunnelKey>(json).unwrap(), a);
+    }
 }
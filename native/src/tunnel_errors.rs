@@ -0,0 +1,236 @@
+//! gcloud tunnel failure diagnosis
+//!
+//! `gcloud compute start-iap-tunnel` explains why it failed only on stderr.
+//! Every gcloud tunnel keeps its last lines in a [`StderrLog`], and startup
+//! failures are matched against the known IAP error messages so callers get
+//! a [`TunnelError`] with a remediation hint instead of a bare timeout.
+//!
+//! The error travels inside `anyhow::Error`; use
+//! `err.downcast_ref::<TunnelError>()` to get at the kind.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+
+/// Lines kept per tunnel; gcloud prints a few per connection at most
+pub const STDERR_LOG_LINES: usize = 200;
+
+/// Ring buffer with the most recent stderr lines of one gcloud tunnel
+#[derive(Debug, Default)]
+pub struct StderrLog {
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl StderrLog {
+    pub fn push(&self, line: String) {
+        if let Ok(mut lines) = self.lines.lock() {
+            if lines.len() == STDERR_LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+    }
+
+    /// Buffered lines, oldest first
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().map(|l| l.iter().cloned().collect()).unwrap_or_default()
+    }
+}
+
+/// Known reasons for a gcloud tunnel to fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TunnelFailureKind {
+    /// 4033: the caller lacks IAP access to the instance
+    NotAuthorized,
+    /// 4003: IAP reached the VPC but not the instance port
+    BackendUnreachable,
+    /// The IAP API is disabled in the project
+    IapApiDisabled,
+    /// No firewall rule lets IAP (35.235.240.0/20) in
+    FirewallBlocked,
+    /// The instance is stopped, suspended or being created
+    InstanceNotRunning,
+    /// gcloud credentials are missing or expired
+    CredentialsExpired,
+    /// Nothing recognisable on stderr
+    Unknown,
+}
+
+impl TunnelFailureKind {
+    pub fn description(&self) -> &'static str {
+        match self {
+            TunnelFailureKind::NotAuthorized => "not authorized to tunnel to the instance (IAP 4033)",
+            TunnelFailureKind::BackendUnreachable => "IAP could not connect to the instance port (IAP 4003)",
+            TunnelFailureKind::IapApiDisabled => "the Identity-Aware Proxy API is not enabled",
+            TunnelFailureKind::FirewallBlocked => "the firewall does not allow IAP traffic",
+            TunnelFailureKind::InstanceNotRunning => "the instance is not running",
+            TunnelFailureKind::CredentialsExpired => "gcloud credentials are missing or expired",
+            TunnelFailureKind::Unknown => "the tunnel failed to start",
+        }
+    }
+
+    /// What the user can do about it
+    pub fn hint(&self) -> &'static str {
+        match self {
+            TunnelFailureKind::NotAuthorized => {
+                "Grant roles/iap.tunnelResourceAccessor on the project or instance to the active account"
+            }
+            TunnelFailureKind::BackendUnreachable => {
+                "Check that the instance is running, the service listens on the remote port, \
+                 and a firewall rule allows ingress from 35.235.240.0/20"
+            }
+            TunnelFailureKind::IapApiDisabled => "Run: gcloud services enable iap.googleapis.com --project PROJECT",
+            TunnelFailureKind::FirewallBlocked => {
+                "Create a firewall rule allowing TCP ingress from 35.235.240.0/20 to the remote port"
+            }
+            TunnelFailureKind::InstanceNotRunning => "Start the instance and retry",
+            TunnelFailureKind::CredentialsExpired => "Run: gcloud auth login",
+            TunnelFailureKind::Unknown => "Check the gcloud output below and try SSH first to verify IAP works",
+        }
+    }
+}
+
+/// (kind, lowercase needles); checked in order, the more specific first
+const PATTERNS: &[(TunnelFailureKind, &[&str])] = &[
+    (TunnelFailureKind::IapApiDisabled, &["iap.googleapis.com", "identity-aware proxy api has not been used"]),
+    (TunnelFailureKind::CredentialsExpired, &["reauthentication", "gcloud auth login", "refresh token has expired"]),
+    (TunnelFailureKind::FirewallBlocked, &["firewall", "35.235.240.0/20"]),
+    (TunnelFailureKind::InstanceNotRunning, &["is not running", "not in running state", "status terminated", "status suspended"]),
+    (TunnelFailureKind::NotAuthorized, &["4033", "not authorized"]),
+    (TunnelFailureKind::BackendUnreachable, &["4003", "failed to connect to backend"]),
+];
+
+/// First known failure in `lines`, with the line that gave it away
+pub fn classify_stderr(lines: &[String]) -> Option<(TunnelFailureKind, String)> {
+    let lowered: Vec<String> = lines.iter().map(|l| l.to_lowercase()).collect();
+    PATTERNS.iter().find_map(|(kind, needles)| {
+        lowered
+            .iter()
+            .position(|line| needles.iter().any(|n| line.contains(n)))
+            .map(|i| (*kind, lines[i].trim().to_string()))
+    })
+}
+
+/// A gcloud tunnel that failed, with what gcloud said about it
+#[derive(Debug, Clone, Serialize)]
+pub struct TunnelError {
+    pub tunnel: String,
+    pub kind: TunnelFailureKind,
+    /// What lcc observed (e.g. "port not listening after 10 seconds")
+    pub symptom: String,
+    /// The stderr line that identified `kind`
+    pub detail: Option<String>,
+    /// Last stderr lines at the time of failure
+    pub stderr_tail: Vec<String>,
+}
+
+/// Lines of stderr quoted in the error message
+const TAIL_LINES: usize = 10;
+
+impl TunnelError {
+    /// Diagnose a failed startup from the tunnel's stderr
+    pub fn from_stderr(tunnel: &str, symptom: &str, log: &StderrLog) -> Self {
+        let lines = log.lines();
+        let (kind, detail) = match classify_stderr(&lines) {
+            Some((kind, line)) => (kind, Some(line)),
+            None => (TunnelFailureKind::Unknown, None),
+        };
+        let stderr_tail = lines[lines.len().saturating_sub(TAIL_LINES)..].to_vec();
+        TunnelError { tunnel: tunnel.to_string(), kind, symptom: symptom.to_string(), detail, stderr_tail }
+    }
+
+    pub fn hint(&self) -> &'static str {
+        self.kind.hint()
+    }
+}
+
+impl fmt::Display for TunnelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tunnel {} failed: {} ({})", self.tunnel, self.kind.description(), self.symptom)?;
+        match &self.detail {
+            Some(detail) => write!(f, "\n  gcloud: {}", detail)?,
+            None => {
+                for line in &self.stderr_tail {
+                    write!(f, "\n  gcloud: {}", line)?;
+                }
+            }
+        }
+        write!(f, "\n  Hint: {}", self.hint())
+    }
+}
+
+impl std::error::Error for TunnelError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(lines: &[&str]) -> StderrLog {
+        let log = StderrLog::default();
+        for line in lines {
+            log.push(line.to_string());
+        }
+        log
+    }
+
+    #[test]
+    fn test_classify_gcloud_failures() {
+        let cases = [
+            (
+                "ERROR: (gcloud.compute.start-iap-tunnel) Error while connecting [4033: 'not authorized'].",
+                TunnelFailureKind::NotAuthorized,
+            ),
+            (
+                "ERROR: (gcloud.compute.start-iap-tunnel) Error while connecting [4003: 'failed to connect to backend'].",
+                TunnelFailureKind::BackendUnreachable,
+            ),
+            (
+                "ERROR: (gcloud.compute.start-iap-tunnel) PERMISSION_DENIED: Cloud Identity-Aware Proxy API has not \
+                 been used in project 123 before or it is disabled. Enable it by visiting \
+                 https://console.developers.google.com/apis/api/iap.googleapis.com/overview?project=123",
+                TunnelFailureKind::IapApiDisabled,
+            ),
+            (
+                "No firewall rule allows ingress from 35.235.240.0/20 to port 5432",
+                TunnelFailureKind::FirewallBlocked,
+            ),
+            (
+                "ERROR: (gcloud.compute.start-iap-tunnel) Instance [db-1] is not running (status TERMINATED).",
+                TunnelFailureKind::InstanceNotRunning,
+            ),
+        ];
+        for (line, kind) in cases {
+            let lines = vec!["Testing if tunnel connection works.".to_string(), line.to_string()];
+            assert_eq!(classify_stderr(&lines), Some((kind, line.to_string())), "{}", line);
+        }
+        assert_eq!(classify_stderr(&["Listening on port [15432].".to_string()]), None);
+    }
+
+    #[test]
+    fn test_tunnel_error_from_stderr() {
+        let err = TunnelError::from_stderr(
+            "default/p/z/db-1:5432@127.0.0.1",
+            "process exited before listening",
+            &log(&["Testing if tunnel connection works.", "ERROR: Error while connecting [4033: 'not authorized']."]),
+        );
+        assert_eq!(err.kind, TunnelFailureKind::NotAuthorized);
+        let text = anyhow::Error::new(err).to_string();
+        assert!(text.contains("gcloud: ERROR: Error while connecting [4033"));
+        assert!(text.contains("Hint: Grant roles/iap.tunnelResourceAccessor"));
+
+        // Unrecognised output is quoted as-is
+        let err = TunnelError::from_stderr("t", "timeout", &log(&["something odd"]));
+        assert_eq!(err.kind, TunnelFailureKind::Unknown);
+        assert!(err.to_string().contains("gcloud: something odd"));
+
+        // The buffer keeps only the newest lines
+        let many = StderrLog::default();
+        for i in 0..STDERR_LOG_LINES + 5 {
+            many.push(i.to_string());
+        }
+        let lines = many.lines();
+        assert_eq!(lines.len(), STDERR_LOG_LINES);
+        assert_eq!(lines[0], "5");
+    }
+}
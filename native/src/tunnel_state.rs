@@ -0,0 +1,319 @@
+//! Tunnel persistence
+//!
+//! The tunnel registry lives in memory, so restarting the app lost every
+//! tunnel while the gcloud children it had spawned kept running unseen.
+//! With persistence enabled the registry is mirrored to a state file after
+//! every change. On the next launch [`restore_tunnels`] finds the gcloud
+//! processes of the previous run, adopts the ones still serving their port
+//! (or kills them), and reopens everything else on the same local ports
+//! when they are free.
+
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::tunnel::{self, LocalPortSpec, TunnelBackendKind, TunnelDescriptor, TunnelKey, TunnelSpec};
+
+/// One tunnel as written to the state file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistedTunnel {
+    #[serde(flatten)]
+    pub key: TunnelKey,
+    pub backend: TunnelBackendKind,
+    /// Port requested when the tunnel was opened
+    pub local_port: LocalPortSpec,
+    /// Port it was actually listening on
+    pub last_local_port: u16,
+    /// gcloud process serving the tunnel, if any
+    pub pid: Option<u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StateFile {
+    tunnels: Vec<PersistedTunnel>,
+}
+
+/// What to do with gcloud tunnels left running by a previous run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrphanPolicy {
+    /// Keep serving through the old process if its port still answers
+    #[default]
+    Adopt,
+    /// Always kill it and open a fresh tunnel
+    Kill,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RestoreFailure {
+    #[serde(flatten)]
+    pub key: TunnelKey,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RestoreReport {
+    /// Reopened from scratch
+    pub restored: Vec<TunnelDescriptor>,
+    /// Orphaned gcloud processes taken over as they were
+    pub adopted: Vec<TunnelDescriptor>,
+    /// Orphaned gcloud processes that were terminated
+    pub killed_orphans: Vec<u32>,
+    pub failed: Vec<RestoreFailure>,
+}
+
+lazy_static! {
+    /// State file in use; `None` = persistence disabled (e.g. short-lived CLI tunnels)
+    static ref STATE_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+/// `<config dir>/linux_cloud_connector/tunnels.json`
+pub fn default_state_path() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .ok_or_else(|| anyhow!("Could not determine config directory"))?
+        .join("linux_cloud_connector")
+        .join("tunnels.json"))
+}
+
+/// Mirror the tunnel registry to `path` from now on
+pub fn enable_persistence(path: PathBuf) -> Result<()> {
+    *STATE_PATH.lock().map_err(|_| anyhow!("Tunnel state lock poisoned"))? = Some(path);
+    Ok(())
+}
+
+pub fn disable_persistence() -> Result<()> {
+    *STATE_PATH.lock().map_err(|_| anyhow!("Tunnel state lock poisoned"))? = None;
+    Ok(())
+}
+
+/// Tunnels recorded in `path`; a missing file is an empty list
+pub fn load_state(path: &Path) -> Result<Vec<PersistedTunnel>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(serde_json::from_slice::<StateFile>(&bytes)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?
+            .tunnels),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(anyhow!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn save_state(path: &Path, mut tunnels: Vec<PersistedTunnel>) -> Result<()> {
+    tunnels.sort_by_key(|t| t.key.to_string());
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(&StateFile { tunnels })?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Called by the registry after every change
+pub(crate) fn save_if_enabled(tunnels: Vec<PersistedTunnel>) -> Result<()> {
+    let guard = STATE_PATH.lock().map_err(|_| anyhow!("Tunnel state lock poisoned"))?;
+    match guard.as_deref() {
+        Some(path) => save_state(path, tunnels),
+        None => Ok(()),
+    }
+}
+
+/// Bring back the tunnels of the previous run and keep persisting from now on
+///
+/// `path` defaults to [`default_state_path`]. Tunnels that asked for any
+/// free port come back on the port they had, if it is still free.
+pub fn restore_tunnels(path: Option<PathBuf>, policy: OrphanPolicy) -> Result<RestoreReport> {
+    let path = match path {
+        Some(path) => path,
+        None => default_state_path()?,
+    };
+    let records = load_state(&path)?;
+    // Only after reading: every reopened tunnel rewrites the file
+    enable_persistence(path)?;
+
+    let mut report = RestoreReport::default();
+    for record in records {
+        let spec = TunnelSpec {
+            key: record.key.clone(),
+            backend: record.backend,
+            local_port: match record.local_port {
+                LocalPortSpec::Any if record.last_local_port != 0 => LocalPortSpec::Preferred(record.last_local_port),
+                requested => requested,
+            },
+        };
+
+        if let Some(pid) = record.pid.filter(|pid| is_gcloud_tunnel_process(*pid, &record.key)) {
+            if policy == OrphanPolicy::Adopt && tunnel::is_port_listening(record.key.bind_address, record.last_local_port) {
+                match tunnel::adopt_tunnel(spec.clone(), pid, record.last_local_port) {
+                    Ok(descriptor) => {
+                        report.adopted.push(descriptor);
+                        continue;
+                    }
+                    Err(e) => tracing::warn!(tunnel = %record.key, error = %e, "Could not adopt orphaned tunnel"),
+                }
+            }
+            tracing::info!(tunnel = %record.key, pid = pid, "Terminating orphaned gcloud tunnel");
+            match terminate_process(pid) {
+                Ok(()) => report.killed_orphans.push(pid),
+                Err(e) => tracing::warn!(pid = pid, error = %e, "Failed to terminate orphaned tunnel"),
+            }
+        }
+
+        match tunnel::open_tunnel(spec) {
+            Ok(descriptor) => report.restored.push(descriptor),
+            Err(e) => {
+                tracing::warn!(tunnel = %record.key, error = %e, "Failed to restore tunnel");
+                report.failed.push(RestoreFailure { key: record.key, error: e.to_string() });
+            }
+        }
+    }
+
+    tracing::info!(
+        restored = report.restored.len(),
+        adopted = report.adopted.len(),
+        killed = report.killed_orphans.len(),
+        failed = report.failed.len(),
+        "Restored tunnels"
+    );
+    Ok(report)
+}
+
+/// Whether `pid` is still a `gcloud compute start-iap-tunnel` for `key`
+///
+/// Guards against the pid having been reused by an unrelated process.
+pub(crate) fn is_gcloud_tunnel_process(pid: u32, key: &TunnelKey) -> bool {
+    process_args(pid).is_some_and(|args| cmdline_matches(&args, key))
+}
+
+/// Whether `pid` is still an `lcc tunnel start` for `key`
+///
+/// Same guard as [`is_gcloud_tunnel_process`], for tunnels owned by another lcc.
+pub fn is_lcc_tunnel_process(pid: u32, key: &TunnelKey) -> bool {
+    process_args(pid).is_some_and(|args| lcc_cmdline_matches(&args, key))
+}
+
+/// Arguments of `pid`; `None` once it is gone (zombies have an empty cmdline)
+fn process_args(pid: u32) -> Option<Vec<String>> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    Some(
+        raw.split(|b| *b == 0)
+            .filter(|a| !a.is_empty())
+            .map(|a| String::from_utf8_lossy(a).into_owned())
+            .collect(),
+    )
+}
+
+/// `... start-iap-tunnel INSTANCE PORT ... --zone ZONE --project PROJECT`
+fn cmdline_matches(args: &[String], key: &TunnelKey) -> bool {
+    let Some(start) = args.iter().position(|a| a == "start-iap-tunnel") else { return false };
+    let flag = |name: &str| args.windows(2).find(|w| w[0] == name).map(|w| w[1].as_str());
+    args.get(start + 1) == Some(&key.instance)
+        && args.get(start + 2) == Some(&key.remote_port.to_string())
+        && flag("--zone") == Some(key.zone.as_str())
+        && flag("--project") == Some(key.project.as_str())
+}
+
+fn process_exists(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// `lcc ... tunnel start ... INSTANCE PORT --project PROJECT --zone ZONE`
+fn lcc_cmdline_matches(args: &[String], key: &TunnelKey) -> bool {
+    let Some(start) = args.windows(2).position(|w| w[0] == "tunnel" && w[1] == "start") else { return false };
+    let args = &args[start + 2..];
+    // clap takes both `--flag value` and `--flag=value`
+    let flag = |name: &str| {
+        let prefix = format!("{}=", name);
+        args.iter().enumerate().find_map(|(i, a)| match a.strip_prefix(&prefix) {
+            Some(value) => Some(value),
+            None if a == name => args.get(i + 1).map(String::as_str),
+            None => None,
+        })
+    };
+    args.contains(&key.instance)
+        && args.contains(&key.remote_port.to_string())
+        && flag("--project") == Some(key.project.as_str())
+        && flag("--zone") == Some(key.zone.as_str())
+}
+
+/// SIGTERM `pid` and wait up to 2 seconds for it to go away
+///
+/// A process that is already gone counts as terminated.
+pub fn terminate_process(pid: u32) -> Result<()> {
+    let status = std::process::Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map_err(|e| anyhow!("Failed to signal process {}: {}", pid, e))?;
+    if !status.success() {
+        if !process_exists(pid) {
+            return Ok(());
+        }
+        return Err(anyhow!("Failed to signal process {}", pid));
+    }
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while Instant::now() < deadline && process_exists(pid) {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_orphan_cmdline_matching() {
+        let key = TunnelKey::new("my-project", "us-central1-a", "db-1", 5432);
+        let cmdline = args(
+            "/usr/bin/python3 /usr/lib/google-cloud-sdk/lib/gcloud.py compute start-iap-tunnel db-1 5432 \
+             --local-host-port=127.0.0.1:5432 --zone us-central1-a --project my-project",
+        );
+        assert!(cmdline_matches(&cmdline, &key));
+        assert!(!cmdline_matches(&cmdline, &TunnelKey::new("other-project", "us-central1-a", "db-1", 5432)));
+        assert!(!cmdline_matches(&cmdline, &TunnelKey::new("my-project", "us-central1-a", "db-1", 22)));
+        assert!(!cmdline_matches(&args("vim db-1 5432 --zone us-central1-a --project my-project"), &key));
+
+        // This test process is not a gcloud tunnel
+        assert!(!is_gcloud_tunnel_process(std::process::id(), &key));
+    }
+
+    #[test]
+    fn test_lcc_cmdline_matching() {
+        let key = TunnelKey::new("my-project", "us-central1-a", "db-1", 5432);
+        let cmdline = args("/usr/local/bin/lcc tunnel start --project=my-project --zone us-central1-a db-1 5432 --supervise");
+        assert!(lcc_cmdline_matches(&cmdline, &key));
+        assert!(!lcc_cmdline_matches(&cmdline, &TunnelKey::new("my-project", "europe-west1-b", "db-1", 5432)));
+        assert!(!lcc_cmdline_matches(&cmdline, &TunnelKey::new("my-project", "us-central1-a", "db-2", 5432)));
+        assert!(!lcc_cmdline_matches(
+            &args("/usr/local/bin/lcc tunnel stop db-1 5432 --project my-project --zone us-central1-a"),
+            &key
+        ));
+        assert!(!is_lcc_tunnel_process(std::process::id(), &key));
+    }
+
+    #[test]
+    fn test_state_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("lcc-tunnel-state-{}.json", std::process::id()));
+        assert!(load_state(&path).unwrap().is_empty());
+
+        let tunnel = PersistedTunnel {
+            key: TunnelKey::new("my-project", "us-central1-a", "db-1", 5432),
+            backend: TunnelBackendKind::Gcloud,
+            local_port: LocalPortSpec::Fixed(5432),
+            last_local_port: 5432,
+            pid: Some(4242),
+        };
+        save_state(&path, vec![tunnel.clone()]).unwrap();
+        assert_eq!(load_state(&path).unwrap(), vec![tunnel]);
+
+        std::fs::write(&path, b"{ not json").unwrap();
+        assert!(load_state(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
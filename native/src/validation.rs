@@ -28,6 +28,44 @@ lazy_static! {
     static ref USERNAME_REGEX: Regex = Regex::new(
         r"^[a-z_][a-z0-9_-]{0,31}$"
     ).unwrap();
+
+    // gcloud account: user or service account email
+    static ref ACCOUNT_REGEX: Regex = Regex::new(
+        r"^[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(\.[A-Za-z0-9-]+)+$"
+    ).unwrap();
+
+    // gcloud configuration name: lowercase letter, then letters/digits/hyphens
+    static ref CONFIGURATION_NAME_REGEX: Regex = Regex::new(
+        r"^[a-z][a-z0-9-]{0,62}$"
+    ).unwrap();
+
+    // Resource label: key starts with a lowercase letter, values may be empty
+    static ref LABEL_KEY_REGEX: Regex = Regex::new(
+        r"^[a-z][a-z0-9_-]{0,62}$"
+    ).unwrap();
+    static ref LABEL_VALUE_REGEX: Regex = Regex::new(
+        r"^[a-z0-9_-]{0,63}$"
+    ).unwrap();
+
+    // Machine type: e2-standard-4, n2-custom-8-32768-ext, a3-highgpu-8g
+    static ref MACHINE_TYPE_REGEX: Regex = Regex::new(
+        r"^[a-z][a-z0-9-]{1,61}[a-z0-9]$"
+    ).unwrap();
+
+    // Resource Manager container: organizations/123 or folders/456
+    static ref RESOURCE_PARENT_REGEX: Regex = Regex::new(
+        r"^(organizations|folders)/[0-9]{1,32}$"
+    ).unwrap();
+
+    // GCP Region: a zone without the zone letter (us-central1, europe-west2)
+    static ref REGION_REGEX: Regex = Regex::new(
+        r"^[a-z]+-[a-z]+[0-9]+$"
+    ).unwrap();
+
+    // Schedule id: like an instance name, short enough for an "lcc-" policy name
+    static ref SCHEDULE_ID_REGEX: Regex = Regex::new(
+        r"^[a-z]([a-z0-9-]{0,47}[a-z0-9])?$"
+    ).unwrap();
 }
 
 /// Validates a GCP project ID
@@ -166,6 +204,146 @@ pub fn validate_username(username: &str) -> Result<()> {
     Ok(())
 }
 
+/// Validates a gcloud account (user or service account email)
+///
+/// # Examples
+/// ```
+/// assert!(validate_account("me@example.com").is_ok());
+/// assert!(validate_account("ci@my-project.iam.gserviceaccount.com").is_ok());
+/// assert!(validate_account("--impersonate-service-account=x").is_err());
+/// ```
+pub fn validate_account(account: &str) -> Result<()> {
+    if account.is_empty() {
+        return Err(anyhow!("Account cannot be empty"));
+    }
+
+    if account.len() > 254 || !ACCOUNT_REGEX.is_match(account) {
+        return Err(anyhow!("Invalid account '{}'. Expected an email address", account));
+    }
+
+    Ok(())
+}
+
+/// Validates a gcloud configuration name
+///
+/// # Examples
+/// ```
+/// assert!(validate_configuration_name("client-a").is_ok());
+/// assert!(validate_configuration_name("Client A").is_err());
+/// ```
+pub fn validate_configuration_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(anyhow!("Configuration name cannot be empty"));
+    }
+
+    if !CONFIGURATION_NAME_REGEX.is_match(name) {
+        return Err(anyhow!(
+            "Invalid configuration name '{}'. Must start with a lowercase letter and \
+             contain only lowercase letters/digits/hyphens",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validates a resource label `key=value` pair (used in list filters)
+///
+/// # Examples
+/// ```
+/// assert!(validate_label("env", "prod").is_ok());
+/// assert!(validate_label("env", "prod\" OR name:*").is_err());
+/// ```
+pub fn validate_label(key: &str, value: &str) -> Result<()> {
+    if !LABEL_KEY_REGEX.is_match(key) {
+        return Err(anyhow!(
+            "Invalid label key '{}'. Must start with a lowercase letter and \
+             contain only lowercase letters/digits/underscores/hyphens",
+            key
+        ));
+    }
+
+    if !LABEL_VALUE_REGEX.is_match(value) {
+        return Err(anyhow!("Invalid value '{}' for label '{}'", value, key));
+    }
+
+    Ok(())
+}
+
+/// Validates a machine type name
+///
+/// # Examples
+/// ```
+/// assert!(validate_machine_type("n2-custom-8-32768-ext").is_ok());
+/// assert!(validate_machine_type("zones/x/machineTypes/e2-micro").is_err());
+/// ```
+pub fn validate_machine_type(machine_type: &str) -> Result<()> {
+    if !MACHINE_TYPE_REGEX.is_match(machine_type) {
+        return Err(anyhow!(
+            "Invalid machine type '{}'. Expected a name like e2-standard-4",
+            machine_type
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validates a Resource Manager parent (`organizations/ID` or `folders/ID`)
+///
+/// # Examples
+/// ```
+/// assert!(validate_resource_parent("folders/123456").is_ok());
+/// assert!(validate_resource_parent("folders/123 OR state:*").is_err());
+/// ```
+pub fn validate_resource_parent(parent: &str) -> Result<()> {
+    if !RESOURCE_PARENT_REGEX.is_match(parent) {
+        return Err(anyhow!(
+            "Invalid parent '{}'. Expected organizations/ID or folders/ID",
+            parent
+        ));
+    }
+
+    Ok(())
+}
+
+/// Validates a GCP region name
+///
+/// # Examples
+/// ```
+/// assert!(validate_region("europe-west1").is_ok());
+/// assert!(validate_region("europe-west1-b").is_err()); // that's a zone
+/// ```
+pub fn validate_region(region: &str) -> Result<()> {
+    if !REGION_REGEX.is_match(region) {
+        return Err(anyhow!("Invalid region '{}'. Expected a name like us-central1", region));
+    }
+
+    Ok(())
+}
+
+/// Validates a start/stop schedule id
+///
+/// # Rules
+/// - 1-49 characters: lowercase letters, digits and hyphens
+/// - Must start with a letter and end with a letter or digit
+///
+/// # Examples
+/// ```
+/// assert!(validate_schedule_id("dev-office-hours").is_ok());
+/// assert!(validate_schedule_id("Office Hours").is_err());
+/// ```
+pub fn validate_schedule_id(id: &str) -> Result<()> {
+    if !SCHEDULE_ID_REGEX.is_match(id) {
+        return Err(anyhow!(
+            "Invalid schedule id '{}'. Use up to 49 lowercase letters, digits and hyphens, \
+             starting with a letter",
+            id
+        ));
+    }
+
+    Ok(())
+}
+
 /// Sanitizes a zone string from GCP API response
 ///
 /// GCP API returns zones as full URLs like:
@@ -333,5 +511,57 @@ mod tests {
         assert!(validate_zone("us-central1-a; ls -la").is_err());
         assert!(validate_username("user; rm -rf /").is_err());
         assert!(validate_username("user`whoami`").is_err());
+        assert!(validate_account("me@example.com; whoami").is_err());
+        assert!(validate_account("me@example.com --quiet").is_err());
+        assert!(validate_configuration_name("default;ls").is_err());
+    }
+
+    #[test]
+    fn test_accounts_and_configurations() {
+        assert!(validate_account("jordi@example.com").is_ok());
+        assert!(validate_account("ci@my-project.iam.gserviceaccount.com").is_ok());
+        assert!(validate_account("not-an-email").is_err());
+        assert!(validate_account("").is_err());
+
+        assert!(validate_configuration_name("default").is_ok());
+        assert!(validate_configuration_name("client-a2").is_ok());
+        assert!(validate_configuration_name("-flag").is_err());
+        assert!(validate_configuration_name("").is_err());
+    }
+
+    #[test]
+    fn test_labels() {
+        assert!(validate_label("env", "prod").is_ok());
+        assert!(validate_label("team_db", "").is_ok());
+        assert!(validate_label("Env", "prod").is_err());
+        assert!(validate_label("env", "prod\" OR name:*").is_err());
+    }
+
+    #[test]
+    fn test_resource_parents() {
+        assert!(validate_resource_parent("organizations/1234567890").is_ok());
+        assert!(validate_resource_parent("folders/42").is_ok());
+        assert!(validate_resource_parent("projects/42").is_err());
+        assert!(validate_resource_parent("folders/42 state:*").is_err());
+    }
+
+    #[test]
+    fn test_machine_types() {
+        assert!(validate_machine_type("e2-micro").is_ok());
+        assert!(validate_machine_type("custom-4-8192").is_ok());
+        assert!(validate_machine_type("E2-micro").is_err());
+        assert!(validate_machine_type("e2-micro --zone=x").is_err());
+    }
+
+    #[test]
+    fn test_regions_and_schedule_ids() {
+        assert!(validate_region("us-central1").is_ok());
+        assert!(validate_region("us-central1-a").is_err());
+        assert!(validate_region("us-central1;ls").is_err());
+
+        assert!(validate_schedule_id("office-hours").is_ok());
+        assert!(validate_schedule_id("a").is_ok());
+        assert!(validate_schedule_id("office-hours-").is_err());
+        assert!(validate_schedule_id(&"a".repeat(50)).is_err());
     }
 }
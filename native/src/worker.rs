@@ -0,0 +1,203 @@
+//! Background workers
+//!
+//! The tunnel supervisor, idle monitor, scheduler and metrics endpoint each
+//! run one named thread that polls until asked to stop, and most of them
+//! broadcast events to whoever subscribed. [`Worker`] owns such a thread
+//! and [`EventBus`] the subscriber list.
+
+use anyhow::{Result, anyhow};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often a sleeping worker checks whether it should stop
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Stop request seen by a worker thread
+#[derive(Clone, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn stop(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Sleep for `duration`, waking early on stop; returns whether stopped
+    ///
+    /// Sleeps in small steps so stopping a worker returns promptly.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while !self.is_stopped() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            std::thread::sleep(left.min(STOP_POLL_INTERVAL));
+        }
+        self.is_stopped()
+    }
+}
+
+struct RunningWorker<S> {
+    stop: StopSignal,
+    thread: JoinHandle<()>,
+    state: S,
+}
+
+/// Slot for one named background thread, plus state shared with its callers
+pub struct Worker<S = ()> {
+    name: &'static str,
+    running: Mutex<Option<RunningWorker<S>>>,
+}
+
+impl<S> Worker<S> {
+    pub fn new(name: &'static str) -> Self {
+        Self { name, running: Mutex::new(None) }
+    }
+
+    /// Spawn `body` on a thread named after the worker
+    ///
+    /// Returns false without spawning if the worker is already running.
+    pub fn start(&self, state: S, body: impl FnOnce(StopSignal) + Send + 'static) -> Result<bool> {
+        let mut guard = self.running.lock().map_err(|_| anyhow!("{} lock poisoned", self.name))?;
+        if self.reap(&mut guard) {
+            return Ok(false);
+        }
+
+        let stop = StopSignal::default();
+        let stop_flag = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name(self.name.to_string())
+            .spawn(move || body(stop_flag))
+            .map_err(|e| anyhow!("Failed to spawn {} thread: {}", self.name, e))?;
+
+        tracing::info!(worker = self.name, "Background worker started");
+        *guard = Some(RunningWorker { stop, thread, state });
+        Ok(true)
+    }
+
+    /// Ask the thread to stop and wait for it to exit
+    pub fn stop(&self) -> Result<()> {
+        let running = self.running.lock().map_err(|_| anyhow!("{} lock poisoned", self.name))?.take();
+        if let Some(running) = running {
+            running.stop.stop();
+            let _ = running.thread.join();
+            tracing::info!(worker = self.name, "Background worker stopped");
+        }
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.lock().map(|mut g| self.reap(&mut g)).unwrap_or(false)
+    }
+
+    /// Clear the slot if the thread has returned on its own; returns whether it still runs
+    fn reap(&self, running: &mut Option<RunningWorker<S>>) -> bool {
+        match running {
+            Some(worker) if worker.thread.is_finished() => {
+                tracing::warn!(worker = self.name, "Background worker exited on its own");
+                *running = None;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// Run `f` on the state of the running worker; `None` if it is not running
+    pub fn with_state<R>(&self, f: impl FnOnce(&S) -> R) -> Option<R> {
+        let mut guard = self.running.lock().ok()?;
+        if !self.reap(&mut guard) {
+            return None;
+        }
+        guard.as_ref().map(|running| f(&running.state))
+    }
+}
+
+/// Fan-out of events to every subscribed receiver
+pub struct EventBus<E> {
+    /// Message the events are logged under
+    label: &'static str,
+    subscribers: Mutex<Vec<Sender<E>>>,
+}
+
+impl<E: Clone + Debug> EventBus<E> {
+    pub fn new(label: &'static str) -> Self {
+        Self { label, subscribers: Mutex::new(Vec::new()) }
+    }
+
+    /// Receive every event from now on; the channel closes when the receiver is dropped
+    pub fn subscribe(&self) -> Receiver<E> {
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(tx);
+        }
+        rx
+    }
+
+    /// Log `event` and send it to every live subscriber
+    pub fn emit(&self, event: E) {
+        tracing::info!(event = ?event, "{}", self.label);
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_worker_lifecycle_and_events() {
+        let worker: Worker<u16> = Worker::new("test-worker");
+        let bus: Arc<EventBus<u32>> = Arc::new(EventBus::new("Test event"));
+        let events = bus.subscribe();
+
+        let thread_bus = bus.clone();
+        let started = worker
+            .start(7, move |stop| {
+                let mut ticks = 0;
+                while !stop.sleep(Duration::from_millis(10)) {
+                    ticks += 1;
+                    thread_bus.emit(ticks);
+                }
+            })
+            .unwrap();
+        assert!(started && worker.is_running());
+        assert!(!worker.start(8, |_| {}).unwrap());
+        assert_eq!(worker.with_state(|port| *port), Some(7));
+
+        assert_eq!(events.recv_timeout(Duration::from_secs(5)), Ok(1));
+        let stopping = Instant::now();
+        worker.stop().unwrap();
+        assert!(stopping.elapsed() < Duration::from_secs(1));
+        assert!(!worker.is_running());
+        assert_eq!(worker.with_state(|port| *port), None);
+
+        drop(events);
+        bus.emit(99);
+        assert!(bus.subscribers.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_worker_that_returns_early_can_restart() {
+        let worker: Worker = Worker::new("test-early-exit");
+        assert!(worker.start((), |_| {}).unwrap());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while worker.is_running() {
+            assert!(Instant::now() < deadline, "worker never reported its exit");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(worker.start((), |stop| while !stop.sleep(Duration::from_millis(10)) {}).unwrap());
+        assert!(worker.is_running());
+        worker.stop().unwrap();
+    }
+}